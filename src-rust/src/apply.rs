@@ -0,0 +1,445 @@
+//! `apply`: converge the repository to the Crossfile.
+//!
+//! The Crossfile is the desired state; metadata and git remotes are the
+//! actual one. [`CrossRepo::apply_changes`] compares them and lists what has
+//! to be added, updated or removed, without fetching. Each change is planned
+//! with [`CrossRepo::plan_apply`] right before it runs, so patches are
+//! planned against the remotes added by earlier changes. Apply never edits
+//! the Crossfile.
+
+use crate::crossfile::Statement;
+use crate::diff::FileChange;
+use crate::error::CrossError;
+use crate::filter::PathFilter;
+use crate::metadata::{Patch, RefKind};
+use crate::plan::{Op, Plan};
+use crate::repo::{CrossRepo, PatchOptions};
+use crate::spec::PatchSpec;
+use anyhow::{Context, Result, anyhow};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ApplyAction {
+    Add,
+    Update,
+    Remove,
+}
+
+impl ApplyAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApplyAction::Add => "add",
+            ApplyAction::Update => "update",
+            ApplyAction::Remove => "remove",
+        }
+    }
+
+    /// `+`, `~` or `-`, as in a plan summary.
+    pub fn symbol(&self) -> char {
+        match self {
+            ApplyAction::Add => '+',
+            ApplyAction::Update => '~',
+            ApplyAction::Remove => '-',
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ApplyKind {
+    Remote,
+    Patch,
+}
+
+impl ApplyKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApplyKind::Remote => "remote",
+            ApplyKind::Patch => "patch",
+        }
+    }
+}
+
+/// One difference between the Crossfile and the repository.
+#[derive(Serialize, Debug, Clone)]
+pub struct ApplyChange {
+    pub action: ApplyAction,
+    pub kind: ApplyKind,
+    /// Remote name or local path.
+    pub subject: String,
+    /// Current URL or spec; `None` when adding.
+    pub from: Option<String>,
+    /// URL or spec from the Crossfile; `None` when removing.
+    pub to: Option<String>,
+    /// Local edits to a vendored patch that updating or removing it discards.
+    pub local_changes: Vec<FileChange>,
+    #[serde(skip)]
+    statement: Option<Statement>,
+}
+
+impl fmt::Display for ApplyChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ", self.action.symbol())?;
+        if self.kind == ApplyKind::Remote {
+            write!(f, "remote ")?;
+        }
+        write!(f, "{}", self.subject)?;
+        match (&self.from, &self.to) {
+            (Some(from), Some(to)) => write!(f, ": {} -> {}", from, to),
+            (Some(value), None) | (None, Some(value)) => write!(f, " ({})", value),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// Spec of a patch with its filter, as shown in change summaries.
+fn describe(spec: &str, filter: &PathFilter) -> String {
+    if filter.is_empty() {
+        spec.to_string()
+    } else {
+        format!("{} [{}]", spec, filter)
+    }
+}
+
+/// Whether a recorded patch is what a Crossfile spec asks for. Specs without
+/// a ref accept whatever branch the patch was made from.
+fn patch_matches(patch: &Patch, spec: &PatchSpec, filter: &PathFilter) -> bool {
+    let same_ref = match spec.branch.as_deref().filter(|_| spec.branch_provided) {
+        None => true,
+        Some(name) => {
+            patch.branch == name
+                || (patch.ref_kind == RefKind::Commit && patch.branch.starts_with(name))
+        }
+    };
+    patch.remote == spec.remote
+        && patch.remote_path == spec.remote_path
+        && patch.filter == *filter
+        && same_ref
+}
+
+impl CrossRepo {
+    /// Differences between the Crossfile and the recorded patches and git
+    /// remotes, in the order they have to be applied: remotes, patch
+    /// removals, updates, additions and finally remotes nothing uses anymore.
+    pub fn apply_changes(&self) -> Result<Vec<ApplyChange>> {
+        let Some(crossfile) = self.load_crossfile()? else {
+            return Err(anyhow!("No Crossfile found; nothing to apply"));
+        };
+
+        // Later lines win, as they would when replayed.
+        let mut uses: Vec<(String, String)> = Vec::new();
+        let mut wanted: Vec<(String, PatchSpec, Statement)> = Vec::new();
+        for (line, statement) in crossfile.statements() {
+            match statement {
+                Statement::Use { name, url } => {
+                    uses.retain(|(n, _)| n != name);
+                    uses.push((name.clone(), url.clone()));
+                }
+                Statement::Patch { spec, .. } => {
+                    let parsed = PatchSpec::parse(spec)
+                        .with_context(|| format!("{}: {}", crossfile.location(line), statement))?;
+                    let local_path = statement.local_path().unwrap_or_default();
+                    wanted.retain(|(path, ..)| *path != local_path);
+                    wanted.push((local_path, parsed, statement.clone()));
+                }
+                Statement::Sync { .. } | Statement::Exec { .. } => {}
+            }
+        }
+
+        let (git, root) = (self.git(), self.root());
+        let metadata = self.load_metadata()?;
+        let remotes = self.remote_names()?;
+        let mut changes = Vec::new();
+        let change = |action, kind, subject: &str, from, to| ApplyChange {
+            action,
+            kind,
+            subject: subject.to_string(),
+            from,
+            to,
+            local_changes: Vec::new(),
+            statement: None,
+        };
+
+        for (name, url) in &uses {
+            if !remotes.contains(name) {
+                changes.push(change(
+                    ApplyAction::Add,
+                    ApplyKind::Remote,
+                    name,
+                    None,
+                    Some(url.clone()),
+                ));
+            } else {
+                let (fetch, _) = git.remote_urls(root, name)?;
+                if fetch != *url {
+                    changes.push(change(
+                        ApplyAction::Update,
+                        ApplyKind::Remote,
+                        name,
+                        Some(fetch),
+                        Some(url.clone()),
+                    ));
+                }
+            }
+        }
+
+        // Edits are only known when the worktree exists; without it there
+        // is no upstream copy to compare with.
+        let local_changes = |patch: &Patch| self.changed_files(patch).unwrap_or_default();
+
+        for patch in &metadata.patches {
+            if !wanted.iter().any(|(path, ..)| *path == patch.local_path) {
+                let mut removal = change(
+                    ApplyAction::Remove,
+                    ApplyKind::Patch,
+                    &patch.local_path,
+                    Some(describe(&patch.spec(), &patch.filter)),
+                    None,
+                );
+                removal.local_changes = local_changes(patch);
+                changes.push(removal);
+            }
+        }
+
+        let mut additions = Vec::new();
+        for (local_path, spec, statement) in &wanted {
+            let Statement::Patch {
+                spec: raw, filter, ..
+            } = statement
+            else {
+                continue;
+            };
+            let to = Some(describe(raw, filter));
+            match metadata.find(local_path) {
+                None => {
+                    let mut addition =
+                        change(ApplyAction::Add, ApplyKind::Patch, local_path, None, to);
+                    addition.statement = Some(statement.clone());
+                    additions.push(addition);
+                }
+                Some(patch) if !patch_matches(patch, spec, filter) => {
+                    let mut update = change(
+                        ApplyAction::Update,
+                        ApplyKind::Patch,
+                        local_path,
+                        Some(describe(&patch.spec(), &patch.filter)),
+                        to,
+                    );
+                    update.local_changes = local_changes(patch);
+                    update.statement = Some(statement.clone());
+                    changes.push(update);
+                }
+                Some(_) => {}
+            }
+        }
+        changes.append(&mut additions);
+
+        let declared: HashSet<&str> = uses.iter().map(|(name, _)| name.as_str()).collect();
+        let needed: HashSet<&str> = wanted.iter().map(|(_, s, _)| s.remote.as_str()).collect();
+        let mut unused: Vec<&str> = metadata
+            .patches
+            .iter()
+            .map(|p| p.remote.as_str())
+            .filter(|r| {
+                !declared.contains(r) && !needed.contains(r) && remotes.iter().any(|n| n == r)
+            })
+            .collect();
+        unused.sort();
+        unused.dedup();
+        for name in unused {
+            let (fetch, _) = git.remote_urls(root, name)?;
+            changes.push(change(
+                ApplyAction::Remove,
+                ApplyKind::Remote,
+                name,
+                Some(fetch),
+                None,
+            ));
+        }
+        Ok(changes)
+    }
+
+    /// Ops carrying out one change. Patches are planned like `patch`,
+    /// fetching their upstream, so this has to run after the changes before
+    /// it have been executed.
+    pub fn plan_apply(&self, change: &ApplyChange) -> Result<Plan> {
+        let mut plan = match (change.kind, change.action) {
+            (ApplyKind::Remote, ApplyAction::Remove) => {
+                self.plan_remove_remotes(std::slice::from_ref(&change.subject))
+            }
+            (ApplyKind::Remote, _) => {
+                let url = change.to.as_deref().unwrap_or_default();
+                self.plan_use(&change.subject, url)?.plan
+            }
+            (ApplyKind::Patch, ApplyAction::Remove) => self.plan_remove(&change.subject)?.plan,
+            (ApplyKind::Patch, action) => {
+                let Some(Statement::Patch {
+                    spec,
+                    local_path,
+                    filter,
+                }) = &change.statement
+                else {
+                    return Err(anyhow!("{}: no Crossfile patch to apply", change.subject));
+                };
+                let opts = PatchOptions {
+                    local_path: local_path.clone(),
+                    filter: filter.clone(),
+                    ..PatchOptions::default()
+                };
+                let mut plan = self.plan_patch(spec, &opts)?.plan;
+                let metadata = self.load_metadata()?;
+                // The old worktree goes unless the new spec reuses it or
+                // another patch of the old spec still needs it.
+                if action == ApplyAction::Update
+                    && let Some(old) = metadata.find(&change.subject)
+                    && !plan.ops.iter().any(
+                        |op| matches!(op, Op::Checkout { worktree, .. } if *worktree == old.worktree),
+                    )
+                    && !metadata.shares_worktree(&old.worktree, &old.local_path)
+                    && self.join(&old.worktree).exists()
+                {
+                    plan.ops.insert(0, Op::RemoveWorktree(old.worktree.clone()));
+                }
+                plan
+            }
+        };
+        plan.title = change.to_string();
+        // The Crossfile is the desired state: apply only reads it.
+        plan.ops
+            .retain(|op| !matches!(op, Op::AddToCrossfile(_) | Op::RemoveFromCrossfile { .. }));
+        Ok(plan)
+    }
+
+    /// Converge the repository to the Crossfile. Without `force`, fails with
+    /// [`CrossError::DirtyWorkingTree`] before changing anything if a patch
+    /// to update or remove has local edits.
+    pub fn apply(&self, force: bool) -> Result<Vec<ApplyChange>> {
+        let changes = self.apply_changes()?;
+        if !force {
+            check_local_changes(&changes)?;
+        }
+        for change in &changes {
+            self.execute(&self.plan_apply(change)?)?;
+        }
+        Ok(changes)
+    }
+}
+
+/// Fail for the first change that would discard local edits.
+pub fn check_local_changes(changes: &[ApplyChange]) -> Result<()> {
+    match changes.iter().find(|c| !c.local_changes.is_empty()) {
+        Some(change) => Err(CrossError::DirtyWorkingTree {
+            path: change.subject.clone(),
+        }
+        .into()),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ApplyAction;
+    use crate::error::CrossError;
+    use crate::git::testing::{URL, repo};
+    use crate::repo::PatchOptions;
+    use std::fs;
+
+    #[test]
+    fn apply_adds_updates_and_removes_patches() {
+        let (_dir, fake, repo) = repo();
+        fake.upstream_commit(
+            URL,
+            "main",
+            &[
+                ("docs/a.md", "a\n"),
+                ("src/x.rs", "x\n"),
+                ("lib/l.rs", "l\n"),
+            ],
+            "Add files",
+        );
+        fake.upstream_commit(URL, "dev", &[("docs/a.md", "dev\n")], "Dev docs");
+        repo.use_remote("demo", URL).unwrap();
+        let opts = |path: &str| PatchOptions {
+            local_path: Some(path.to_string()),
+            ..PatchOptions::default()
+        };
+        repo.add_patch("demo:docs", &opts("vendor/docs")).unwrap();
+        repo.add_patch("demo:src", &opts("vendor/src")).unwrap();
+        assert!(repo.apply_changes().unwrap().is_empty());
+
+        fs::write(
+            repo.crossfile_path(),
+            format!(
+                "cross use demo {}\ncross patch demo:dev:docs vendor/docs\ncross patch demo:lib vendor/lib\n",
+                URL
+            ),
+        )
+        .unwrap();
+        fs::write(repo.join("vendor/src/x.rs"), "edited\n").unwrap();
+        let changes = repo.apply_changes().unwrap();
+        let summary: Vec<String> = changes.iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            summary,
+            [
+                "- vendor/src (demo:main:src)",
+                "~ vendor/docs: demo:main:docs -> demo:dev:docs",
+                "+ vendor/lib (demo:lib)",
+            ]
+        );
+        assert_eq!(changes[0].action, ApplyAction::Remove);
+        assert_eq!(changes[0].local_changes.len(), 1);
+
+        let err = repo.apply(false).unwrap_err();
+        assert!(matches!(
+            CrossError::find(&err),
+            Some(CrossError::DirtyWorkingTree { path }) if path == "vendor/src"
+        ));
+        assert!(repo.join("vendor/src").exists());
+
+        repo.apply(true).unwrap();
+        assert!(repo.apply_changes().unwrap().is_empty());
+        assert!(!repo.join("vendor/src").exists());
+        assert_eq!(
+            fs::read_to_string(repo.join("vendor/docs/a.md")).unwrap(),
+            "dev\n"
+        );
+        assert!(repo.join("vendor/lib/l.rs").exists());
+        let crossfile = fs::read_to_string(repo.crossfile_path()).unwrap();
+        assert_eq!(crossfile.lines().count(), 3);
+        assert_eq!(fake.worktrees().len(), 2);
+    }
+
+    #[test]
+    fn apply_update_keeps_a_worktree_another_patch_uses() {
+        let (_dir, fake, repo) = repo();
+        fake.upstream_commit(URL, "main", &[("docs/a.md", "a\n")], "Add docs");
+        fake.upstream_commit(URL, "dev", &[("docs/a.md", "dev\n")], "Dev docs");
+        repo.use_remote("demo", URL).unwrap();
+        let opts = |path: &str| PatchOptions {
+            local_path: Some(path.to_string()),
+            ..PatchOptions::default()
+        };
+        repo.add_patch("demo:docs", &opts("vendor/a")).unwrap();
+        let shared = repo.add_patch("demo:docs", &opts("vendor/b")).unwrap();
+
+        fs::write(
+            repo.crossfile_path(),
+            format!(
+                "cross use demo {}\ncross patch demo:dev:docs vendor/a\ncross patch demo:main:docs vendor/b\n",
+                URL
+            ),
+        )
+        .unwrap();
+        repo.apply(false).unwrap();
+        assert_eq!(
+            fs::read_to_string(repo.join("vendor/a/a.md")).unwrap(),
+            "dev\n"
+        );
+        assert!(repo.join(&shared.worktree).join("docs/a.md").exists());
+        assert_eq!(fake.worktrees().len(), 2);
+        assert!(repo.apply_changes().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,736 @@
+//! Crossfile grammar.
+//!
+//! A Crossfile is a line oriented list of `cross` commands. Each non-blank,
+//! non-comment line is `<prefix> <command> <args...>` where the prefix is one
+//! of `cross`, `git cross` or `just cross` and the command is one of `use`,
+//! `patch`, `sync` or `exec`. Arguments follow shell quoting rules and a `#`
+//! outside quotes starts a trailing comment. Anything else is rejected: shell
+//! snippets must be spelled out explicitly with `cross exec`.
+//!
+//! The Crossfile is also edited by commands (`patch`, `remove`, ...). Edits
+//! address lines by what they describe: `use` lines by remote name, `patch`
+//! lines by local path. Every other line, its comments and its prefix are
+//! kept as written.
+
+use crate::filter::PathFilter;
+use crate::spec::PatchSpec;
+use crate::util::{normalize_local_path, write_atomic};
+use anyhow::{Result, anyhow};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Command prefix a Crossfile line was written with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Prefix {
+    Cross,
+    GitCross,
+    JustCross,
+}
+
+impl Prefix {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Prefix::Cross => "cross",
+            Prefix::GitCross => "git cross",
+            Prefix::JustCross => "just cross",
+        }
+    }
+}
+
+/// A single Crossfile command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Statement {
+    /// `use <name> <url>`
+    Use { name: String, url: String },
+    /// `patch <remote[:ref]:path> [local_path] [--include <glob>]... [--exclude <glob>]...`
+    Patch {
+        spec: String,
+        local_path: Option<String>,
+        filter: PathFilter,
+    },
+    /// `sync [path]`
+    Sync { path: Option<String> },
+    /// `exec <command...>`
+    Exec { args: Vec<String> },
+}
+
+impl Statement {
+    /// Whether `other` describes the same Crossfile entry: remotes are keyed
+    /// by name and patches by their effective local path, so re-recording a
+    /// patch with an autodetected branch does not produce a duplicate line.
+    pub fn same_entry(&self, other: &Statement) -> bool {
+        match (self, other) {
+            (Statement::Use { name: a, .. }, Statement::Use { name: b, .. }) => a == b,
+            (
+                Statement::Patch {
+                    spec: a_spec,
+                    local_path: a_local,
+                    ..
+                },
+                Statement::Patch {
+                    spec: b_spec,
+                    local_path: b_local,
+                    ..
+                },
+            ) => {
+                effective_local_path(a_spec, a_local.as_deref())
+                    == effective_local_path(b_spec, b_local.as_deref())
+            }
+            _ => self == other,
+        }
+    }
+
+    /// Whether recording `new` has to rewrite this line of the same entry. A
+    /// ref spelled out in `new` does not replace an autodetected one.
+    fn superseded_by(&self, new: &Statement) -> bool {
+        match (self, new) {
+            (Statement::Use { url: old, .. }, Statement::Use { url: new, .. }) => old != new,
+            (
+                Statement::Patch {
+                    spec: old_spec,
+                    filter: old_filter,
+                    ..
+                },
+                Statement::Patch {
+                    spec: new_spec,
+                    filter: new_filter,
+                    ..
+                },
+            ) => {
+                let (Ok(old), Ok(new)) = (PatchSpec::parse(old_spec), PatchSpec::parse(new_spec))
+                else {
+                    return old_spec != new_spec || old_filter != new_filter;
+                };
+                old.remote != new.remote
+                    || old.remote_path != new.remote_path
+                    || (old.branch_provided && new.branch_provided && old.branch != new.branch)
+                    || old_filter != new_filter
+            }
+            _ => false,
+        }
+    }
+
+    /// Local path of a `patch` statement; `None` for other statements.
+    pub fn local_path(&self) -> Option<String> {
+        match self {
+            Statement::Patch {
+                spec, local_path, ..
+            } => Some(effective_local_path(spec, local_path.as_deref())),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Statement::Use { name, url } => write!(f, "use {} {}", quote(name), quote(url)),
+            Statement::Patch {
+                spec,
+                local_path,
+                filter,
+            } => {
+                write!(f, "patch {}", quote(spec))?;
+                if let Some(local_path) = local_path {
+                    write!(f, " {}", quote(local_path))?;
+                }
+                for arg in filter.args() {
+                    write!(f, " {}", quote(&arg))?;
+                }
+                Ok(())
+            }
+            Statement::Sync { path } => {
+                write!(f, "sync")?;
+                if let Some(path) = path {
+                    write!(f, " {}", quote(path))?;
+                }
+                Ok(())
+            }
+            Statement::Exec { args } => {
+                write!(f, "exec")?;
+                for arg in args {
+                    write!(f, " {}", quote(arg))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Parsed form of one physical Crossfile line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LineKind {
+    Blank,
+    Comment,
+    Command {
+        prefix: Prefix,
+        statement: Statement,
+    },
+    /// A line that does not parse, kept verbatim by
+    /// [`Crossfile::load_for_edit`].
+    Invalid,
+}
+
+#[derive(Debug, Clone)]
+pub struct Line {
+    /// 1-based line number.
+    pub number: usize,
+    pub raw: String,
+    pub kind: LineKind,
+}
+
+impl Line {
+    /// Parse one line of Crossfile text. Errors carry no location; callers
+    /// that know the file and line number add it.
+    pub fn parse(raw: &str) -> Result<LineKind> {
+        let trimmed = raw.trim();
+        if trimmed.is_empty() {
+            return Ok(LineKind::Blank);
+        }
+        if trimmed.starts_with('#') {
+            return Ok(LineKind::Comment);
+        }
+
+        let code = strip_comment(trimmed);
+        let tokens = shell_words::split(code).map_err(|e| anyhow!("{}", e))?;
+        let mut tokens = tokens.into_iter();
+
+        let prefix = match tokens.next().as_deref() {
+            Some("cross") => Prefix::Cross,
+            Some(tool @ ("git" | "just")) => {
+                if tokens.next().as_deref() != Some("cross") {
+                    return Err(not_a_command(trimmed));
+                }
+                if tool == "git" {
+                    Prefix::GitCross
+                } else {
+                    Prefix::JustCross
+                }
+            }
+            _ => return Err(not_a_command(trimmed)),
+        };
+
+        let command = tokens
+            .next()
+            .ok_or_else(|| anyhow!("missing command after '{}'", prefix.as_str()))?;
+        let args: Vec<String> = tokens.collect();
+
+        let statement = match command.as_str() {
+            "use" => match <[String; 2]>::try_from(args) {
+                Ok([name, url]) => Statement::Use { name, url },
+                Err(_) => return Err(anyhow!("'use' expects <name> <url>")),
+            },
+            "patch" => parse_patch(args)?,
+            "sync" => {
+                if args.len() > 1 {
+                    return Err(anyhow!("'sync' expects at most one path"));
+                }
+                Statement::Sync {
+                    path: args.into_iter().next(),
+                }
+            }
+            "exec" => {
+                if args.is_empty() {
+                    return Err(anyhow!("'exec' expects a command"));
+                }
+                Statement::Exec { args }
+            }
+            other => {
+                return Err(anyhow!(
+                    "unsupported command '{}' (expected use, patch, sync or exec)",
+                    other
+                ));
+            }
+        };
+
+        Ok(LineKind::Command { prefix, statement })
+    }
+}
+
+impl Line {
+    /// Replace the command of a command line, keeping its prefix,
+    /// indentation and trailing comment.
+    fn rewrite(&mut self, statement: &Statement) {
+        let LineKind::Command { prefix, .. } = self.kind else {
+            return;
+        };
+        let indent = &self.raw[..self.raw.len() - self.raw.trim_start().len()];
+        let trimmed = self.raw.trim();
+        let comment = &trimmed[strip_comment(trimmed).len()..];
+        self.raw = format!("{}{} {}{}", indent, prefix.as_str(), statement, comment);
+        self.kind = LineKind::Command {
+            prefix,
+            statement: statement.clone(),
+        };
+    }
+}
+
+fn parse_patch(args: Vec<String>) -> Result<Statement> {
+    let usage = || {
+        anyhow!(
+            "'patch' expects <remote[:ref]:path> [local_path] [--include <glob>]... [--exclude <glob>]..."
+        )
+    };
+    let mut positional = Vec::new();
+    let mut filter = PathFilter::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => {
+                (flag.to_string(), Some(value.to_string()))
+            }
+            _ => (arg.clone(), None),
+        };
+        let target = match flag.as_str() {
+            "--include" => &mut filter.include,
+            "--exclude" => &mut filter.exclude,
+            _ if arg.starts_with("--") => return Err(anyhow!("unknown 'patch' option '{}'", arg)),
+            _ => {
+                positional.push(arg);
+                continue;
+            }
+        };
+        let value = inline
+            .or_else(|| args.next())
+            .ok_or_else(|| anyhow!("'{}' expects a glob", flag))?;
+        target.push(value);
+    }
+    filter.matcher()?;
+
+    if positional.is_empty() || positional.len() > 2 {
+        return Err(usage());
+    }
+    let mut positional = positional.into_iter();
+    Ok(Statement::Patch {
+        spec: positional.next().unwrap_or_default(),
+        local_path: positional.next(),
+        filter,
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct Crossfile {
+    pub path: PathBuf,
+    pub lines: Vec<Line>,
+}
+
+impl Crossfile {
+    /// Create an empty Crossfile at `path`. Returns `false` if one already exists.
+    pub fn init(path: &Path) -> Result<bool> {
+        if path.exists() {
+            return Ok(false);
+        }
+        std::fs::write(path, "# git-cross configuration\n")?;
+        Ok(true)
+    }
+
+    pub fn load(path: &Path) -> Result<Crossfile> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+        Crossfile::parse(path, &content)
+    }
+
+    /// Parse a whole Crossfile. The first invalid line aborts parsing with
+    /// an error of the form `<path>:<line>: <reason>`.
+    pub fn parse(path: &Path, content: &str) -> Result<Crossfile> {
+        let mut lines = Vec::new();
+        for (idx, raw) in content.lines().enumerate() {
+            let number = idx + 1;
+            let kind =
+                Line::parse(raw).map_err(|e| anyhow!("{}:{}: {}", path.display(), number, e))?;
+            lines.push(Line {
+                number,
+                raw: raw.to_string(),
+                kind,
+            });
+        }
+        Ok(Crossfile {
+            path: path.to_path_buf(),
+            lines,
+        })
+    }
+
+    /// Load `path` for editing. Unlike [`Crossfile::load`], a missing file is
+    /// empty and lines that do not parse are kept as they are (they never
+    /// match an entry), leaving them for `replay` to report.
+    pub fn load_for_edit(path: &Path) -> Result<Crossfile> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(anyhow!("Failed to read {}: {}", path.display(), e)),
+        };
+        let lines = content
+            .lines()
+            .enumerate()
+            .map(|(idx, raw)| Line {
+                number: idx + 1,
+                raw: raw.to_string(),
+                kind: Line::parse(raw).unwrap_or(LineKind::Invalid),
+            })
+            .collect();
+        Ok(Crossfile {
+            path: path.to_path_buf(),
+            lines,
+        })
+    }
+
+    /// Record `statement`. The line of the same entry is rewritten in place
+    /// (keeping its prefix, indentation and trailing comment) when the
+    /// statement changed it; a new entry is appended with the prefix of the
+    /// last command line. Returns whether the Crossfile changed.
+    pub fn record(&mut self, statement: &Statement) -> bool {
+        let existing = self.lines.iter_mut().find(|line| {
+            matches!(&line.kind, LineKind::Command { statement: current, .. } if current.same_entry(statement))
+        });
+        match existing {
+            Some(line) => {
+                if let LineKind::Command {
+                    statement: current, ..
+                } = &line.kind
+                    && !current.superseded_by(statement)
+                {
+                    return false;
+                }
+                line.rewrite(statement);
+            }
+            None => {
+                let prefix = self
+                    .lines
+                    .iter()
+                    .rev()
+                    .find_map(|line| match &line.kind {
+                        LineKind::Command { prefix, .. } => Some(*prefix),
+                        _ => None,
+                    })
+                    .unwrap_or(Prefix::Cross);
+                self.lines.push(Line {
+                    number: self.lines.len() + 1,
+                    raw: format!("{} {}", prefix.as_str(), statement),
+                    kind: LineKind::Command {
+                        prefix,
+                        statement: statement.clone(),
+                    },
+                });
+            }
+        }
+        true
+    }
+
+    /// The `patch` statement vendoring into `local_path`, if any.
+    pub fn find_patch(&self, local_path: &str) -> Option<&Statement> {
+        let local_path = normalize_local_path(local_path);
+        self.statements()
+            .map(|(_, statement)| statement)
+            .find(|statement| {
+                statement
+                    .local_path()
+                    .is_some_and(|path| normalize_local_path(&path) == local_path)
+            })
+    }
+
+    /// Rewrite the `patch` line of `local_path` as `statement`, keeping its
+    /// prefix and comments, even where [`Crossfile::record`] would keep the
+    /// old line. Returns whether such a line exists.
+    pub fn replace_patch(&mut self, local_path: &str, statement: &Statement) -> bool {
+        let local_path = normalize_local_path(local_path);
+        let line = self.lines.iter_mut().find(|line| match &line.kind {
+            LineKind::Command { statement, .. } => statement
+                .local_path()
+                .is_some_and(|path| normalize_local_path(&path) == local_path),
+            _ => false,
+        });
+        match line {
+            Some(line) => {
+                line.rewrite(statement);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Drop the `patch` lines vendoring into exactly `local_path`; comments
+    /// and other patches stay. Returns whether any line was removed.
+    pub fn remove_patch(&mut self, local_path: &str) -> bool {
+        let local_path = normalize_local_path(local_path);
+        let before = self.lines.len();
+        self.lines.retain(|line| match &line.kind {
+            LineKind::Command { statement, .. } => statement
+                .local_path()
+                .is_none_or(|path| normalize_local_path(&path) != local_path),
+            _ => true,
+        });
+        for (idx, line) in self.lines.iter_mut().enumerate() {
+            line.number = idx + 1;
+        }
+        self.lines.len() != before
+    }
+
+    /// Write the lines back to [`Crossfile::path`].
+    pub fn save(&self) -> Result<()> {
+        write_atomic(&self.path, self.to_string().as_bytes())
+    }
+
+    /// Iterate over command lines as `(line, statement)` pairs.
+    pub fn statements(&self) -> impl Iterator<Item = (&Line, &Statement)> {
+        self.lines.iter().filter_map(|line| match &line.kind {
+            LineKind::Command { statement, .. } => Some((line, statement)),
+            _ => None,
+        })
+    }
+
+    /// `<path>:<line>` label used when reporting errors for a line.
+    pub fn location(&self, line: &Line) -> String {
+        format!("{}:{}", self.path.display(), line.number)
+    }
+}
+
+impl fmt::Display for Crossfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in &self.lines {
+            writeln!(f, "{}", line.raw)?;
+        }
+        Ok(())
+    }
+}
+
+fn not_a_command(line: &str) -> anyhow::Error {
+    anyhow!(
+        "expected a 'cross' command, found '{}' (use 'cross exec' to run shell commands)",
+        line
+    )
+}
+
+/// Drop a trailing `# comment` that is not inside quotes.
+fn strip_comment(line: &str) -> &str {
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    let mut prev_ws = true;
+    for (idx, ch) in line.char_indices() {
+        if escaped {
+            escaped = false;
+            prev_ws = false;
+            continue;
+        }
+        match quote {
+            Some(q) if ch == q => quote = None,
+            Some('"') if ch == '\\' => escaped = true,
+            Some(_) => {}
+            None => match ch {
+                '\\' => escaped = true,
+                '\'' | '"' => quote = Some(ch),
+                '#' if prev_ws => return line[..idx].trim_end(),
+                _ => {}
+            },
+        }
+        prev_ws = ch.is_whitespace();
+    }
+    line
+}
+
+fn quote(arg: &str) -> String {
+    shell_words::quote(arg).into_owned()
+}
+
+/// Local path a patch line vendors into, defaulting like `cross patch` does.
+/// An unparsable spec keys the line by its text, which matches no patch.
+fn effective_local_path(spec: &str, local_path: Option<&str>) -> String {
+    match local_path {
+        Some(local_path) => normalize_local_path(local_path),
+        None => PatchSpec::parse(spec)
+            .map(|spec| spec.default_local_path())
+            .unwrap_or_else(|_| spec.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Crossfile, Line, LineKind, Prefix, Statement, strip_comment};
+    use crate::spec::PatchSpec;
+    use std::fs;
+    use std::path::Path;
+
+    const CONTENT: &str = "\
+# vendored code
+just cross use demo https://example.com/demo.git
+
+  git cross patch demo:main:lib vendor/lib  # core library
+git cross patch demo:lib-extra vendor/lib-extra
+this line is not a command
+";
+
+    fn crossfile(dir: &tempfile::TempDir) -> Crossfile {
+        let path = dir.path().join("Crossfile");
+        fs::write(&path, CONTENT).unwrap();
+        Crossfile::load_for_edit(&path).unwrap()
+    }
+
+    fn patch(spec: &str, local_path: &str) -> Statement {
+        Statement::Patch {
+            spec: spec.to_string(),
+            local_path: Some(local_path.to_string()),
+            filter: Default::default(),
+        }
+    }
+
+    fn command(raw: &str) -> (Prefix, Statement) {
+        match Line::parse(raw).unwrap() {
+            LineKind::Command { prefix, statement } => (prefix, statement),
+            other => panic!("{:?} is not a command: {:?}", raw, other),
+        }
+    }
+
+    fn parse_error(raw: &str) -> String {
+        Line::parse(raw).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn parse_accepts_every_prefix() {
+        let sync = Statement::Sync { path: None };
+        assert_eq!(command("cross sync"), (Prefix::Cross, sync.clone()));
+        assert_eq!(
+            command("  git cross sync"),
+            (Prefix::GitCross, sync.clone())
+        );
+        assert_eq!(command("just cross sync"), (Prefix::JustCross, sync));
+        assert_eq!(Line::parse("   ").unwrap(), LineKind::Blank);
+        assert_eq!(Line::parse("  # cross sync").unwrap(), LineKind::Comment);
+    }
+
+    #[test]
+    fn parse_splits_quoted_arguments_and_drops_trailing_comments() {
+        let (_, statement) = command("cross use demo https://example.com/demo.git # upstream");
+        assert_eq!(
+            statement,
+            Statement::Use {
+                name: "demo".to_string(),
+                url: "https://example.com/demo.git".to_string(),
+            }
+        );
+        let (_, statement) = command(r#"cross exec "echo '# not a comment'" 'a b' # done"#);
+        assert_eq!(
+            statement,
+            Statement::Exec {
+                args: vec!["echo '# not a comment'".to_string(), "a b".to_string()],
+            }
+        );
+        let (_, statement) = command("cross patch demo:lib 'vendor/my lib' --exclude '*.md'");
+        match statement {
+            Statement::Patch {
+                spec,
+                local_path,
+                filter,
+            } => {
+                assert_eq!(spec, "demo:lib");
+                assert_eq!(local_path.as_deref(), Some("vendor/my lib"));
+                assert_eq!(filter.exclude, ["*.md"]);
+            }
+            other => panic!("not a patch: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn strip_comment_ignores_hashes_in_quotes_and_words() {
+        assert_eq!(strip_comment("cross sync # later"), "cross sync");
+        assert_eq!(
+            strip_comment("cross sync vendor/a#b"),
+            "cross sync vendor/a#b"
+        );
+        assert_eq!(
+            strip_comment(r#"cross exec "a # b""#),
+            r#"cross exec "a # b""#
+        );
+        assert_eq!(
+            strip_comment(r#"cross exec 'a \' # b"#),
+            r#"cross exec 'a \'"#
+        );
+        assert_eq!(strip_comment(r"cross exec a\ #b"), r"cross exec a\ #b");
+    }
+
+    #[test]
+    fn parse_rejects_bare_and_malformed_lines() {
+        assert!(parse_error("use demo https://example.com").contains("expected a 'cross' command"));
+        assert!(parse_error("git remote -v").contains("use 'cross exec'"));
+        assert!(parse_error("just cross").contains("missing command after 'just cross'"));
+        assert!(parse_error("cross use demo").contains("'use' expects <name> <url>"));
+        assert!(parse_error("cross sync a b").contains("at most one path"));
+        assert!(parse_error("cross exec").contains("'exec' expects a command"));
+        assert!(parse_error("cross fetch demo").contains("unsupported command 'fetch'"));
+        assert!(parse_error("cross exec 'unterminated").contains("quote"));
+    }
+
+    #[test]
+    fn parse_errors_point_at_the_file_and_line() {
+        let content = "# upstream\ncross use demo https://example.com\necho hi > shell.txt\n";
+        let err = Crossfile::parse(Path::new("Crossfile"), content).unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("Crossfile:3: expected a 'cross' command"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn local_path_defaults_like_patch_specs() {
+        let statement = |raw: &str| command(raw).1.local_path();
+        assert_eq!(
+            statement("cross patch demo:v1:docs/guide/").as_deref(),
+            Some("guide")
+        );
+        assert_eq!(
+            statement("cross patch demo:docs ./vendor/docs/").as_deref(),
+            Some("vendor/docs")
+        );
+        let spec = PatchSpec::parse("demo:main:a:b").unwrap();
+        assert_eq!(
+            statement("cross patch demo:main:a:b"),
+            Some(spec.default_local_path())
+        );
+    }
+
+    #[test]
+    fn remove_patch_matches_the_local_path_exactly() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut crossfile = crossfile(&dir);
+        assert!(crossfile.remove_patch("./vendor/lib/"));
+        assert!(!crossfile.remove_patch("vendor/lib"));
+        assert_eq!(
+            crossfile.to_string(),
+            CONTENT.replace(
+                "  git cross patch demo:main:lib vendor/lib  # core library\n",
+                ""
+            )
+        );
+    }
+
+    #[test]
+    fn record_rewrites_entries_in_place_and_appends_with_the_file_prefix() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut crossfile = crossfile(&dir);
+        assert!(!crossfile.record(&patch("demo:main:lib", "vendor/lib")));
+        assert!(!crossfile.record(&patch("demo:main:lib-extra", "vendor/lib-extra")));
+        assert_eq!(crossfile.to_string(), CONTENT);
+
+        assert!(crossfile.record(&patch("demo:v2:lib", "vendor/lib")));
+        assert!(crossfile.record(&Statement::Use {
+            name: "demo".to_string(),
+            url: "https://example.com/moved.git".to_string(),
+        }));
+        assert!(crossfile.record(&patch("demo:docs", "vendor/docs")));
+        let expected = CONTENT
+            .replace(
+                "just cross use demo https://example.com/demo.git",
+                "just cross use demo https://example.com/moved.git",
+            )
+            .replace(
+                "  git cross patch demo:main:lib vendor/lib  # core library",
+                "  git cross patch demo:v2:lib vendor/lib  # core library",
+            )
+            + "git cross patch demo:docs vendor/docs\n";
+        assert_eq!(crossfile.to_string(), expected);
+
+        crossfile.save().unwrap();
+        assert_eq!(fs::read_to_string(&crossfile.path).unwrap(), expected);
+    }
+}
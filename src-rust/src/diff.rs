@@ -0,0 +1,194 @@
+//! `diff`: local edits of a patch relative to its upstream content.
+
+use crate::error::CrossError;
+use crate::metadata::Patch;
+use crate::repo::CrossRepo;
+use crate::tree::{files, read_entry, same_file};
+use anyhow::Result;
+use serde::Serialize;
+use std::fs;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    /// Present locally but not upstream.
+    Added,
+    Modified,
+    /// Present upstream but removed locally.
+    Deleted,
+}
+
+impl ChangeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChangeKind::Added => "added",
+            ChangeKind::Modified => "modified",
+            ChangeKind::Deleted => "deleted",
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct FileChange {
+    /// Path relative to the patch directory; the file name for single-file
+    /// patches.
+    pub path: String,
+    pub change: ChangeKind,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct PatchDiff {
+    pub local_path: String,
+    pub files: Vec<FileChange>,
+    /// Unified diff from upstream to local content.
+    pub patch: String,
+}
+
+impl CrossRepo {
+    /// Repo relative paths of one file of a patch in the worktree and
+    /// locally; `rel` is ignored for single-file patches.
+    fn diff_paths(&self, patch: &Patch, rel: &str) -> (String, String) {
+        if patch.file {
+            let upstream = format!("{}/{}", patch.worktree, patch.remote_path);
+            return (upstream, patch.local_path.clone());
+        }
+        (
+            format!("{}/{}", self.upstream_dir(patch), rel),
+            format!("{}/{}", patch.local_path, rel),
+        )
+    }
+
+    /// Worktree directory holding the remote path of a patch.
+    fn upstream_dir(&self, patch: &Patch) -> String {
+        match patch.remote_path.as_str() {
+            "." => patch.worktree.clone(),
+            remote_path => format!("{}/{}", patch.worktree, remote_path),
+        }
+    }
+
+    /// Every file that differs between the worktree copy of the remote path
+    /// and the local copy, before filtering.
+    fn all_changed_files(&self, patch: &Patch) -> Result<Vec<FileChange>> {
+        if !self.join(&patch.worktree).exists() {
+            return Err(CrossError::WorktreeMissing {
+                path: patch.local_path.clone(),
+            }
+            .into());
+        }
+
+        if patch.file {
+            let (upstream, local) = self.diff_paths(patch, "");
+            let change = if fs::symlink_metadata(self.join(&local)).is_err() {
+                Some(ChangeKind::Deleted)
+            } else if !same_file(&self.join(&upstream), &self.join(&local))? {
+                Some(ChangeKind::Modified)
+            } else {
+                None
+            };
+            // Single files are reported under their own name.
+            let path = patch
+                .local_path
+                .rsplit('/')
+                .next()
+                .unwrap_or(&patch.local_path)
+                .to_string();
+            return Ok(change
+                .map(|change| FileChange { path, change })
+                .into_iter()
+                .collect());
+        }
+
+        let upstream_files = files(&self.join(&self.upstream_dir(patch)))?;
+        let local_files = files(&self.join(&patch.local_path))?;
+        let mut changes = Vec::new();
+        for rel in upstream_files.union(&local_files) {
+            let change = match (upstream_files.contains(rel), local_files.contains(rel)) {
+                (true, false) => ChangeKind::Deleted,
+                (false, true) => ChangeKind::Added,
+                _ => {
+                    let (upstream, local) = self.diff_paths(patch, rel);
+                    if same_file(&self.join(&upstream), &self.join(&local))? {
+                        continue;
+                    }
+                    ChangeKind::Modified
+                }
+            };
+            changes.push(FileChange {
+                path: rel.clone(),
+                change,
+            });
+        }
+        Ok(changes)
+    }
+
+    /// Files of the patch (after include/exclude filters) that differ from upstream.
+    pub fn changed_files(&self, patch: &Patch) -> Result<Vec<FileChange>> {
+        let mut files = self.all_changed_files(patch)?;
+        let matcher = patch.filter.matcher()?;
+        files.retain(|f| matcher.matches(&f.path));
+        Ok(files)
+    }
+
+    /// Files and unified diff from upstream content to the local copy of a patch.
+    pub fn diff(&self, patch: &Patch) -> Result<PatchDiff> {
+        let files = self.changed_files(patch)?;
+        let mut text = String::new();
+        for file in &files {
+            let (upstream, local) = self.diff_paths(patch, &file.path);
+            let old = (file.change != ChangeKind::Added).then_some(upstream.as_str());
+            let new = (file.change != ChangeKind::Deleted).then_some(local.as_str());
+            text.push_str(&self.unified_diff(old, new)?);
+        }
+        Ok(PatchDiff {
+            local_path: patch.local_path.clone(),
+            files,
+            patch: text,
+        })
+    }
+
+    /// Unified diff between two repo relative files; `None` is a missing side.
+    fn unified_diff(&self, old: Option<&str>, new: Option<&str>) -> Result<String> {
+        let read = |path: Option<&str>| -> Result<Vec<u8>> {
+            path.map(|p| read_entry(&self.join(p)))
+                .transpose()
+                .map(Option::unwrap_or_default)
+        };
+        let (old_content, new_content) = (read(old)?, read(new)?);
+        // A diff of buffers cannot tell an empty file from a missing one, so
+        // the file header is written here.
+        let (a, b) = (
+            old.or(new).unwrap_or_default(),
+            new.or(old).unwrap_or_default(),
+        );
+        let mut out = format!("diff --git a/{} b/{}\n", a, b);
+        match (old, new) {
+            (None, _) => out.push_str("new file mode 100644\n--- /dev/null\n"),
+            (_, None) => out.push_str("deleted file mode 100644\n"),
+            _ => {}
+        }
+        if old.is_some() {
+            out.push_str(&format!("--- a/{}\n", a));
+        }
+        match new {
+            Some(_) => out.push_str(&format!("+++ b/{}\n", b)),
+            None => out.push_str("+++ /dev/null\n"),
+        }
+        let hunks = self
+            .git()
+            .diff_hunks(self.root(), &old_content, &new_content)?;
+        if hunks.starts_with("Binary files ") {
+            // The backend only saw buffers; name the real files.
+            let side = |path: Option<&str>, prefix| {
+                path.map_or("/dev/null".to_string(), |p| format!("{}/{}", prefix, p))
+            };
+            out.push_str(&format!(
+                "Binary files {} and {} differ\n",
+                side(old, "a"),
+                side(new, "b")
+            ));
+        } else {
+            out.push_str(&hunks);
+        }
+        Ok(out)
+    }
+}
@@ -0,0 +1,435 @@
+//! `doctor`: cross-check the sources of git-cross state.
+//!
+//! Five sources describe the same patches and have to agree: the metadata,
+//! the Crossfile, the git remotes, the worktrees registered with git and the
+//! directories under `.git/cross/worktrees`. Every disagreement becomes a
+//! [`Finding`]. Findings that can be repaired from the other sources carry
+//! the ops doing so; [`CrossRepo::plan_doctor_fix`] collects them.
+
+use crate::crossfile::Statement;
+use crate::metadata::Patch;
+use crate::plan::{Op, Plan};
+use crate::repo::{CrossRepo, WORKTREES_REL_PATH};
+use anyhow::Result;
+use serde::{Serialize, Serializer};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// How bad a finding is, most severe first.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// Commands on the patch or remote fail until it is repaired.
+    Error,
+    /// The sources disagree, but commands still work.
+    Warning,
+    /// Worth knowing, nothing to repair.
+    Info,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "info",
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Finding {
+    pub severity: Severity,
+    /// What the finding is about: a local path, remote or worktree.
+    pub subject: String,
+    pub message: String,
+    /// Ops repairing it; empty when it needs a manual fix.
+    #[serde(serialize_with = "op_strings")]
+    pub fix: Vec<Op>,
+    /// What to do when there is no automatic fix.
+    pub hint: Option<String>,
+}
+
+impl Finding {
+    fn new(severity: Severity, subject: &str, message: String) -> Finding {
+        Finding {
+            severity,
+            subject: subject.to_string(),
+            message,
+            fix: Vec::new(),
+            hint: None,
+        }
+    }
+
+    fn fix(mut self, ops: Vec<Op>) -> Finding {
+        self.fix = ops;
+        self
+    }
+
+    fn hint(mut self, hint: impl Into<String>) -> Finding {
+        self.hint = Some(hint.into());
+        self
+    }
+
+    pub fn fixable(&self) -> bool {
+        !self.fix.is_empty()
+    }
+}
+
+fn op_strings<S: Serializer>(ops: &[Op], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(ops.iter().map(Op::to_string))
+}
+
+impl CrossRepo {
+    /// Cross-check metadata, Crossfile, git remotes, registered worktrees
+    /// and worktree directories. Findings are sorted by severity.
+    pub fn doctor(&self) -> Result<Vec<Finding>> {
+        let (git, root) = (self.git(), self.root());
+        let patches = self.patches()?;
+        let mut findings = Vec::new();
+
+        let mut uses: BTreeMap<String, String> = BTreeMap::new();
+        let mut recorded: BTreeSet<String> = BTreeSet::new();
+        let mut crossfile_patches = Vec::new();
+        match self.load_crossfile() {
+            Ok(crossfile) => {
+                for (_, statement) in crossfile.iter().flat_map(|c| c.statements()) {
+                    match statement {
+                        Statement::Use { name, url } => {
+                            uses.insert(name.clone(), url.clone());
+                        }
+                        Statement::Patch { .. } => {
+                            let local_path = statement.local_path().unwrap_or_default();
+                            recorded.insert(local_path.clone());
+                            crossfile_patches.push(local_path);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Err(e) => findings.push(
+                Finding::new(Severity::Error, "Crossfile", format!("{:#}", e))
+                    .hint("fix the Crossfile line"),
+            ),
+        }
+
+        let mut remotes: BTreeMap<String, String> = BTreeMap::new();
+        for name in git.remote_names(root)? {
+            let (fetch, _) = git.remote_urls(root, &name)?;
+            remotes.insert(name, fetch);
+        }
+
+        let registered: BTreeSet<String> = git
+            .list_worktrees(root)?
+            .iter()
+            .filter_map(|path| self.relative(path))
+            .filter(|rel| rel.starts_with(WORKTREES_REL_PATH))
+            .collect();
+        let on_disk: BTreeSet<String> = fs::read_dir(self.join(WORKTREES_REL_PATH))
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .filter(|e| e.path().is_dir())
+                    .map(|e| format!("{}/{}", WORKTREES_REL_PATH, e.file_name().to_string_lossy()))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        // Remotes used by patches or declared in the Crossfile.
+        let used: BTreeSet<&str> = patches.iter().map(|p| p.remote.as_str()).collect();
+        let names: BTreeSet<&str> = used
+            .iter()
+            .copied()
+            .chain(uses.keys().map(String::as_str))
+            .collect();
+        for name in names {
+            let declared = uses.get(name);
+            match (remotes.get(name), declared) {
+                (None, _) => {
+                    let severity = if used.contains(name) {
+                        Severity::Error
+                    } else {
+                        Severity::Warning
+                    };
+                    let finding =
+                        Finding::new(severity, name, "remote is not configured in git".into());
+                    findings.push(match declared {
+                        Some(url) => finding.fix(vec![Op::SetRemote {
+                            name: name.to_string(),
+                            url: url.clone(),
+                            exists: false,
+                        }]),
+                        None => finding.hint(format!("run 'cross use {} <url>'", name)),
+                    });
+                }
+                (Some(actual), Some(url)) if actual != url => findings.push(
+                    Finding::new(
+                        Severity::Warning,
+                        name,
+                        format!("remote points at {}, the Crossfile uses {}", actual, url),
+                    )
+                    .fix(vec![Op::SetRemote {
+                        name: name.to_string(),
+                        url: url.clone(),
+                        exists: true,
+                    }]),
+                ),
+                (Some(actual), None) => findings.push(
+                    Finding::new(
+                        Severity::Warning,
+                        name,
+                        "remote is not recorded in the Crossfile".into(),
+                    )
+                    .fix(vec![Op::AddToCrossfile(Statement::Use {
+                        name: name.to_string(),
+                        url: actual.clone(),
+                    })]),
+                ),
+                (Some(_), Some(_)) if !used.contains(name) => findings.push(Finding::new(
+                    Severity::Info,
+                    name,
+                    "remote is not used by any patch".into(),
+                )),
+                _ => {}
+            }
+        }
+
+        let mut moved: BTreeSet<&str> = BTreeSet::new();
+        for patch in &patches {
+            let subject = patch.local_path.as_str();
+            let worktree = patch.worktree.as_str();
+            let present = on_disk.contains(worktree);
+            let healthy = present && registered.contains(worktree);
+            // Patches of the same spec share a worktree; repair it once.
+            let repair = !healthy && moved.insert(worktree);
+            let restore_commit = if healthy {
+                None
+            } else {
+                self.restore_commit(patch)
+            };
+            if !healthy {
+                let message = if present {
+                    format!("worktree {} is not registered with git", worktree)
+                } else {
+                    format!("worktree {} is missing", worktree)
+                };
+                let mut finding = Finding::new(Severity::Error, subject, message);
+                match &restore_commit {
+                    Some(commit) if repair => {
+                        let mut ops = Vec::new();
+                        if present {
+                            ops.push(Op::RemoveDir {
+                                path: worktree.to_string(),
+                                removes: Vec::new(),
+                            });
+                        }
+                        if registered.contains(worktree) || present {
+                            ops.push(Op::PruneWorktrees);
+                        }
+                        ops.push(Op::AddWorktree {
+                            path: worktree.to_string(),
+                            commit: commit.clone(),
+                            sparse: patch.remote_path.clone(),
+                        });
+                        finding = finding.fix(ops);
+                    }
+                    Some(_) => {}
+                    None => {
+                        finding = finding.hint(format!("run 'cross patch {}' again", patch.spec()))
+                    }
+                }
+                findings.push(finding);
+            }
+
+            if !self.join(subject).exists() {
+                let restore = if patch.file {
+                    Op::CopyFile {
+                        src: format!("{}/{}", worktree, patch.remote_path),
+                        dst: subject.to_string(),
+                    }
+                } else {
+                    Op::Mirror {
+                        src: format!("{}/{}", worktree, patch.remote_path),
+                        dst: subject.to_string(),
+                        delete: false,
+                        filter: patch.filter.clone(),
+                        removes: Vec::new(),
+                    }
+                };
+                let mut finding = Finding::new(
+                    Severity::Warning,
+                    subject,
+                    "vendored path is missing".into(),
+                )
+                .hint(format!("run 'cross remove {}' to drop the patch", subject));
+                if healthy || restore_commit.is_some() {
+                    finding = finding.fix(vec![restore]);
+                }
+                findings.push(finding);
+            }
+
+            if !recorded.contains(subject) {
+                findings.push(
+                    Finding::new(
+                        Severity::Warning,
+                        subject,
+                        "patch is not recorded in the Crossfile".into(),
+                    )
+                    .fix(vec![Op::AddToCrossfile(patch.statement())]),
+                );
+            }
+        }
+
+        for local_path in &crossfile_patches {
+            if !patches.iter().any(|p| &p.local_path == local_path) {
+                findings.push(
+                    Finding::new(
+                        Severity::Warning,
+                        local_path,
+                        "Crossfile patch has no metadata".into(),
+                    )
+                    .hint("run 'cross replay' to vendor it"),
+                );
+            }
+        }
+
+        let referenced: BTreeSet<&str> = patches.iter().map(|p| p.worktree.as_str()).collect();
+        for worktree in registered.union(&on_disk) {
+            if referenced.contains(worktree.as_str()) {
+                continue;
+            }
+            let finding = if !on_disk.contains(worktree) {
+                Finding::new(
+                    Severity::Warning,
+                    worktree,
+                    "registered worktree directory is gone".into(),
+                )
+                .fix(vec![Op::PruneWorktrees])
+            } else if registered.contains(worktree) {
+                Finding::new(
+                    Severity::Warning,
+                    worktree,
+                    "worktree is not used by any patch".into(),
+                )
+                .fix(vec![Op::RemoveWorktree(worktree.clone())])
+            } else {
+                Finding::new(
+                    Severity::Warning,
+                    worktree,
+                    "directory is neither a worktree nor used by any patch".into(),
+                )
+                .fix(vec![Op::RemoveDir {
+                    path: worktree.clone(),
+                    removes: Vec::new(),
+                }])
+            };
+            findings.push(finding);
+        }
+
+        findings.sort_by_key(|f| f.severity);
+        Ok(findings)
+    }
+
+    /// Plan repairing every fixable finding. Ops shared by several
+    /// findings (e.g. `git worktree prune`) run once.
+    pub fn plan_doctor_fix(&self, findings: &[Finding]) -> Plan {
+        let mut plan = Plan::new("doctor --fix");
+        let mut seen = BTreeSet::new();
+        // Remotes and worktree registrations first: the other fixes need them.
+        let mut ops: Vec<&Op> = findings.iter().flat_map(|f| &f.fix).collect();
+        ops.sort_by_key(|op| match op {
+            Op::SetRemote { .. } => 0,
+            Op::RemoveDir { .. } | Op::RemoveWorktree(_) => 1,
+            Op::PruneWorktrees => 2,
+            Op::AddWorktree { .. } => 3,
+            _ => 4,
+        });
+        for op in ops {
+            if seen.insert(op.to_string()) {
+                plan.push(op.clone());
+            }
+        }
+        plan
+    }
+
+    /// Commit a missing worktree can be recreated at: the sync base, or the
+    /// locked commit, when it is available locally.
+    fn restore_commit(&self, patch: &Patch) -> Option<String> {
+        let locked = self
+            .load_lock()
+            .ok()
+            .and_then(|lock| lock.find(&patch.local_path).map(|l| l.commit.clone()));
+        patch
+            .base
+            .clone()
+            .into_iter()
+            .chain(locked)
+            .find(|commit| self.git().has_commit(self.root(), commit))
+    }
+
+    /// Repository relative form of an absolute path, if it is inside.
+    fn relative(&self, path: &Path) -> Option<String> {
+        let canonical = |p: &Path| fs::canonicalize(p).unwrap_or_else(|_| p.to_path_buf());
+        let rel = path
+            .strip_prefix(self.root())
+            .ok()
+            .map(Path::to_path_buf)
+            .or_else(|| {
+                let parent = canonical(path.parent()?);
+                let full = parent.join(path.file_name()?);
+                Some(
+                    full.strip_prefix(canonical(self.root()))
+                        .ok()?
+                        .to_path_buf(),
+                )
+            })?;
+        Some(rel.to_string_lossy().replace('\\', "/"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Severity;
+    use crate::git::testing::{URL, repo};
+    use crate::repo::PatchOptions;
+    use std::fs;
+
+    #[test]
+    fn doctor_repairs_a_deleted_worktree_and_remote() {
+        let (_dir, fake, repo) = repo();
+        fake.upstream_commit(URL, "main", &[("docs/a.md", "a\n")], "Add docs");
+        repo.use_remote("demo", URL).unwrap();
+        let patch = repo
+            .add_patch("demo:docs", &PatchOptions::default())
+            .unwrap();
+        assert!(repo.doctor().unwrap().is_empty());
+
+        fs::remove_dir_all(repo.join(&patch.worktree)).unwrap();
+        repo.git().remove_remote(repo.root(), "demo").unwrap();
+        let findings = repo.doctor().unwrap();
+        let summary: Vec<(Severity, &str, bool)> = findings
+            .iter()
+            .map(|f| (f.severity, f.subject.as_str(), f.fixable()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (Severity::Error, "demo", true),
+                (Severity::Error, "docs", true)
+            ]
+        );
+
+        repo.execute(&repo.plan_doctor_fix(&findings)).unwrap();
+        assert!(repo.doctor().unwrap().is_empty());
+        assert_eq!(fake.worktrees(), [repo.join(&patch.worktree)]);
+        assert!(repo.join(&patch.worktree).join("docs/a.md").exists());
+    }
+}
@@ -0,0 +1,92 @@
+//! Typed errors with stable exit codes.
+//!
+//! Library functions return [`anyhow::Error`]; failures a caller may want to
+//! react to are a [`CrossError`] somewhere in its chain (see
+//! [`CrossError::find`]). The CLI exits with [`CrossError::exit_code`], so
+//! wrapper scripts can tell e.g. a sync conflict from a network failure:
+//!
+//! | Code | Meaning                                              |
+//! |------|------------------------------------------------------|
+//! | 1    | Any other error (network, I/O, git failures)         |
+//! | 2    | Invalid command line                                 |
+//! | 3    | [`CrossError::InvalidSpec`]                          |
+//! | 4    | [`CrossError::RemoteNotFound`]                       |
+//! | 5    | [`CrossError::PatchNotFound`]                        |
+//! | 6    | [`CrossError::WorktreeMissing`]                      |
+//! | 7    | [`CrossError::MetadataCorrupt`], [`CrossError::UnsupportedVersion`] |
+//! | 8    | [`CrossError::DirtyWorkingTree`]                     |
+//! | 9    | [`CrossError::Locked`]                               |
+//! | 10-13| `status --check` failures                            |
+//! | 20   | [`CrossError::SyncConflict`]                         |
+//! | 21   | [`CrossError::PushRejected`]                         |
+
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum CrossError {
+    /// A patch spec or the ref in it cannot be used.
+    #[error("Invalid spec '{spec}': {reason}")]
+    InvalidSpec { spec: String, reason: String },
+    #[error("Remote {name} not found. Run 'use' first.")]
+    RemoteNotFound { name: String },
+    /// No patch is recorded for a local path.
+    #[error("Patch not found for path: {path}")]
+    PatchNotFound { path: String },
+    /// The worktree of a patch is gone; `path` is the patch's local path.
+    #[error("Worktree not found for {path}. Run patch again.")]
+    WorktreeMissing { path: String },
+    /// `.git/cross/metadata.json` or `Crossfile.lock` cannot be parsed.
+    #[error("{} is corrupt: {reason}", path.display())]
+    MetadataCorrupt { path: PathBuf, reason: String },
+    /// `.git/cross/metadata.json` or `Crossfile.lock` was written by a newer
+    /// git-cross.
+    #[error(
+        "{} has schema version {version}, this build supports up to {supported}; upgrade git-cross",
+        path.display()
+    )]
+    UnsupportedVersion {
+        path: PathBuf,
+        version: u32,
+        supported: u32,
+    },
+    /// Local changes in `path` would be overwritten.
+    #[error("{path} has local changes that would be overwritten")]
+    DirtyWorkingTree { path: String },
+    /// Another process holds `.git/cross/operation.lock`.
+    #[error(
+        "Cannot lock {}: another cross operation is running (pid {pid})",
+        path.display()
+    )]
+    Locked { path: PathBuf, pid: String },
+    /// Sync finished but left conflicts in these repository relative files.
+    #[error("Sync left conflicts in {}", files.join(", "))]
+    SyncConflict { files: Vec<String> },
+    #[error("{remote} rejected {refspec}: {reason}")]
+    PushRejected {
+        remote: String,
+        refspec: String,
+        reason: String,
+    },
+}
+
+impl CrossError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CrossError::InvalidSpec { .. } => 3,
+            CrossError::RemoteNotFound { .. } => 4,
+            CrossError::PatchNotFound { .. } => 5,
+            CrossError::WorktreeMissing { .. } => 6,
+            CrossError::MetadataCorrupt { .. } | CrossError::UnsupportedVersion { .. } => 7,
+            CrossError::DirtyWorkingTree { .. } => 8,
+            CrossError::Locked { .. } => 9,
+            CrossError::SyncConflict { .. } => 20,
+            CrossError::PushRejected { .. } => 21,
+        }
+    }
+
+    /// The outermost `CrossError` in the chain of `err`.
+    pub fn find(err: &anyhow::Error) -> Option<&CrossError> {
+        err.chain().find_map(|e| e.downcast_ref::<CrossError>())
+    }
+}
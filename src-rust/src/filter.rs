@@ -0,0 +1,129 @@
+//! Per-patch include/exclude globs.
+//!
+//! Patterns are matched against paths relative to the patch root (the remote
+//! path upstream, the local path locally) with `.gitignore`-like rules: a
+//! pattern without `/` matches a file or directory name at any depth, a
+//! pattern with `/` matches from the patch root, and a match on a directory
+//! applies to everything below it. `*` does not cross `/`; use `**` for that.
+//!
+//! A file is part of the patch when it matches an include pattern (or there
+//! are none) and matches no exclude pattern. Files outside the patch are
+//! never copied, pushed, deleted or reported as drift.
+
+use anyhow::{Result, anyhow};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct PathFilter {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+}
+
+impl PathFilter {
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    pub fn matcher(&self) -> Result<PathMatcher> {
+        Ok(PathMatcher {
+            include: if self.include.is_empty() {
+                None
+            } else {
+                Some(Patterns::new(&self.include)?)
+            },
+            exclude: Patterns::new(&self.exclude)?,
+        })
+    }
+
+    /// Command line form: `--include <glob>... --exclude <glob>...`.
+    pub fn args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        for pattern in &self.include {
+            args.extend(["--include".to_string(), pattern.clone()]);
+        }
+        for pattern in &self.exclude {
+            args.extend(["--exclude".to_string(), pattern.clone()]);
+        }
+        args
+    }
+}
+
+impl fmt::Display for PathFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if !self.include.is_empty() {
+            parts.push(format!("include {}", self.include.join(", ")));
+        }
+        if !self.exclude.is_empty() {
+            parts.push(format!("exclude {}", self.exclude.join(", ")));
+        }
+        write!(f, "{}", parts.join("; "))
+    }
+}
+
+/// Compiled [`PathFilter`].
+#[derive(Debug, Clone)]
+pub struct PathMatcher {
+    include: Option<Patterns>,
+    exclude: Patterns,
+}
+
+impl PathMatcher {
+    /// Whether the file at `rel` (relative to the patch root) is part of the patch.
+    pub fn matches(&self, rel: &str) -> bool {
+        let included = self.include.as_ref().is_none_or(|p| p.is_match(rel));
+        included && !self.exclude.is_match(rel)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Patterns {
+    /// Patterns containing `/`, matched against the path from the root.
+    anchored: GlobSet,
+    /// Patterns without `/`, matched against single names.
+    names: GlobSet,
+}
+
+impl Patterns {
+    fn new(patterns: &[String]) -> Result<Patterns> {
+        let mut anchored = GlobSetBuilder::new();
+        let mut names = GlobSetBuilder::new();
+        for pattern in patterns {
+            let trimmed = pattern.trim_start_matches("./").trim_matches('/');
+            if trimmed.is_empty() {
+                return Err(anyhow!("Invalid glob '{}'", pattern));
+            }
+            let glob = GlobBuilder::new(trimmed)
+                .literal_separator(true)
+                .build()
+                .map_err(|e| anyhow!("Invalid glob '{}': {}", pattern, e))?;
+            if trimmed.contains('/') {
+                anchored.add(glob);
+            } else {
+                names.add(glob);
+            }
+        }
+        Ok(Patterns {
+            anchored: anchored.build()?,
+            names: names.build()?,
+        })
+    }
+
+    /// Whether `rel` or one of its parent directories matches.
+    fn is_match(&self, rel: &str) -> bool {
+        let mut end = 0;
+        for name in rel.split('/') {
+            end += name.len();
+            let prefix = &rel[..end];
+            if self.names.is_match(name) || self.anchored.is_match(prefix) {
+                return true;
+            }
+            end += 1;
+        }
+        false
+    }
+}
@@ -0,0 +1,479 @@
+//! A [`GitBackend`] running the system `git` binary, for setups libgit2 does
+//! not cover (custom transports, `insteadOf` rewrites, credential setups).
+//! Sparse checkouts use `git sparse-checkout` in non-cone mode.
+
+use super::{GitBackend, TreeBlob, pick_default_branch, relative, rename_in_workdir, within};
+use crate::error::CrossError;
+use anyhow::{Context, Result, anyhow};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Runs `git -C <repo> ...` for every operation.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CliBackend;
+
+/// Output of a finished `git` command.
+struct Output {
+    code: Option<i32>,
+    stdout: Vec<u8>,
+    stderr: String,
+}
+
+/// Run `git -C dir args...` without failing on a non-zero exit.
+fn run(dir: &Path, args: &[&str]) -> Result<Output> {
+    let mut full = vec!["-C".to_string(), dir.to_string_lossy().to_string()];
+    full.extend(args.iter().map(|a| a.to_string()));
+    let output = duct::cmd("git", &full)
+        .stdout_capture()
+        .stderr_capture()
+        .unchecked()
+        .run()
+        .context("Failed to execute git")?;
+    Ok(Output {
+        code: output.status.code(),
+        stdout: output.stdout,
+        stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+    })
+}
+
+/// Raw stdout of a successful `git -C dir args...`.
+fn git_bytes(dir: &Path, args: &[&str]) -> Result<Vec<u8>> {
+    let output = run(dir, args)?;
+    if output.code != Some(0) {
+        return Err(anyhow!("git {} failed: {}", args.join(" "), output.stderr));
+    }
+    Ok(output.stdout)
+}
+
+/// Trimmed stdout of a successful `git -C dir args...`.
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let stdout = git_bytes(dir, args)?;
+    Ok(String::from_utf8_lossy(&stdout).trim().to_string())
+}
+
+/// `rev:path`, or the root tree of `rev` for `.`.
+fn object_name(rev: &str, path: &str) -> String {
+    if path == "." {
+        format!("{}^{{tree}}", rev)
+    } else {
+        format!("{}:{}", rev, path)
+    }
+}
+
+/// Parse `sha<TAB>refname` lines of `git ls-remote`.
+fn parse_refs(output: &str) -> Vec<(String, String)> {
+    output
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .map(|(sha, name)| (sha.to_string(), name.to_string()))
+        .collect()
+}
+
+/// Restrict the worktree to `sparse`, or lift the restriction for `.`.
+/// Patterns are anchored so same-named paths deeper down stay out.
+fn set_sparse(worktree: &Path, sparse: &str) -> Result<()> {
+    if sparse == "." {
+        git(worktree, &["sparse-checkout", "disable"])?;
+    } else {
+        let pattern = format!("/{}", sparse);
+        git(worktree, &["sparse-checkout", "set", "--no-cone", &pattern])?;
+    }
+    Ok(())
+}
+
+impl GitBackend for CliBackend {
+    fn toplevel(&self, path: &Path) -> Result<PathBuf> {
+        let root = git(path, &["rev-parse", "--show-toplevel"])
+            .with_context(|| format!("Not a git repository: {}", path.display()))?;
+        Ok(fs::canonicalize(root)?)
+    }
+
+    fn commit_id(&self, repo: &Path, rev: &str) -> Result<String> {
+        git(
+            repo,
+            &[
+                "rev-parse",
+                "--verify",
+                "--quiet",
+                &format!("{}^{{commit}}", rev),
+            ],
+        )
+        .with_context(|| format!("Cannot resolve {} to a commit", rev))
+    }
+
+    fn path_id(&self, repo: &Path, rev: &str, path: &str) -> Result<String> {
+        git(
+            repo,
+            &["rev-parse", "--verify", "--quiet", &object_name(rev, path)],
+        )
+        .with_context(|| format!("{} does not exist in {}", path, rev))
+    }
+
+    fn is_file(&self, repo: &Path, rev: &str, path: &str) -> Result<bool> {
+        let kind = git(repo, &["cat-file", "-t", &object_name(rev, path)])
+            .with_context(|| format!("{} does not exist in {}", path, rev))?;
+        Ok(kind == "blob")
+    }
+
+    fn read_blob(&self, repo: &Path, oid: &str) -> Result<Vec<u8>> {
+        git_bytes(repo, &["cat-file", "blob", oid])
+    }
+
+    fn blobs(&self, repo: &Path, rev: &str, path: &str) -> Result<BTreeMap<String, TreeBlob>> {
+        let mut args = vec!["ls-tree", "-r", "-z", "--full-tree", rev];
+        if path != "." {
+            args.extend(["--", path]);
+        }
+        let output = git_bytes(repo, &args)?;
+        let mut blobs = BTreeMap::new();
+        // `<mode> <type> <oid>\t<path>\0`
+        for record in output.split(|b| *b == 0).filter(|r| !r.is_empty()) {
+            let record = String::from_utf8_lossy(record);
+            let Some((info, file)) = record.split_once('\t') else {
+                continue;
+            };
+            let fields: Vec<&str> = info.split(' ').collect();
+            if fields.len() != 3 || fields[1] != "blob" || !within(path, file) {
+                continue;
+            }
+            blobs.insert(
+                relative(path, file).to_string(),
+                TreeBlob {
+                    oid: fields[2].to_string(),
+                    mode: i32::from_str_radix(fields[0], 8)?,
+                },
+            );
+        }
+        Ok(blobs)
+    }
+
+    fn hash_file(&self, repo: &Path, path: &Path) -> Result<String> {
+        git(
+            repo,
+            &["hash-object", "--no-filters", &path.to_string_lossy()],
+        )
+    }
+
+    fn merge_file(
+        &self,
+        repo: &Path,
+        local: &[u8],
+        base: Option<&[u8]>,
+        upstream: Option<&[u8]>,
+    ) -> Result<(Vec<u8>, bool)> {
+        let file = |content: Option<&[u8]>| -> Result<tempfile::NamedTempFile> {
+            let mut file = tempfile::NamedTempFile::new()?;
+            file.write_all(content.unwrap_or_default())?;
+            Ok(file)
+        };
+        let (local, base, upstream) = (file(Some(local))?, file(base)?, file(upstream)?);
+        let paths = [&local, &base, &upstream].map(|f| f.path().to_string_lossy().to_string());
+        let output = run(
+            repo,
+            &[
+                "merge-file",
+                "-p",
+                "-L",
+                "local",
+                "-L",
+                "base",
+                "-L",
+                "upstream",
+                &paths[0],
+                &paths[1],
+                &paths[2],
+            ],
+        )?;
+        // The exit code is the number of conflicts; negative values (shown as
+        // >= 128) are errors such as binary files.
+        match output.code {
+            Some(0) => Ok((output.stdout, false)),
+            Some(n) if n > 0 && n < 128 => Ok((output.stdout, true)),
+            _ => Err(anyhow!("git merge-file failed: {}", output.stderr)),
+        }
+    }
+
+    fn diff_hunks(&self, repo: &Path, old: &[u8], new: &[u8]) -> Result<String> {
+        let file = |content: &[u8]| -> Result<tempfile::NamedTempFile> {
+            let mut file = tempfile::NamedTempFile::new()?;
+            file.write_all(content)?;
+            Ok(file)
+        };
+        let (old, new) = (file(old)?, file(new)?);
+        let paths = [&old, &new].map(|f| f.path().to_string_lossy().to_string());
+        let output = run(
+            repo,
+            &[
+                "diff",
+                "--no-index",
+                "--no-color",
+                "--no-ext-diff",
+                "--",
+                &paths[0],
+                &paths[1],
+            ],
+        )?;
+        // Exit code 1 means the files differ.
+        if !matches!(output.code, Some(0 | 1)) {
+            return Err(anyhow!("git diff --no-index failed: {}", output.stderr));
+        }
+        // Drop the header naming the temporary files.
+        let text = String::from_utf8_lossy(&output.stdout);
+        let start = text
+            .find("\n@@ ")
+            .or_else(|| text.find("\nBinary files "))
+            .map_or(text.len(), |i| i + 1);
+        Ok(text[start..].to_string())
+    }
+
+    fn count(&self, repo: &Path, from: &str, to: &str) -> Result<u32> {
+        let range = format!("{}..{}", from, to);
+        Ok(git(repo, &["rev-list", "--count", &range])?.parse()?)
+    }
+
+    fn log(&self, repo: &Path, from: &str, to: &str, path: &str) -> Result<Vec<(String, String)>> {
+        let range = format!("{}..{}", from, to);
+        let mut args = vec!["log", "--format=%H%x09%s", &range];
+        if path != "." {
+            args.extend(["--", path]);
+        }
+        Ok(parse_refs(&git(repo, &args)?))
+    }
+
+    fn last_subject(&self, repo: &Path, path: &str) -> Result<Option<String>> {
+        let mut args = vec!["log", "-1", "--format=%s"];
+        if path != "." {
+            args.extend(["--", path]);
+        }
+        let subject = git(repo, &args)?;
+        Ok(Some(subject).filter(|s| !s.is_empty()))
+    }
+
+    fn has_conflicts(&self, repo: &Path, prefix: Option<&str>) -> Result<bool> {
+        let mut args = vec!["ls-files", "--unmerged"];
+        if let Some(prefix) = prefix {
+            args.extend(["--", prefix]);
+        }
+        Ok(!git(repo, &args)?.is_empty())
+    }
+
+    fn remote_names(&self, repo: &Path) -> Result<Vec<String>> {
+        Ok(git(repo, &["remote"])?
+            .lines()
+            .map(str::to_string)
+            .collect())
+    }
+
+    fn remote_urls(&self, repo: &Path, name: &str) -> Result<(String, String)> {
+        let fetch =
+            git(repo, &["remote", "get-url", name]).map_err(|_| CrossError::RemoteNotFound {
+                name: name.to_string(),
+            })?;
+        let push = git(repo, &["remote", "get-url", "--push", name])?;
+        Ok((fetch, push))
+    }
+
+    fn set_remote(&self, repo: &Path, name: &str, url: &str) -> Result<()> {
+        let action = if self.has_remote(repo, name) {
+            "set-url"
+        } else {
+            "add"
+        };
+        git(repo, &["remote", action, name, url])?;
+        Ok(())
+    }
+
+    fn remove_remote(&self, repo: &Path, name: &str) -> Result<()> {
+        git(repo, &["remote", "remove", name])
+            .with_context(|| format!("Cannot remove remote {}", name))?;
+        Ok(())
+    }
+
+    fn default_branch(&self, repo: &Path, remote: &str) -> Result<String> {
+        let output = git(repo, &["ls-remote", "--symref", remote, "HEAD"])
+            .with_context(|| format!("Cannot connect to {}", remote))?;
+        for line in output.lines() {
+            if let Some(target) = line.strip_prefix("ref: ")
+                && let Some((target, _)) = target.split_once('\t')
+                && let Some(branch) = target.strip_prefix("refs/heads/")
+            {
+                return Ok(branch.to_string());
+            }
+        }
+        let heads = parse_refs(&git(repo, &["ls-remote", "--heads", remote])?)
+            .into_iter()
+            .filter_map(|(_, name)| name.strip_prefix("refs/heads/").map(str::to_string))
+            .collect();
+        Ok(pick_default_branch(heads))
+    }
+
+    fn ls_remote(&self, repo: &Path, remote: &str) -> Result<Vec<(String, String)>> {
+        let output = git(repo, &["ls-remote", remote])
+            .with_context(|| format!("Cannot connect to {}", remote))?;
+        Ok(parse_refs(&output))
+    }
+
+    fn fetch(&self, repo: &Path, remote: &str, refspecs: &[String]) -> Result<()> {
+        self.remote_urls(repo, remote)?;
+        let mut args = vec!["fetch", "--quiet", "--no-tags", remote];
+        args.extend(refspecs.iter().map(String::as_str));
+        git(repo, &args)
+            .with_context(|| format!("Cannot fetch {} from {}", refspecs.join(" "), remote))?;
+        Ok(())
+    }
+
+    fn push(&self, repo: &Path, remote: &str, refspec: &str, force: bool) -> Result<()> {
+        let mut args = vec!["push", "--quiet"];
+        if force {
+            args.push("--force");
+        }
+        args.extend([remote, refspec]);
+        self.remote_urls(repo, remote)?;
+        let output = run(repo, &args)?;
+        if output.code == Some(0) {
+            return Ok(());
+        }
+        if output.stderr.contains("[rejected]") || output.stderr.contains("[remote rejected]") {
+            return Err(CrossError::PushRejected {
+                remote: remote.to_string(),
+                refspec: refspec.to_string(),
+                reason: output.stderr,
+            }
+            .into());
+        }
+        Err(anyhow!("git push failed: {}", output.stderr))
+            .with_context(|| format!("Cannot push {} to {}", refspec, remote))
+    }
+
+    fn add_worktree(&self, repo: &Path, path: &Path, commit: &str, sparse: &str) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let wt = path.to_string_lossy();
+        git(
+            repo,
+            &["worktree", "add", "--no-checkout", "--detach", &wt, commit],
+        )?;
+        if sparse != "." {
+            set_sparse(path, sparse)?;
+        }
+        git(
+            path,
+            &["checkout", "--quiet", "--force", "--detach", commit],
+        )?;
+        Ok(())
+    }
+
+    fn checkout(&self, worktree: &Path, commit: &str, sparse: &str, force: bool) -> Result<()> {
+        let mut args = vec!["checkout", "--quiet"];
+        if force {
+            args.push("--force");
+        }
+        args.extend(["--detach", commit]);
+        let output = run(worktree, &args)?;
+        if output.code != Some(0) {
+            if output.stderr.contains("would be overwritten") {
+                return Err(CrossError::DirtyWorkingTree {
+                    path: worktree.display().to_string(),
+                }
+                .into());
+            }
+            return Err(anyhow!("git checkout failed: {}", output.stderr));
+        }
+        set_sparse(worktree, sparse)
+    }
+
+    fn remove_worktree(&self, repo: &Path, path: &Path) -> Result<()> {
+        let wt = path.to_string_lossy();
+        if git(repo, &["worktree", "remove", "--force", "--force", &wt]).is_err() && path.exists() {
+            fs::remove_dir_all(path)?;
+        }
+        Ok(())
+    }
+
+    fn list_worktrees(&self, repo: &Path) -> Result<Vec<PathBuf>> {
+        let output = git(repo, &["worktree", "list", "--porcelain"])?;
+        // The first entry is the main working tree.
+        Ok(output
+            .lines()
+            .filter_map(|line| line.strip_prefix("worktree "))
+            .skip(1)
+            .map(PathBuf::from)
+            .collect())
+    }
+
+    fn prune_worktrees(&self, repo: &Path) -> Result<Vec<String>> {
+        let output = run(repo, &["worktree", "prune", "--verbose"])?;
+        if output.code != Some(0) {
+            return Err(anyhow!("git worktree prune failed: {}", output.stderr));
+        }
+        // `Removing worktrees/<name>: <reason>` (on stderr in recent git).
+        let text = format!(
+            "{}\n{}",
+            String::from_utf8_lossy(&output.stdout),
+            output.stderr
+        );
+        Ok(text
+            .lines()
+            .filter_map(|line| line.strip_prefix("Removing worktrees/"))
+            .filter_map(|rest| rest.split_once(':').map(|(name, _)| name.to_string()))
+            .collect())
+    }
+
+    fn commit_path(&self, worktree: &Path, path: &str, message: &str) -> Result<bool> {
+        git(worktree, &["add", "--all", "--", path])?;
+        let staged = run(worktree, &["diff", "--cached", "--quiet"])?;
+        if staged.code == Some(0) {
+            return Ok(false);
+        }
+        git(worktree, &["commit", "--quiet", "-m", message])?;
+        Ok(true)
+    }
+
+    fn move_path(&self, repo: &Path, from: &str, to: &str) -> Result<()> {
+        // `git mv` refuses untracked files, so the index entries are renamed
+        // through `update-index`, keeping their staged content.
+        let pathspec = format!(":(literal){}", from);
+        let staged = git_bytes(repo, &["ls-files", "--stage", "-z", "--", &pathspec])?;
+        rename_in_workdir(repo, from, to)?;
+        let mut info = String::new();
+        for record in staged.split(|b| *b == 0).filter(|r| !r.is_empty()) {
+            let record = String::from_utf8_lossy(record);
+            let Some((entry, path)) = record.split_once('\t') else {
+                continue;
+            };
+            let Some((mode_and_id, _stage)) = entry.rsplit_once(' ') else {
+                continue;
+            };
+            let id_len = mode_and_id.len() - mode_and_id.find(' ').map_or(0, |i| i + 1);
+            info.push_str(&format!("0 {}\t{}\n", "0".repeat(id_len), path));
+            info.push_str(&format!("{}\t{}{}\n", entry, to, &path[from.len()..]));
+        }
+        if info.is_empty() {
+            return Ok(());
+        }
+        let output = duct::cmd(
+            "git",
+            [
+                "-C",
+                &repo.to_string_lossy(),
+                "update-index",
+                "--index-info",
+            ],
+        )
+        .stdin_bytes(info)
+        .stderr_capture()
+        .unchecked()
+        .run()
+        .context("Failed to execute git")?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "git update-index failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,679 @@
+//! An in-memory [`GitBackend`] for tests.
+//!
+//! Upstream repositories live in memory, keyed by URL, and are built with
+//! [`FakeBackend::upstream_commit`] and [`FakeBackend::tag`]. All
+//! repositories share one object store, so a commit is known everywhere once
+//! it exists; fetching only moves refs. Worktrees are plain directories the
+//! fake writes the sparse path of their `HEAD` into. Pushes are recorded and
+//! can be inspected with [`FakeBackend::pushes`].
+//!
+//! Merges are whole-file: when local and upstream both changed a file
+//! differently, the result is both versions between conflict markers.
+
+use super::{GitBackend, TreeBlob, pick_default_branch, relative, rename_in_workdir, within};
+use crate::error::CrossError;
+use crate::tree::{is_executable, write_file, write_symlink};
+use anyhow::{Result, anyhow};
+use git2::{ObjectType, Oid};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+/// A push made through the fake.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FakePush {
+    pub remote: String,
+    pub refspec: String,
+    pub force: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Commit {
+    parents: Vec<String>,
+    subject: String,
+    files: BTreeMap<String, TreeBlob>,
+}
+
+#[derive(Debug, Default)]
+struct Upstream {
+    refs: BTreeMap<String, String>,
+    default_branch: String,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    blobs: HashMap<String, Vec<u8>>,
+    commits: HashMap<String, Commit>,
+    upstreams: BTreeMap<String, Upstream>,
+    remotes: BTreeMap<String, String>,
+    /// Local refs (`refs/remotes/...`, `FETCH_HEAD`), shared by all worktrees.
+    refs: BTreeMap<String, String>,
+    /// `HEAD` of each worktree, by path.
+    worktrees: BTreeMap<PathBuf, String>,
+    pushes: Vec<FakePush>,
+}
+
+#[derive(Debug, Default)]
+pub struct FakeBackend {
+    state: Mutex<State>,
+}
+
+impl State {
+    fn add_blob(&mut self, content: &[u8]) -> String {
+        let oid = Oid::hash_object(ObjectType::Blob, content)
+            .expect("hashing a blob")
+            .to_string();
+        self.blobs.insert(oid.clone(), content.to_vec());
+        oid
+    }
+
+    fn add_commit(&mut self, commit: Commit) -> String {
+        let mut text = format!("{}\n{}\n", commit.parents.join(" "), commit.subject);
+        for (path, blob) in &commit.files {
+            text.push_str(&format!("{:o} {} {}\n", blob.mode, blob.oid, path));
+        }
+        let id = Oid::hash_object(ObjectType::Commit, text.as_bytes())
+            .expect("hashing a commit")
+            .to_string();
+        self.commits.insert(id.clone(), commit);
+        id
+    }
+
+    fn commit(&self, id: &str) -> Result<&Commit> {
+        self.commits
+            .get(id)
+            .ok_or_else(|| anyhow!("Unknown commit {}", id))
+    }
+
+    fn url(&self, remote: &str) -> String {
+        self.remotes
+            .get(remote)
+            .cloned()
+            .unwrap_or_else(|| remote.to_string())
+    }
+
+    fn upstream(&self, remote: &str) -> Result<&Upstream> {
+        let url = self.url(remote);
+        self.upstreams
+            .get(&url)
+            .ok_or_else(|| anyhow!("Cannot connect to {}", url))
+    }
+
+    /// Resolve `rev` in the repository (or worktree) at `repo`.
+    fn resolve(&self, repo: &Path, rev: &str) -> Result<String> {
+        if rev == "HEAD" {
+            return self
+                .worktrees
+                .get(repo)
+                .cloned()
+                .ok_or_else(|| anyhow!("{} has no HEAD", repo.display()));
+        }
+        if let Some(id) = self.refs.get(rev) {
+            return Ok(id.clone());
+        }
+        if let Some(id) = self.refs.get(&format!("refs/remotes/{}", rev)) {
+            return Ok(id.clone());
+        }
+        let matches: Vec<&String> = self
+            .commits
+            .keys()
+            .filter(|id| rev.len() >= 4 && id.starts_with(rev))
+            .collect();
+        match matches.as_slice() {
+            [id] => Ok((*id).clone()),
+            _ => Err(anyhow!("Cannot resolve {} to a commit", rev)),
+        }
+    }
+
+    /// Blobs of `path` in `rev`, relative to `path`.
+    fn blobs(&self, repo: &Path, rev: &str, path: &str) -> Result<BTreeMap<String, TreeBlob>> {
+        let commit = self.commit(&self.resolve(repo, rev)?)?;
+        Ok(commit
+            .files
+            .iter()
+            .filter(|(file, _)| within(path, file))
+            .map(|(file, blob)| (relative(path, file).to_string(), blob.clone()))
+            .collect())
+    }
+
+    fn ancestors(&self, id: &str) -> HashSet<String> {
+        let mut seen = HashSet::new();
+        let mut todo = vec![id.to_string()];
+        while let Some(id) = todo.pop() {
+            if let Some(commit) = self.commits.get(&id)
+                && seen.insert(id)
+            {
+                todo.extend(commit.parents.iter().cloned());
+            }
+        }
+        seen
+    }
+
+    /// Commits in `from..to`, newest first.
+    fn range(&self, repo: &Path, from: &str, to: &str) -> Result<Vec<String>> {
+        let hidden = self.ancestors(&self.resolve(repo, from)?);
+        let mut commits = Vec::new();
+        let mut next = Some(self.resolve(repo, to)?);
+        while let Some(id) = next.take().filter(|id| !hidden.contains(id)) {
+            next = self.commit(&id)?.parents.first().cloned();
+            commits.push(id);
+        }
+        Ok(commits)
+    }
+
+    fn touches(&self, id: &str, path: &str) -> Result<bool> {
+        let commit = self.commit(id)?;
+        let subset = |files: &BTreeMap<String, TreeBlob>| -> Vec<(String, TreeBlob)> {
+            files
+                .iter()
+                .filter(|(file, _)| within(path, file))
+                .map(|(file, blob)| (file.clone(), blob.clone()))
+                .collect()
+        };
+        let mine = subset(&commit.files);
+        if commit.parents.is_empty() {
+            return Ok(!mine.is_empty());
+        }
+        for parent in &commit.parents {
+            if subset(&self.commit(parent)?.files) == mine {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Write the files of `id` below `sparse` into `worktree`, replacing
+    /// what was there.
+    fn materialize(&self, worktree: &Path, id: &str, sparse: &str) -> Result<()> {
+        let target = if sparse == "." {
+            worktree.to_path_buf()
+        } else {
+            worktree.join(sparse)
+        };
+        if let Ok(meta) = fs::symlink_metadata(&target) {
+            if meta.is_dir() {
+                fs::remove_dir_all(&target)?;
+            } else {
+                fs::remove_file(&target)?;
+            }
+        }
+        fs::create_dir_all(worktree)?;
+        for (file, blob) in &self.commit(id)?.files {
+            if !within(sparse, file) {
+                continue;
+            }
+            let content = &self.blobs[&blob.oid];
+            let path = worktree.join(file);
+            if blob.symlink() {
+                write_symlink(&path, content)?;
+            } else {
+                write_file(&path, content, blob.executable())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FakeBackend {
+    pub fn new() -> FakeBackend {
+        FakeBackend::default()
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Commit `files` (the complete tree, as `(path, content)` pairs) on
+    /// `branch` of the upstream at `url`, creating the upstream when needed.
+    /// Its first branch becomes its default branch. Returns the commit.
+    pub fn upstream_commit(
+        &self,
+        url: &str,
+        branch: &str,
+        files: &[(&str, &str)],
+        subject: &str,
+    ) -> String {
+        let mut state = self.state();
+        let refname = format!("refs/heads/{}", branch);
+        let parent = state
+            .upstreams
+            .get(url)
+            .and_then(|u| u.refs.get(&refname))
+            .cloned();
+        let mut tree = BTreeMap::new();
+        for (path, content) in files {
+            let oid = state.add_blob(content.as_bytes());
+            tree.insert(
+                path.to_string(),
+                TreeBlob {
+                    oid,
+                    mode: 0o100644,
+                },
+            );
+        }
+        let id = state.add_commit(Commit {
+            parents: parent.into_iter().collect(),
+            subject: subject.to_string(),
+            files: tree,
+        });
+        let upstream = state.upstreams.entry(url.to_string()).or_default();
+        if upstream.default_branch.is_empty() {
+            upstream.default_branch = branch.to_string();
+        }
+        upstream.refs.insert(refname, id.clone());
+        id
+    }
+
+    /// Tag `commit` as `tag` in the upstream at `url`.
+    pub fn tag(&self, url: &str, tag: &str, commit: &str) {
+        let mut state = self.state();
+        let upstream = state.upstreams.entry(url.to_string()).or_default();
+        upstream
+            .refs
+            .insert(format!("refs/tags/{}", tag), commit.to_string());
+    }
+
+    /// The commit `refname` points to in the upstream at `url`.
+    pub fn upstream_ref(&self, url: &str, refname: &str) -> Option<String> {
+        let state = self.state();
+        state.upstreams.get(url)?.refs.get(refname).cloned()
+    }
+
+    /// Every push made so far, in order.
+    pub fn pushes(&self) -> Vec<FakePush> {
+        self.state().pushes.clone()
+    }
+
+    /// Paths of the registered worktrees.
+    pub fn worktrees(&self) -> Vec<PathBuf> {
+        self.state().worktrees.keys().cloned().collect()
+    }
+}
+
+impl GitBackend for FakeBackend {
+    fn toplevel(&self, path: &Path) -> Result<PathBuf> {
+        Ok(fs::canonicalize(path)?)
+    }
+
+    fn commit_id(&self, repo: &Path, rev: &str) -> Result<String> {
+        self.state().resolve(repo, rev)
+    }
+
+    fn path_id(&self, repo: &Path, rev: &str, path: &str) -> Result<String> {
+        let state = self.state();
+        let blobs = state.blobs(repo, rev, path)?;
+        match blobs.get("") {
+            Some(blob) => Ok(blob.oid.clone()),
+            None if blobs.is_empty() => Err(anyhow!("{} does not exist in {}", path, rev)),
+            None => {
+                let listing: String = blobs
+                    .iter()
+                    .map(|(file, blob)| format!("{:o} {} {}\n", blob.mode, blob.oid, file))
+                    .collect();
+                Ok(Oid::hash_object(ObjectType::Tree, listing.as_bytes())?.to_string())
+            }
+        }
+    }
+
+    fn is_file(&self, repo: &Path, rev: &str, path: &str) -> Result<bool> {
+        let state = self.state();
+        let blobs = state.blobs(repo, rev, path)?;
+        if blobs.is_empty() {
+            return Err(anyhow!("{} does not exist in {}", path, rev));
+        }
+        Ok(blobs.contains_key(""))
+    }
+
+    fn read_blob(&self, _repo: &Path, oid: &str) -> Result<Vec<u8>> {
+        self.state()
+            .blobs
+            .get(oid)
+            .cloned()
+            .ok_or_else(|| anyhow!("Unknown blob {}", oid))
+    }
+
+    fn blobs(&self, repo: &Path, rev: &str, path: &str) -> Result<BTreeMap<String, TreeBlob>> {
+        self.state().blobs(repo, rev, path)
+    }
+
+    fn hash_file(&self, _repo: &Path, path: &Path) -> Result<String> {
+        Ok(Oid::hash_file(ObjectType::Blob, path)?.to_string())
+    }
+
+    fn merge_file(
+        &self,
+        _repo: &Path,
+        local: &[u8],
+        base: Option<&[u8]>,
+        upstream: Option<&[u8]>,
+    ) -> Result<(Vec<u8>, bool)> {
+        let sides = [Some(local), base, upstream];
+        if sides.iter().flatten().any(|content| content.contains(&0)) {
+            return Err(anyhow!("Cannot merge binary content"));
+        }
+        let upstream = upstream.unwrap_or_default();
+        if Some(local) == base || local == upstream {
+            return Ok((upstream.to_vec(), false));
+        }
+        if base == Some(upstream) {
+            return Ok((local.to_vec(), false));
+        }
+        let mut merged = b"<<<<<<< local\n".to_vec();
+        for (side, marker) in [
+            (local, "||||||| base\n"),
+            (base.unwrap_or_default(), "=======\n"),
+            (upstream, ">>>>>>> upstream\n"),
+        ] {
+            merged.extend_from_slice(side);
+            if !side.is_empty() && !side.ends_with(b"\n") {
+                merged.push(b'\n');
+            }
+            merged.extend_from_slice(marker.as_bytes());
+        }
+        Ok((merged, true))
+    }
+
+    fn diff_hunks(&self, _repo: &Path, old: &[u8], new: &[u8]) -> Result<String> {
+        super::libgit2::diff_hunks(old, new)
+    }
+
+    fn count(&self, repo: &Path, from: &str, to: &str) -> Result<u32> {
+        Ok(self.state().range(repo, from, to)?.len() as u32)
+    }
+
+    fn log(&self, repo: &Path, from: &str, to: &str, path: &str) -> Result<Vec<(String, String)>> {
+        let state = self.state();
+        let mut commits = Vec::new();
+        for id in state.range(repo, from, to)? {
+            if state.touches(&id, path)? {
+                commits.push((id.clone(), state.commit(&id)?.subject.clone()));
+            }
+        }
+        Ok(commits)
+    }
+
+    fn last_subject(&self, repo: &Path, path: &str) -> Result<Option<String>> {
+        let state = self.state();
+        let Ok(mut id) = state.resolve(repo, "HEAD") else {
+            return Ok(None);
+        };
+        loop {
+            let commit = state.commit(&id)?;
+            if state.touches(&id, path)? {
+                return Ok(Some(commit.subject.clone()));
+            }
+            match commit.parents.first() {
+                Some(parent) => id = parent.clone(),
+                None => return Ok(None),
+            }
+        }
+    }
+
+    fn has_conflicts(&self, _repo: &Path, _prefix: Option<&str>) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn remote_names(&self, _repo: &Path) -> Result<Vec<String>> {
+        Ok(self.state().remotes.keys().cloned().collect())
+    }
+
+    fn remote_urls(&self, _repo: &Path, name: &str) -> Result<(String, String)> {
+        let url =
+            self.state()
+                .remotes
+                .get(name)
+                .cloned()
+                .ok_or_else(|| CrossError::RemoteNotFound {
+                    name: name.to_string(),
+                })?;
+        Ok((url.clone(), url))
+    }
+
+    fn set_remote(&self, _repo: &Path, name: &str, url: &str) -> Result<()> {
+        self.state()
+            .remotes
+            .insert(name.to_string(), url.to_string());
+        Ok(())
+    }
+
+    fn remove_remote(&self, _repo: &Path, name: &str) -> Result<()> {
+        let mut state = self.state();
+        state
+            .remotes
+            .remove(name)
+            .ok_or_else(|| anyhow!("Cannot remove remote {}", name))?;
+        let prefix = format!("refs/remotes/{}/", name);
+        state
+            .refs
+            .retain(|refname, _| !refname.starts_with(&prefix));
+        Ok(())
+    }
+
+    fn default_branch(&self, _repo: &Path, remote: &str) -> Result<String> {
+        let state = self.state();
+        let upstream = state.upstream(remote)?;
+        if upstream.default_branch.is_empty() {
+            let heads = upstream
+                .refs
+                .keys()
+                .filter_map(|r| r.strip_prefix("refs/heads/").map(str::to_string))
+                .collect();
+            return Ok(pick_default_branch(heads));
+        }
+        Ok(upstream.default_branch.clone())
+    }
+
+    fn ls_remote(&self, _repo: &Path, remote: &str) -> Result<Vec<(String, String)>> {
+        let state = self.state();
+        Ok(state
+            .upstream(remote)?
+            .refs
+            .iter()
+            .map(|(refname, id)| (id.clone(), refname.clone()))
+            .collect())
+    }
+
+    fn fetch(&self, _repo: &Path, remote: &str, refspecs: &[String]) -> Result<()> {
+        let mut state = self.state();
+        if !state.remotes.contains_key(remote) {
+            return Err(CrossError::RemoteNotFound {
+                name: remote.to_string(),
+            }
+            .into());
+        }
+        let default = [format!("+refs/heads/*:refs/remotes/{}/*", remote)];
+        let refspecs = if refspecs.is_empty() {
+            &default[..]
+        } else {
+            refspecs
+        };
+        let refs = state.upstream(remote)?.refs.clone();
+        for refspec in refspecs {
+            let refspec = refspec.trim_start_matches('+');
+            let (src, dst) = match refspec.split_once(':') {
+                Some((src, dst)) => (src, Some(dst)),
+                None => (refspec, None),
+            };
+            if let Some(prefix) = src.strip_suffix('*') {
+                let dst = dst.unwrap_or_default().trim_end_matches('*');
+                for (refname, id) in refs.iter() {
+                    if let Some(rest) = refname.strip_prefix(prefix) {
+                        state.refs.insert(format!("{}{}", dst, rest), id.clone());
+                    }
+                }
+                continue;
+            }
+            let id = match refs.get(src) {
+                Some(id) => id.clone(),
+                None if state.commits.contains_key(src) => src.to_string(),
+                None => return Err(anyhow!("Couldn't find remote ref {}", src)),
+            };
+            if let Some(dst) = dst {
+                state.refs.insert(dst.to_string(), id.clone());
+            }
+            state.refs.insert("FETCH_HEAD".to_string(), id);
+        }
+        Ok(())
+    }
+
+    fn push(&self, repo: &Path, remote: &str, refspec: &str, force: bool) -> Result<()> {
+        let mut state = self.state();
+        let (src, dst) = refspec
+            .split_once(':')
+            .ok_or_else(|| anyhow!("Invalid refspec {}", refspec))?;
+        let id = state.resolve(repo, src)?;
+        let url = state.url(remote);
+        let current = state.upstream(remote)?.refs.get(dst).cloned();
+        if let Some(current) = current
+            && !force
+            && !state.ancestors(&id).contains(&current)
+        {
+            return Err(CrossError::PushRejected {
+                remote: remote.to_string(),
+                refspec: refspec.to_string(),
+                reason: "non-fast-forward".to_string(),
+            }
+            .into());
+        }
+        if let Some(upstream) = state.upstreams.get_mut(&url) {
+            upstream.refs.insert(dst.to_string(), id);
+        }
+        state.pushes.push(FakePush {
+            remote: remote.to_string(),
+            refspec: refspec.to_string(),
+            force,
+        });
+        Ok(())
+    }
+
+    fn add_worktree(&self, repo: &Path, path: &Path, commit: &str, sparse: &str) -> Result<()> {
+        let mut state = self.state();
+        if state.worktrees.contains_key(path) {
+            return Err(anyhow!("{} is already a worktree", path.display()));
+        }
+        let id = state.resolve(repo, commit)?;
+        state.materialize(path, &id, sparse)?;
+        state.worktrees.insert(path.to_path_buf(), id);
+        Ok(())
+    }
+
+    fn checkout(&self, worktree: &Path, commit: &str, sparse: &str, _force: bool) -> Result<()> {
+        let mut state = self.state();
+        if !state.worktrees.contains_key(worktree) {
+            return Err(anyhow!("{} is not a worktree", worktree.display()));
+        }
+        let id = state.resolve(worktree, commit)?;
+        state.materialize(worktree, &id, sparse)?;
+        state.worktrees.insert(worktree.to_path_buf(), id);
+        Ok(())
+    }
+
+    fn remove_worktree(&self, _repo: &Path, path: &Path) -> Result<()> {
+        self.state().worktrees.remove(path);
+        if path.exists() {
+            fs::remove_dir_all(path)?;
+        }
+        Ok(())
+    }
+
+    fn list_worktrees(&self, _repo: &Path) -> Result<Vec<PathBuf>> {
+        Ok(self.worktrees())
+    }
+
+    fn prune_worktrees(&self, _repo: &Path) -> Result<Vec<String>> {
+        let mut state = self.state();
+        let gone: Vec<PathBuf> = state
+            .worktrees
+            .keys()
+            .filter(|path| !path.exists())
+            .cloned()
+            .collect();
+        for path in &gone {
+            state.worktrees.remove(path);
+        }
+        Ok(gone
+            .iter()
+            .filter_map(|p| p.file_name())
+            .map(|name| name.to_string_lossy().to_string())
+            .collect())
+    }
+
+    fn commit_path(&self, worktree: &Path, path: &str, message: &str) -> Result<bool> {
+        let mut state = self.state();
+        let head = state.resolve(worktree, "HEAD")?;
+        let mut files: BTreeMap<String, TreeBlob> = state
+            .commit(&head)?
+            .files
+            .iter()
+            .filter(|(file, _)| !within(path, file))
+            .map(|(file, blob)| (file.clone(), blob.clone()))
+            .collect();
+        let dir = if path == "." {
+            worktree.to_path_buf()
+        } else {
+            worktree.join(path)
+        };
+        for file in crate::tree::files(&dir)? {
+            let full = dir.join(&file);
+            let content = crate::tree::read_entry(&full)?;
+            let mode = if fs::symlink_metadata(&full)?.file_type().is_symlink() {
+                0o120000
+            } else if is_executable(&full) {
+                0o100755
+            } else {
+                0o100644
+            };
+            let oid = state.add_blob(&content);
+            let name = if path == "." {
+                file
+            } else {
+                format!("{}/{}", path, file)
+            };
+            files.insert(name, TreeBlob { oid, mode });
+        }
+        if dir.is_file() {
+            let content = fs::read(&dir)?;
+            let oid = state.add_blob(&content);
+            let mode = if is_executable(&dir) {
+                0o100755
+            } else {
+                0o100644
+            };
+            files.insert(path.to_string(), TreeBlob { oid, mode });
+        }
+        if state.commit(&head)?.files == files {
+            return Ok(false);
+        }
+        let id = state.add_commit(Commit {
+            parents: vec![head],
+            subject: message.to_string(),
+            files,
+        });
+        state.worktrees.insert(worktree.to_path_buf(), id);
+        Ok(true)
+    }
+
+    fn move_path(&self, repo: &Path, from: &str, to: &str) -> Result<()> {
+        // The fake main repository has no index.
+        rename_in_workdir(repo, from, to)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::FakeBackend;
+    use crate::repo::CrossRepo;
+    use std::sync::Arc;
+
+    pub(crate) const URL: &str = "https://example.com/demo.git";
+
+    /// An empty repository in a temporary directory running on a fake
+    /// backend.
+    pub(crate) fn repo() -> (tempfile::TempDir, Arc<FakeBackend>, CrossRepo) {
+        let dir = tempfile::tempdir().unwrap();
+        let fake = Arc::new(FakeBackend::new());
+        let repo = CrossRepo::open_with(dir.path(), fake.clone()).unwrap();
+        (dir, fake, repo)
+    }
+}
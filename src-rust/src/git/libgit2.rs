@@ -0,0 +1,821 @@
+//! The default [`GitBackend`]: every operation goes through libgit2 and the
+//! system `git` binary is never run. Errors keep the underlying
+//! [`git2::Error`] as their source.
+
+use super::{GitBackend, TreeBlob, pick_default_branch, rename_in_workdir, within};
+use crate::error::CrossError;
+use anyhow::{Context, Result, anyhow};
+use git2::build::CheckoutBuilder;
+use git2::{
+    AutotagOption, Config, Cred, CredentialType, Direction, FetchOptions, IndexAddOption,
+    IndexEntry, MergeOptions, ObjectType, Oid, PushOptions, Remote, RemoteCallbacks, Repository,
+    Signature, Sort, Time, WorktreeAddOptions, WorktreePruneOptions,
+};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// `skip-worktree` index flag, as set by `git sparse-checkout`.
+const SKIP_WORKTREE: u16 = 1 << 14;
+
+/// One opened repository; [`Libgit2Backend`] opens one per call.
+struct Git {
+    repo: Repository,
+}
+
+impl Git {
+    /// Open the repository containing `path`.
+    fn discover(path: &Path) -> Result<Git> {
+        let repo = Repository::discover(path)
+            .with_context(|| format!("Not a git repository: {}", path.display()))?;
+        Ok(Git { repo })
+    }
+
+    /// Open the repository (or worktree) whose working directory is `path`.
+    fn open(path: &Path) -> Result<Git> {
+        let repo = Repository::open(path)
+            .with_context(|| format!("Cannot open git repository {}", path.display()))?;
+        Ok(Git { repo })
+    }
+
+    /// Absolute working directory, without a trailing slash.
+    fn workdir(&self) -> Result<PathBuf> {
+        let workdir = self
+            .repo
+            .workdir()
+            .ok_or_else(|| anyhow!("Bare repositories are not supported"))?;
+        Ok(fs::canonicalize(workdir)?)
+    }
+
+    fn commit(&self, rev: &str) -> Result<git2::Commit<'_>> {
+        self.repo
+            .revparse_single(rev)
+            .and_then(|obj| obj.peel_to_commit())
+            .with_context(|| format!("Cannot resolve {} to a commit", rev))
+    }
+
+    /// Full SHA of the commit `rev` points to.
+    fn commit_id(&self, rev: &str) -> Result<String> {
+        Ok(self.commit(rev)?.id().to_string())
+    }
+
+    /// Object at `path` in `commit` (its root tree for `.`).
+    fn object_at(
+        &self,
+        commit: &git2::Commit<'_>,
+        path: &str,
+    ) -> Result<Option<(Oid, ObjectType)>> {
+        let tree = commit.tree()?;
+        if path == "." {
+            return Ok(Some((tree.id(), ObjectType::Tree)));
+        }
+        match tree.get_path(Path::new(path)) {
+            Ok(entry) => Ok(Some((entry.id(), entry.kind().unwrap_or(ObjectType::Any)))),
+            Err(e) if e.code() == git2::ErrorCode::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Tree (or blob) id of `path` in commit `rev`.
+    fn path_id(&self, rev: &str, path: &str) -> Result<String> {
+        let commit = self.commit(rev)?;
+        self.object_at(&commit, path)?
+            .map(|(oid, _)| oid.to_string())
+            .ok_or_else(|| anyhow!("{} does not exist in {}", path, rev))
+    }
+
+    /// Whether `path` is a file (not a directory) in commit `rev`.
+    fn is_file(&self, rev: &str, path: &str) -> Result<bool> {
+        let commit = self.commit(rev)?;
+        match self.object_at(&commit, path)? {
+            Some((_, kind)) => Ok(kind == ObjectType::Blob),
+            None => Err(anyhow!("{} does not exist in {}", path, rev)),
+        }
+    }
+
+    fn read_blob(&self, oid: &str) -> Result<Vec<u8>> {
+        let oid = Oid::from_str(oid)?;
+        Ok(self.repo.find_blob(oid)?.content().to_vec())
+    }
+
+    /// Blobs under `path` in commit `rev`, keyed by their path relative to
+    /// `path`. A file is listed under the empty path; a missing path has none.
+    fn blobs(&self, rev: &str, path: &str) -> Result<BTreeMap<String, TreeBlob>> {
+        let commit = self.commit(rev)?;
+        let mut blobs = BTreeMap::new();
+        let tree = match self.object_at(&commit, path)? {
+            None => return Ok(blobs),
+            Some((oid, ObjectType::Tree)) => self.repo.find_tree(oid)?,
+            Some((oid, _)) => {
+                let entry = commit.tree()?.get_path(Path::new(path))?;
+                blobs.insert(
+                    String::new(),
+                    TreeBlob {
+                        oid: oid.to_string(),
+                        mode: entry.filemode(),
+                    },
+                );
+                return Ok(blobs);
+            }
+        };
+        tree.walk(git2::TreeWalkMode::PreOrder, |dir, entry| {
+            if entry.kind() == Some(ObjectType::Blob)
+                && let Some(name) = entry.name()
+            {
+                blobs.insert(
+                    format!("{}{}", dir, name),
+                    TreeBlob {
+                        oid: entry.id().to_string(),
+                        mode: entry.filemode(),
+                    },
+                );
+            }
+            git2::TreeWalkResult::Ok
+        })?;
+        Ok(blobs)
+    }
+
+    /// Three-way merge of file contents, like `git merge-file` with the
+    /// labels `local`, `base` and `upstream`. Returns the merged content and
+    /// whether it has conflicts. Binary content is refused.
+    fn merge_file(
+        &self,
+        local: &[u8],
+        base: Option<&[u8]>,
+        upstream: Option<&[u8]>,
+    ) -> Result<(Vec<u8>, bool)> {
+        let sides = [Some(local), base, upstream];
+        if sides.iter().flatten().any(|content| content.contains(&0)) {
+            return Err(anyhow!("Cannot merge binary content"));
+        }
+        // Merge one-file trees; a conflicted result is checked out with
+        // conflict markers into a scratch directory.
+        let tree = |content: Option<&[u8]>| -> Result<git2::Tree<'_>> {
+            let mut builder = self.repo.treebuilder(None)?;
+            if let Some(content) = content {
+                builder.insert("file", self.repo.blob(content)?, 0o100644)?;
+            }
+            Ok(self.repo.find_tree(builder.write()?)?)
+        };
+        let mut index = self.repo.merge_trees(
+            &tree(base)?,
+            &tree(Some(local))?,
+            &tree(upstream)?,
+            Some(&MergeOptions::new()),
+        )?;
+        if !index.has_conflicts() {
+            let entry = index
+                .get_path(Path::new("file"), 0)
+                .ok_or_else(|| anyhow!("Merge produced no content"))?;
+            return Ok((self.repo.find_blob(entry.id)?.content().to_vec(), false));
+        }
+        let scratch = tempfile::tempdir()?;
+        let mut checkout = CheckoutBuilder::new();
+        checkout
+            .target_dir(scratch.path())
+            .force()
+            .allow_conflicts(true)
+            .conflict_style_merge(true)
+            .our_label("local")
+            .ancestor_label("base")
+            .their_label("upstream");
+        self.repo
+            .checkout_index(Some(&mut index), Some(&mut checkout))?;
+        Ok((fs::read(scratch.path().join("file"))?, true))
+    }
+
+    /// Number of commits reachable from `to` but not from `from`.
+    fn count(&self, from: &str, to: &str) -> Result<u32> {
+        let mut walk = self.repo.revwalk()?;
+        walk.push(self.commit(to)?.id())?;
+        walk.hide(self.commit(from)?.id())?;
+        Ok(walk.count() as u32)
+    }
+
+    /// Whether `commit` changed `path`: its content differs from every parent.
+    fn touches(&self, commit: &git2::Commit<'_>, path: &str) -> Result<bool> {
+        if path == "." {
+            return Ok(true);
+        }
+        let id = self.object_at(commit, path)?.map(|(oid, _)| oid);
+        for parent in commit.parents() {
+            if self.object_at(&parent, path)?.map(|(oid, _)| oid) == id {
+                return Ok(false);
+            }
+        }
+        Ok(commit.parent_count() > 0 || id.is_some())
+    }
+
+    /// `(sha, subject)` of the commits in `from..to` changing `path`, newest first.
+    fn log(&self, from: &str, to: &str, path: &str) -> Result<Vec<(String, String)>> {
+        let mut walk = self.repo.revwalk()?;
+        walk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
+        walk.push(self.commit(to)?.id())?;
+        walk.hide(self.commit(from)?.id())?;
+        let mut commits = Vec::new();
+        for oid in walk {
+            let commit = self.repo.find_commit(oid?)?;
+            if self.touches(&commit, path)? {
+                let subject = commit.summary().unwrap_or_default().to_string();
+                commits.push((commit.id().to_string(), subject));
+            }
+        }
+        Ok(commits)
+    }
+
+    /// Subject of the newest commit on `HEAD` changing `path`.
+    fn last_subject(&self, path: &str) -> Result<Option<String>> {
+        let mut walk = self.repo.revwalk()?;
+        walk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
+        walk.push_head()?;
+        for oid in walk {
+            let commit = self.repo.find_commit(oid?)?;
+            if self.touches(&commit, path)? {
+                return Ok(commit.summary().map(str::to_string));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Whether the index has unmerged entries, optionally only below `prefix`.
+    fn has_conflicts(&self, prefix: Option<&str>) -> Result<bool> {
+        let index = self.repo.index()?;
+        if !index.has_conflicts() {
+            return Ok(false);
+        }
+        let Some(prefix) = prefix else {
+            return Ok(true);
+        };
+        for conflict in index.conflicts()? {
+            let conflict = conflict?;
+            let paths = [conflict.ancestor, conflict.our, conflict.their];
+            if paths
+                .iter()
+                .flatten()
+                .any(|e| within(prefix, &String::from_utf8_lossy(&e.path)))
+            {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn remote_names(&self) -> Result<Vec<String>> {
+        Ok(self
+            .repo
+            .remotes()?
+            .iter()
+            .flatten()
+            .map(str::to_string)
+            .collect())
+    }
+
+    fn has_remote(&self, name: &str) -> bool {
+        self.repo.find_remote(name).is_ok()
+    }
+
+    /// Fetch and push URL of a remote.
+    fn remote_urls(&self, name: &str) -> Result<(String, String)> {
+        let remote = self.find_remote(name)?;
+        let fetch = remote.url().unwrap_or_default().to_string();
+        let push = remote
+            .pushurl()
+            .map(str::to_string)
+            .unwrap_or(fetch.clone());
+        Ok((fetch, push))
+    }
+
+    /// Add a remote, or point an existing one at `url`.
+    fn set_remote(&self, name: &str, url: &str) -> Result<()> {
+        if self.has_remote(name) {
+            self.repo.remote_set_url(name, url)?;
+        } else {
+            self.repo.remote(name, url)?;
+        }
+        Ok(())
+    }
+
+    fn remove_remote(&self, name: &str) -> Result<()> {
+        self.repo
+            .remote_delete(name)
+            .with_context(|| format!("Cannot remove remote {}", name))
+    }
+
+    /// Default branch of a remote (name or URL), asked over the network.
+    fn default_branch(&self, remote: &str) -> Result<String> {
+        let mut handle = match self.repo.find_remote(remote) {
+            Ok(handle) => handle,
+            Err(_) => self.repo.remote_anonymous(remote)?,
+        };
+        default_branch_of(&mut handle, &self.repo.config()?)
+    }
+
+    fn find_remote(&self, name: &str) -> Result<Remote<'_>> {
+        match self.repo.find_remote(name) {
+            Err(e) if e.code() == git2::ErrorCode::NotFound => Err(CrossError::RemoteNotFound {
+                name: name.to_string(),
+            }
+            .into()),
+            result => Ok(result?),
+        }
+    }
+
+    /// Refs advertised by a remote (name or URL) as `(sha, refname)` pairs,
+    /// including the peeled `^{}` entries of annotated tags.
+    fn ls_remote(&self, remote: &str) -> Result<Vec<(String, String)>> {
+        let mut handle = match self.repo.find_remote(remote) {
+            Ok(handle) => handle,
+            Err(_) => self.repo.remote_anonymous(remote)?,
+        };
+        let config = self.repo.config()?;
+        let connection = handle
+            .connect_auth(Direction::Fetch, Some(callbacks(&config)), None)
+            .with_context(|| format!("Cannot connect to {}", remote))?;
+        Ok(connection
+            .list()?
+            .iter()
+            .map(|head| (head.oid().to_string(), head.name().to_string()))
+            .collect())
+    }
+
+    /// Fetch `refspecs` (the configured ones when empty) from `remote`.
+    /// Tags are never stored locally unless a refspec asks for them.
+    fn fetch(&self, remote: &str, refspecs: &[String]) -> Result<()> {
+        let mut handle = self.find_remote(remote)?;
+        let config = self.repo.config()?;
+        let mut opts = FetchOptions::new();
+        opts.remote_callbacks(callbacks(&config))
+            .download_tags(AutotagOption::None);
+        handle
+            .fetch(refspecs, Some(&mut opts), None)
+            .with_context(|| format!("Cannot fetch {} from {}", refspecs.join(" "), remote))
+    }
+
+    /// Push `refspec` (`HEAD:refs/heads/x`) to `remote`. Rejected updates
+    /// are errors.
+    fn push(&self, remote: &str, refspec: &str, force: bool) -> Result<()> {
+        let mut handle = self.find_remote(remote)?;
+        if let Some(target) = handle.url().and_then(local_checkout) {
+            return self
+                .push_local(&target, remote, refspec, force)
+                .with_context(|| format!("Cannot push {} to {}", refspec, remote));
+        }
+        let config = self.repo.config()?;
+        let rejected: RefCell<Vec<String>> = RefCell::new(Vec::new());
+        let mut callbacks = callbacks(&config);
+        callbacks.push_update_reference(|name, status| {
+            if let Some(status) = status {
+                rejected.borrow_mut().push(format!("{} ({})", name, status));
+            }
+            Ok(())
+        });
+        let mut opts = PushOptions::new();
+        opts.remote_callbacks(callbacks);
+        let refspec = if force {
+            format!("+{}", refspec)
+        } else {
+            refspec.to_string()
+        };
+        let rejection = |reason: String| CrossError::PushRejected {
+            remote: remote.to_string(),
+            refspec: refspec.clone(),
+            reason,
+        };
+        match handle.push(&[refspec.as_str()], Some(&mut opts)) {
+            Err(e) if e.code() == git2::ErrorCode::NotFastForward => {
+                return Err(rejection(e.message().to_string()).into());
+            }
+            result => {
+                result.with_context(|| format!("Cannot push {} to {}", refspec, remote))?;
+            }
+        }
+        drop(opts);
+        let rejected = rejected.into_inner();
+        if !rejected.is_empty() {
+            return Err(rejection(rejected.join(", ")).into());
+        }
+        Ok(())
+    }
+
+    /// libgit2 cannot push to a repository with a working directory, so
+    /// copy the objects over and update the ref directly, refusing what
+    /// `git push` would refuse.
+    fn push_local(
+        &self,
+        target: &Repository,
+        remote: &str,
+        refspec: &str,
+        force: bool,
+    ) -> Result<()> {
+        let rejection = |reason: String| CrossError::PushRejected {
+            remote: remote.to_string(),
+            refspec: refspec.to_string(),
+            reason,
+        };
+        let (src, dst) = refspec
+            .split_once(':')
+            .ok_or_else(|| anyhow!("Invalid refspec {}", refspec))?;
+        let commit = self.commit(src)?.id();
+        let current = target.refname_to_id(dst).ok();
+        if current == Some(commit) {
+            return Ok(());
+        }
+
+        let checked_out = target
+            .find_reference("HEAD")
+            .ok()
+            .and_then(|head| head.symbolic_target().map(str::to_string));
+        if checked_out.as_deref() == Some(dst) {
+            let policy = target
+                .config()?
+                .get_string("receive.denyCurrentBranch")
+                .unwrap_or_default();
+            if !matches!(policy.as_str(), "ignore" | "warn" | "false") {
+                return Err(rejection(format!("{} is checked out", dst)).into());
+            }
+        }
+
+        let mut source = target.remote_anonymous(&self.workdir()?.to_string_lossy())?;
+        let mut opts = FetchOptions::new();
+        opts.download_tags(AutotagOption::None)
+            .update_fetchhead(false);
+        source.fetch(&[src], Some(&mut opts), None)?;
+        if let Some(current) = current
+            && !force
+            && !target.graph_descendant_of(commit, current)?
+        {
+            return Err(rejection("non-fast-forward".to_string()).into());
+        }
+        target.reference(dst, commit, true, "push from git-cross")?;
+        Ok(())
+    }
+
+    /// Create a detached worktree at `path` with only `sparse` (a path in
+    /// the commit, `.` for everything) checked out.
+    fn add_worktree(&self, path: &Path, commit: &str, sparse: &str) -> Result<()> {
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .ok_or_else(|| anyhow!("Invalid worktree path {}", path.display()))?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // libgit2 checks out the whole branch a worktree is created on, so
+        // start from a branch at an empty commit and check out the sparse
+        // path afterwards. The branch is only needed until HEAD is detached.
+        let empty_tree = self.repo.find_tree(self.repo.treebuilder(None)?.write()?)?;
+        let signature = Signature::new("git-cross", "git-cross", &Time::new(0, 0))?;
+        let empty = self.repo.commit(
+            None,
+            &signature,
+            &signature,
+            "git-cross empty worktree",
+            &empty_tree,
+            &[],
+        )?;
+        let branch_name = format!("git-cross/{}", name);
+        let mut branch = self
+            .repo
+            .branch(&branch_name, &self.repo.find_commit(empty)?, true)?;
+        let mut opts = WorktreeAddOptions::new();
+        opts.reference(Some(branch.get()));
+        let added = self.repo.worktree(&name, path, Some(&opts));
+        let result = added
+            .with_context(|| format!("Cannot add worktree {}", path.display()))
+            .and_then(|_| Git::open(path)?.checkout(commit, sparse, true));
+        branch.delete()?;
+        result
+    }
+
+    /// Detach `HEAD` at `commit` and check out only `sparse`. With `force`
+    /// local changes there are discarded, otherwise they make it fail. Like
+    /// `git sparse-checkout`, the index keeps every path and marks the ones
+    /// outside `sparse` as skip-worktree.
+    fn checkout(&self, commit: &str, sparse: &str, force: bool) -> Result<()> {
+        let commit = self.commit(commit)?;
+        let mut checkout = CheckoutBuilder::new();
+        if force {
+            checkout.force();
+        } else {
+            checkout.safe();
+        }
+        if sparse != "." {
+            checkout.path(sparse);
+        }
+        match self
+            .repo
+            .checkout_tree(commit.as_object(), Some(&mut checkout))
+        {
+            Err(e) if e.code() == git2::ErrorCode::Conflict => {
+                return Err(CrossError::DirtyWorkingTree {
+                    path: self.workdir()?.display().to_string(),
+                }
+                .into());
+            }
+            result => result?,
+        }
+        self.repo.set_head_detached(commit.id())?;
+
+        let mut index = self.repo.index()?;
+        index.read_tree(&commit.tree()?)?;
+        if sparse != "." {
+            let outside: Vec<git2::IndexEntry> = index
+                .iter()
+                .filter(|e| !within(sparse, &String::from_utf8_lossy(&e.path)))
+                .collect();
+            for mut entry in outside {
+                entry.flags_extended |= SKIP_WORKTREE;
+                index.add(&entry)?;
+            }
+        }
+        index.write()?;
+        Ok(())
+    }
+
+    /// Remove the worktree at `path` with its working directory, even when
+    /// it has local changes.
+    fn remove_worktree(&self, path: &Path) -> Result<()> {
+        let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        for name in self.repo.worktrees()?.iter().flatten() {
+            let worktree = self.repo.find_worktree(name)?;
+            let wt_path =
+                fs::canonicalize(worktree.path()).unwrap_or_else(|_| worktree.path().to_path_buf());
+            if wt_path == target {
+                let mut opts = WorktreePruneOptions::new();
+                opts.valid(true).locked(true).working_tree(true);
+                worktree.prune(Some(&mut opts))?;
+                return Ok(());
+            }
+        }
+        if path.exists() {
+            fs::remove_dir_all(path)?;
+        }
+        Ok(())
+    }
+
+    /// Paths of the registered linked worktrees, including those whose
+    /// directory is gone.
+    fn list_worktrees(&self) -> Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        for name in self.repo.worktrees()?.iter().flatten() {
+            paths.push(self.repo.find_worktree(name)?.path().to_path_buf());
+        }
+        Ok(paths)
+    }
+
+    /// Forget worktrees whose directory is gone. Returns their names.
+    fn prune_worktrees(&self) -> Result<Vec<String>> {
+        let mut pruned = Vec::new();
+        for name in self.repo.worktrees()?.iter().flatten() {
+            let worktree = self.repo.find_worktree(name)?;
+            if worktree.validate().is_err() && worktree.is_prunable(None)? {
+                worktree.prune(None)?;
+                pruned.push(name.to_string());
+            }
+        }
+        Ok(pruned)
+    }
+
+    /// Stage everything below `path` and commit it on `HEAD` if anything
+    /// changed. Returns whether a commit was made.
+    fn commit_path(&self, path: &str, message: &str) -> Result<bool> {
+        let pathspec = if path == "." { "*" } else { path };
+        let mut index = self.repo.index()?;
+        index.add_all([pathspec], IndexAddOption::DEFAULT, None)?;
+        index.update_all([pathspec], None)?;
+        index.write()?;
+        let tree = self.repo.find_tree(index.write_tree()?)?;
+        let head = self.repo.head()?.peel_to_commit()?;
+        if head.tree_id() == tree.id() {
+            return Ok(false);
+        }
+        let signature = self
+            .repo
+            .signature()
+            .context("Set user.name and user.email to commit")?;
+        self.repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &[&head],
+        )?;
+        Ok(true)
+    }
+
+    fn move_path(&self, from: &str, to: &str) -> Result<()> {
+        rename_in_workdir(&self.workdir()?, from, to)?;
+        let mut index = self.repo.index()?;
+        let moved: Vec<IndexEntry> = index
+            .iter()
+            .filter(|entry| within(from, &String::from_utf8_lossy(&entry.path)))
+            .collect();
+        for mut entry in moved {
+            let path = String::from_utf8_lossy(&entry.path).to_string();
+            let stage = i32::from((entry.flags >> 12) & 0x3);
+            index.remove(Path::new(&path), stage)?;
+            entry.path = format!("{}{}", to, &path[from.len()..]).into_bytes();
+            index.add(&entry)?;
+        }
+        index.write()?;
+        Ok(())
+    }
+}
+
+/// Hunks of the unified diff between two buffers; needs no repository.
+pub(super) fn diff_hunks(old: &[u8], new: &[u8]) -> Result<String> {
+    let mut patch = git2::Patch::from_buffers(old, None, new, None, None)?;
+    let mut body = Vec::new();
+    patch.print(&mut |_, _, line| {
+        match line.origin() {
+            'F' => {}
+            origin @ ('+' | '-' | ' ') => {
+                body.push(origin as u8);
+                body.extend_from_slice(line.content());
+            }
+            _ => body.extend_from_slice(line.content()),
+        }
+        true
+    })?;
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+/// Runs every operation through libgit2, opening the repository per call.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Libgit2Backend;
+
+impl GitBackend for Libgit2Backend {
+    fn toplevel(&self, path: &Path) -> Result<PathBuf> {
+        Git::discover(path)?.workdir()
+    }
+
+    fn commit_id(&self, repo: &Path, rev: &str) -> Result<String> {
+        Git::open(repo)?.commit_id(rev)
+    }
+
+    fn path_id(&self, repo: &Path, rev: &str, path: &str) -> Result<String> {
+        Git::open(repo)?.path_id(rev, path)
+    }
+
+    fn is_file(&self, repo: &Path, rev: &str, path: &str) -> Result<bool> {
+        Git::open(repo)?.is_file(rev, path)
+    }
+
+    fn read_blob(&self, repo: &Path, oid: &str) -> Result<Vec<u8>> {
+        Git::open(repo)?.read_blob(oid)
+    }
+
+    fn blobs(&self, repo: &Path, rev: &str, path: &str) -> Result<BTreeMap<String, TreeBlob>> {
+        Git::open(repo)?.blobs(rev, path)
+    }
+
+    fn hash_file(&self, _repo: &Path, path: &Path) -> Result<String> {
+        Ok(Oid::hash_file(ObjectType::Blob, path)?.to_string())
+    }
+
+    fn merge_file(
+        &self,
+        repo: &Path,
+        local: &[u8],
+        base: Option<&[u8]>,
+        upstream: Option<&[u8]>,
+    ) -> Result<(Vec<u8>, bool)> {
+        Git::open(repo)?.merge_file(local, base, upstream)
+    }
+
+    fn diff_hunks(&self, _repo: &Path, old: &[u8], new: &[u8]) -> Result<String> {
+        diff_hunks(old, new)
+    }
+
+    fn count(&self, repo: &Path, from: &str, to: &str) -> Result<u32> {
+        Git::open(repo)?.count(from, to)
+    }
+
+    fn log(&self, repo: &Path, from: &str, to: &str, path: &str) -> Result<Vec<(String, String)>> {
+        Git::open(repo)?.log(from, to, path)
+    }
+
+    fn last_subject(&self, repo: &Path, path: &str) -> Result<Option<String>> {
+        Git::open(repo)?.last_subject(path)
+    }
+
+    fn has_conflicts(&self, repo: &Path, prefix: Option<&str>) -> Result<bool> {
+        Git::open(repo)?.has_conflicts(prefix)
+    }
+
+    fn remote_names(&self, repo: &Path) -> Result<Vec<String>> {
+        Git::open(repo)?.remote_names()
+    }
+
+    fn remote_urls(&self, repo: &Path, name: &str) -> Result<(String, String)> {
+        Git::open(repo)?.remote_urls(name)
+    }
+
+    fn set_remote(&self, repo: &Path, name: &str, url: &str) -> Result<()> {
+        Git::open(repo)?.set_remote(name, url)
+    }
+
+    fn remove_remote(&self, repo: &Path, name: &str) -> Result<()> {
+        Git::open(repo)?.remove_remote(name)
+    }
+
+    fn default_branch(&self, repo: &Path, remote: &str) -> Result<String> {
+        Git::open(repo)?.default_branch(remote)
+    }
+
+    fn ls_remote(&self, repo: &Path, remote: &str) -> Result<Vec<(String, String)>> {
+        Git::open(repo)?.ls_remote(remote)
+    }
+
+    fn fetch(&self, repo: &Path, remote: &str, refspecs: &[String]) -> Result<()> {
+        Git::open(repo)?.fetch(remote, refspecs)
+    }
+
+    fn push(&self, repo: &Path, remote: &str, refspec: &str, force: bool) -> Result<()> {
+        Git::open(repo)?.push(remote, refspec, force)
+    }
+
+    fn add_worktree(&self, repo: &Path, path: &Path, commit: &str, sparse: &str) -> Result<()> {
+        Git::open(repo)?.add_worktree(path, commit, sparse)
+    }
+
+    fn checkout(&self, worktree: &Path, commit: &str, sparse: &str, force: bool) -> Result<()> {
+        Git::open(worktree)?.checkout(commit, sparse, force)
+    }
+
+    fn remove_worktree(&self, repo: &Path, path: &Path) -> Result<()> {
+        Git::open(repo)?.remove_worktree(path)
+    }
+
+    fn list_worktrees(&self, repo: &Path) -> Result<Vec<PathBuf>> {
+        Git::open(repo)?.list_worktrees()
+    }
+
+    fn prune_worktrees(&self, repo: &Path) -> Result<Vec<String>> {
+        Git::open(repo)?.prune_worktrees()
+    }
+
+    fn commit_path(&self, worktree: &Path, path: &str, message: &str) -> Result<bool> {
+        Git::open(worktree)?.commit_path(path, message)
+    }
+
+    fn move_path(&self, repo: &Path, from: &str, to: &str) -> Result<()> {
+        Git::open(repo)?.move_path(from, to)
+    }
+}
+
+/// The non-bare repository a local remote URL points to.
+fn local_checkout(url: &str) -> Option<Repository> {
+    let path = match url.strip_prefix("file://") {
+        Some(path) => path,
+        None if !url.contains("://") && !url.contains(':') => url,
+        None => return None,
+    };
+    Repository::open(path).ok().filter(|repo| !repo.is_bare())
+}
+
+/// Authentication for fetch and push: the SSH agent for SSH URLs, git
+/// credential helpers for HTTPS.
+fn callbacks(config: &Config) -> RemoteCallbacks<'_> {
+    let mut callbacks = RemoteCallbacks::new();
+    let mut attempts = 0;
+    callbacks.credentials(move |url, username, allowed| {
+        attempts += 1;
+        if attempts > 3 {
+            return Err(git2::Error::from_str("authentication failed"));
+        }
+        let user = username.unwrap_or("git");
+        if allowed.contains(CredentialType::SSH_KEY) {
+            Cred::ssh_key_from_agent(user)
+        } else if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) {
+            Cred::credential_helper(config, url, username)
+        } else if allowed.contains(CredentialType::USERNAME) {
+            Cred::username(user)
+        } else {
+            Cred::default()
+        }
+    });
+    callbacks
+}
+
+/// Default branch advertised by a connected remote, falling back to
+/// `main`/`master` or the first head.
+fn default_branch_of(remote: &mut Remote<'_>, config: &Config) -> Result<String> {
+    let connection = remote.connect_auth(Direction::Fetch, Some(callbacks(config)), None)?;
+    if let Ok(buf) = connection.default_branch()
+        && let Some(branch) = buf.as_str().and_then(|n| n.strip_prefix("refs/heads/"))
+        && !branch.is_empty()
+    {
+        return Ok(branch.to_string());
+    }
+    let heads: Vec<String> = connection
+        .list()?
+        .iter()
+        .filter_map(|head| head.name().strip_prefix("refs/heads/"))
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect();
+    Ok(pick_default_branch(heads))
+}
@@ -0,0 +1,220 @@
+//! The git layer.
+//!
+//! Every git operation of git-cross goes through a [`GitBackend`]. Each call
+//! names the repository it acts on by its working directory: the main
+//! checkout or one of the patch worktrees. Three backends exist:
+//!
+//! - [`Libgit2Backend`] (the default) links libgit2 and never runs `git`.
+//! - [`CliBackend`] runs the system `git` binary, for setups libgit2 does not
+//!   cover (e.g. custom transports or credential setups).
+//! - [`FakeBackend`] keeps upstream repositories in memory, so the planning
+//!   and merge logic can be tested without any real repository.
+
+mod cli;
+mod fake;
+mod libgit2;
+
+pub use cli::CliBackend;
+#[cfg(test)]
+pub(crate) use fake::testing;
+pub use fake::{FakeBackend, FakePush};
+pub use libgit2::Libgit2Backend;
+
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A blob in an upstream tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeBlob {
+    pub oid: String,
+    /// Git file mode (`0o100644`, `0o100755` or `0o120000`).
+    pub mode: i32,
+}
+
+impl TreeBlob {
+    pub fn executable(&self) -> bool {
+        self.mode == 0o100755
+    }
+
+    pub fn symlink(&self) -> bool {
+        self.mode == 0o120000
+    }
+}
+
+/// Git operations used by git-cross. `repo` is always the working directory
+/// of the repository (or worktree) to act on; revisions, refs and remotes are
+/// resolved in it.
+pub trait GitBackend: fmt::Debug + Send + Sync {
+    /// Top-level working directory of the repository containing `path`.
+    fn toplevel(&self, path: &Path) -> Result<PathBuf>;
+
+    /// Full SHA of the commit `rev` points to.
+    fn commit_id(&self, repo: &Path, rev: &str) -> Result<String>;
+
+    fn has_commit(&self, repo: &Path, rev: &str) -> bool {
+        self.commit_id(repo, rev).is_ok()
+    }
+
+    /// Tree (or blob) id of `path` in commit `rev`; the root tree for `.`.
+    fn path_id(&self, repo: &Path, rev: &str, path: &str) -> Result<String>;
+
+    /// Whether `path` is a file (not a directory) in commit `rev`.
+    fn is_file(&self, repo: &Path, rev: &str, path: &str) -> Result<bool>;
+
+    fn read_blob(&self, repo: &Path, oid: &str) -> Result<Vec<u8>>;
+
+    /// Blobs under `path` in commit `rev`, keyed by their path relative to
+    /// `path`. A file is listed under the empty path; a missing path has none.
+    fn blobs(&self, repo: &Path, rev: &str, path: &str) -> Result<BTreeMap<String, TreeBlob>>;
+
+    /// Blob id the file `path` on disk would get.
+    fn hash_file(&self, repo: &Path, path: &Path) -> Result<String>;
+
+    /// Three-way merge of file contents, like `git merge-file` with the
+    /// labels `local`, `base` and `upstream`. Returns the merged content and
+    /// whether it has conflicts. Content that cannot be merged as text is
+    /// an error.
+    fn merge_file(
+        &self,
+        repo: &Path,
+        local: &[u8],
+        base: Option<&[u8]>,
+        upstream: Option<&[u8]>,
+    ) -> Result<(Vec<u8>, bool)>;
+
+    /// Hunks of the unified diff from `old` to `new`, without file headers,
+    /// as `git diff --no-index` prints them. Binary content gives a single
+    /// `Binary files ... differ` line.
+    fn diff_hunks(&self, repo: &Path, old: &[u8], new: &[u8]) -> Result<String>;
+
+    /// Number of commits reachable from `to` but not from `from`.
+    fn count(&self, repo: &Path, from: &str, to: &str) -> Result<u32>;
+
+    /// `(sha, subject)` of the commits in `from..to` changing `path`, newest first.
+    fn log(&self, repo: &Path, from: &str, to: &str, path: &str) -> Result<Vec<(String, String)>>;
+
+    /// Subject of the newest commit on `HEAD` changing `path`.
+    fn last_subject(&self, repo: &Path, path: &str) -> Result<Option<String>>;
+
+    /// Whether the index has unmerged entries, optionally only below `prefix`.
+    fn has_conflicts(&self, repo: &Path, prefix: Option<&str>) -> Result<bool>;
+
+    fn remote_names(&self, repo: &Path) -> Result<Vec<String>>;
+
+    fn has_remote(&self, repo: &Path, name: &str) -> bool {
+        self.remote_names(repo)
+            .is_ok_and(|names| names.iter().any(|n| n == name))
+    }
+
+    /// Fetch and push URL of a remote.
+    fn remote_urls(&self, repo: &Path, name: &str) -> Result<(String, String)>;
+
+    /// Add a remote, or point an existing one at `url`.
+    fn set_remote(&self, repo: &Path, name: &str, url: &str) -> Result<()>;
+
+    fn remove_remote(&self, repo: &Path, name: &str) -> Result<()>;
+
+    /// Default branch of a remote (name or URL), asked over the network.
+    fn default_branch(&self, repo: &Path, remote: &str) -> Result<String>;
+
+    /// Refs advertised by a remote (name or URL) as `(sha, refname)` pairs,
+    /// including the peeled `^{}` entries of annotated tags.
+    fn ls_remote(&self, repo: &Path, remote: &str) -> Result<Vec<(String, String)>>;
+
+    /// Fetch `refspecs` (the configured ones when empty) from `remote`.
+    /// Tags are never stored locally unless a refspec asks for them.
+    fn fetch(&self, repo: &Path, remote: &str, refspecs: &[String]) -> Result<()>;
+
+    /// Fetch the branch `branch` into `refs/remotes/<remote>/<branch>`.
+    fn fetch_branch(&self, repo: &Path, remote: &str, branch: &str) -> Result<()> {
+        let refspec = format!("+refs/heads/{}:refs/remotes/{}/{}", branch, remote, branch);
+        self.fetch(repo, remote, &[refspec])
+    }
+
+    /// Fetch one tag without creating a local tag; returns its commit.
+    fn fetch_tag(&self, repo: &Path, remote: &str, tag: &str) -> Result<String> {
+        self.fetch(repo, remote, &[format!("refs/tags/{}", tag)])?;
+        self.commit_id(repo, "FETCH_HEAD")
+    }
+
+    /// Push `refspec` (`HEAD:refs/heads/x`) to `remote`. Rejected updates
+    /// are errors.
+    fn push(&self, repo: &Path, remote: &str, refspec: &str, force: bool) -> Result<()>;
+
+    /// Create a worktree at `path` detached at `commit`, with only `sparse`
+    /// (a path in the commit, `.` for everything) checked out.
+    fn add_worktree(&self, repo: &Path, path: &Path, commit: &str, sparse: &str) -> Result<()>;
+
+    /// Detach the worktree `HEAD` at `commit` and check out only `sparse`.
+    /// With `force` local changes there are discarded, otherwise they make
+    /// it fail.
+    fn checkout(&self, worktree: &Path, commit: &str, sparse: &str, force: bool) -> Result<()>;
+
+    /// Remove the worktree at `path` with its working directory, even when
+    /// it has local changes.
+    fn remove_worktree(&self, repo: &Path, path: &Path) -> Result<()>;
+
+    /// Paths of the linked worktrees registered in the repository, including
+    /// those whose directory is gone.
+    fn list_worktrees(&self, repo: &Path) -> Result<Vec<PathBuf>>;
+
+    /// Forget worktrees whose directory is gone. Returns their names.
+    fn prune_worktrees(&self, repo: &Path) -> Result<Vec<String>>;
+
+    /// Stage everything below `path` in a worktree and commit it on `HEAD`
+    /// if anything changed. Returns whether a commit was made.
+    fn commit_path(&self, worktree: &Path, path: &str, message: &str) -> Result<bool>;
+
+    /// Move the repository path `from` to `to` like `git mv`: the files
+    /// (tracked or not) are renamed and so are the index entries below
+    /// `from`, keeping what was staged.
+    fn move_path(&self, repo: &Path, from: &str, to: &str) -> Result<()>;
+}
+
+/// Rename `from` to `to` below `workdir`, creating the parents of `to`.
+fn rename_in_workdir(workdir: &Path, from: &str, to: &str) -> Result<()> {
+    let dst = workdir.join(to);
+    if let Some(parent) = dst.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(workdir.join(from), &dst).with_context(|| format!("Cannot move {} to {}", from, to))
+}
+
+/// Whether the repository path `path` is `prefix` or below it.
+fn within(prefix: &str, path: &str) -> bool {
+    prefix == "."
+        || path == prefix
+        || path
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// `path` relative to `prefix`: the empty path for `prefix` itself.
+fn relative<'a>(prefix: &str, path: &'a str) -> &'a str {
+    if prefix == "." {
+        return path;
+    }
+    if path == prefix {
+        return "";
+    }
+    path.strip_prefix(prefix)
+        .and_then(|rest| rest.strip_prefix('/'))
+        .unwrap_or(path)
+}
+
+/// Default branch among advertised `refs/heads/*` names: `main`, `master`
+/// or the first one.
+fn pick_default_branch(heads: Vec<String>) -> String {
+    for candidate in ["main", "master"] {
+        if heads.iter().any(|h| h == candidate) {
+            return candidate.to_string();
+        }
+    }
+    heads
+        .into_iter()
+        .next()
+        .unwrap_or_else(|| "main".to_string())
+}
@@ -0,0 +1,58 @@
+//! git-cross as a library.
+//!
+//! [`CrossRepo`] wraps a git repository and exposes the operations of the
+//! `git-cross` CLI (`use_remote`, `add_patch`, `sync`, `push`, `status`,
+//! `outdated`, ...) as functions returning structured results. Every mutating
+//! operation is also available as a `plan_*` function returning the [`Plan`]
+//! it would execute, which is what the CLI prints for `--dry`.
+//!
+//! Git operations go through a [`GitBackend`]: libgit2 by default, the `git`
+//! binary with [`CrossRepo::open_with`] and [`CliBackend`], or the in-memory
+//! [`FakeBackend`] in tests.
+//!
+//! The library never prints; reporting is left to the caller.
+
+pub mod apply;
+pub mod crossfile;
+pub mod diff;
+pub mod doctor;
+pub mod error;
+pub mod filter;
+pub mod git;
+pub mod lock;
+pub mod metadata;
+pub mod oplock;
+pub mod outdated;
+pub mod plan;
+pub mod push;
+pub mod repair;
+pub mod repo;
+pub mod retarget;
+pub mod spec;
+pub mod status;
+pub mod sync;
+pub mod tags;
+pub mod tree;
+mod util;
+
+pub use apply::{ApplyAction, ApplyChange, ApplyKind};
+pub use crossfile::{Crossfile, Statement};
+pub use diff::{ChangeKind, FileChange, PatchDiff};
+pub use doctor::{Finding, Severity};
+pub use error::CrossError;
+pub use filter::{PathFilter, PathMatcher};
+pub use git::{CliBackend, FakeBackend, GitBackend, Libgit2Backend};
+pub use lock::{LockedPatch, Lockfile};
+pub use metadata::{Metadata, Patch, RefKind};
+pub use oplock::OperationLock;
+pub use outdated::{OutdatedPatch, UpstreamCommit};
+pub use plan::{Op, Plan};
+pub use push::{PushOptions, PushPlan};
+pub use repair::{RepairedPatch, VendoredState};
+pub use repo::{CrossRepo, PatchOptions, PatchPlan, PrunePlan, Remote, RemoteUrls, UsePlan};
+pub use spec::PatchSpec;
+pub use status::{DiffState, PatchStatus, StatusCategory};
+pub use sync::{SyncOutcome, SyncPlan, SyncResult};
+pub use tags::UpstreamTag;
+pub use tree::TreeChanges;
+pub use util::normalize_local_path;
@@ -0,0 +1,96 @@
+//! `Crossfile.lock`: the exact upstream commit every patch was vendored from.
+//!
+//! The lock sits next to the Crossfile and is meant to be committed. `patch`
+//! (and therefore `replay`) checks out the locked commit when the entry still
+//! matches the requested spec; `sync` moves the entry to the new upstream tip.
+
+use crate::error::CrossError;
+use crate::util::write_atomic;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+pub const LOCKFILE_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LockedPatch {
+    pub local_path: String,
+    pub remote: String,
+    pub branch: String,
+    pub remote_path: String,
+    /// Upstream commit the patch content was taken from.
+    pub commit: String,
+    /// Tree hash of `remote_path` at `commit`.
+    pub tree: String,
+    /// Tag a version-range patch resolved to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+}
+
+impl LockedPatch {
+    /// Whether this entry was produced for the same upstream source.
+    pub fn matches(&self, remote: &str, branch: &str, remote_path: &str) -> bool {
+        self.remote == remote && self.branch == branch && self.remote_path == remote_path
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Lockfile {
+    pub version: u32,
+    pub patches: Vec<LockedPatch>,
+}
+
+impl Default for Lockfile {
+    fn default() -> Self {
+        Lockfile {
+            version: LOCKFILE_VERSION,
+            patches: Vec::new(),
+        }
+    }
+}
+
+impl Lockfile {
+    pub fn load(path: &Path) -> Result<Lockfile> {
+        if !path.exists() {
+            return Ok(Lockfile::default());
+        }
+        let content = fs::read_to_string(path)?;
+        let lock: Lockfile =
+            serde_json::from_str(&content).map_err(|e| CrossError::MetadataCorrupt {
+                path: path.to_path_buf(),
+                reason: e.to_string(),
+            })?;
+        if lock.version > LOCKFILE_VERSION {
+            return Err(CrossError::UnsupportedVersion {
+                path: path.to_path_buf(),
+                version: lock.version,
+                supported: LOCKFILE_VERSION,
+            }
+            .into());
+        }
+        Ok(lock)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut content = serde_json::to_string_pretty(self)?;
+        content.push('\n');
+        write_atomic(path, content.as_bytes())
+    }
+
+    pub fn find(&self, local_path: &str) -> Option<&LockedPatch> {
+        self.patches.iter().find(|p| p.local_path == local_path)
+    }
+
+    /// Insert or replace the entry for `entry.local_path`, keeping entries
+    /// sorted so the file diffs cleanly.
+    pub fn upsert(&mut self, entry: LockedPatch) {
+        self.remove(&entry.local_path);
+        self.patches.push(entry);
+        self.patches.sort_by(|a, b| a.local_path.cmp(&b.local_path));
+    }
+
+    pub fn remove(&mut self, local_path: &str) {
+        self.patches.retain(|p| p.local_path != local_path);
+    }
+}
@@ -1,11 +1,20 @@
 use anyhow::{Context, Result, anyhow};
-use clap::{Parser, Subcommand};
-use serde::{Deserialize, Serialize};
+use clap::{Parser, Subcommand, ValueEnum};
+use git_cross::apply::check_local_changes;
+use git_cross::metadata::{Metadata, Patch, RefKind};
+use git_cross::repo::CROSSFILE_REL_PATH;
+use git_cross::{
+    ApplyAction, ApplyChange, CliBackend, CrossError, CrossRepo, Crossfile, DiffState, Finding,
+    GitBackend, Libgit2Backend, PatchOptions, PatchPlan, PatchSpec, PatchStatus, PathFilter, Plan,
+    PrunePlan, PushOptions, PushPlan, RemoteUrls, Severity, Statement, StatusCategory, SyncOutcome,
+    SyncResult, TreeChanges, UsePlan, VendoredState,
+};
+use serde::Serialize;
 use std::env;
-use std::fs;
 use std::io::{ErrorKind, Write};
 use std::path::Path;
 use std::process::{Command, Stdio};
+use std::sync::Arc;
 use tabled::{Table, Tabled};
 
 #[derive(Parser)]
@@ -16,22 +25,40 @@ use tabled::{Table, Tabled};
     long_about = "Note: The Rust implementation of git-cross is currently EXPERIMENTAL and WORK IN PROGRESS. The Go implementation is the primary focus and recommended for production use."
 )]
 struct Cli {
-    #[arg(long, global = true, default_value = "")]
-    dry: String,
+    /// Print the planned operations instead of executing them
+    #[arg(long, global = true)]
+    dry: bool,
     #[command(subcommand)]
     command: Commands,
 }
 
+/// Output of `list`, `status`, `outdated` and `diff`.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// Human readable tables
+    Text,
+    /// One JSON document on stdout
+    Json,
+    /// Tab separated values with a header row
+    Tsv,
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Add a remote repository
     Use { name: String, url: String },
     /// Vendor a directory from a remote
     Patch {
-        /// remote[:branch]:path
+        /// remote[:ref]:path (ref: branch, tag or commit)
         spec: String,
         /// Optional local path (defaults to remote path)
         local_path: Option<String>,
+        /// Only vendor files matching this glob (repeatable)
+        #[arg(long = "include", value_name = "GLOB")]
+        include: Vec<String>,
+        /// Never vendor files matching this glob (repeatable)
+        #[arg(long = "exclude", value_name = "GLOB")]
+        exclude: Vec<String>,
     },
 
     /// Update all patches from upstream
@@ -50,13 +77,36 @@ enum Commands {
         path: String,
     },
     /// Show all configured patches
-    List,
+    List {
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
     /// Show patch status
-    Status,
+    Status {
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+        /// Exit non-zero when a patch drifts in one of the --fail-on categories
+        #[arg(long, default_value_t = false)]
+        check: bool,
+        /// Categories that fail --check: conflicts, missing, behind, modified
+        #[arg(
+            long,
+            value_delimiter = ',',
+            default_value = "conflicts,missing,behind,modified"
+        )]
+        fail_on: Vec<StatusCategory>,
+    },
+    /// Show new upstream commits and tags for every patch
+    Outdated {
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
     /// Show changes between local and upstream
     Diff {
         #[arg(default_value = "")]
         path: String,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
     },
     /// Re-execute all Crossfile commands
     Replay,
@@ -67,6 +117,20 @@ enum Commands {
         /// Local path of the patch to remove
         path: String,
     },
+    /// Move a patch to a new local path, keeping its worktree and sync state
+    Mv {
+        /// Local path of the patch to move
+        from: String,
+        /// New local path
+        to: String,
+    },
+    /// Switch a patch to another remote, branch or upstream path, merging local edits
+    Retarget {
+        /// Local path of the patch to retarget
+        path: String,
+        /// New source (remote:branch:path)
+        spec: String,
+    },
     /// Prune unused remotes and worktrees, or remove all patches for a specific remote
     Prune {
         /// Optional remote name to prune all its patches
@@ -85,6 +149,33 @@ enum Commands {
         #[arg(long)]
         message: Option<String>,
     },
+    /// Check metadata, Crossfile, remotes and worktrees for inconsistencies
+    Doctor {
+        /// Repair what can be repaired automatically
+        #[arg(long, default_value_t = false)]
+        fix: bool,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    /// Add, update and remove patches and remotes until they match the Crossfile
+    Apply {
+        /// Do not ask for confirmation
+        #[arg(long, default_value_t = false)]
+        yes: bool,
+        /// Discard local edits of patches that are updated or removed
+        #[arg(long, default_value_t = false)]
+        force: bool,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    /// Rebuild metadata and worktrees without touching vendored files
+    Repair {
+        /// Recreate remotes, worktrees and metadata from Crossfile and Crossfile.lock
+        #[arg(long, required = true)]
+        from_crossfile: bool,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
     /// Run arbitrary command
     Exec {
         #[arg(trailing_var_arg = true)]
@@ -92,205 +183,32 @@ enum Commands {
     },
 }
 
-#[derive(Serialize, Deserialize, Debug, Tabled, Clone)]
-struct Patch {
-    #[serde(default)]
-    pub id: String,
-    pub remote: String,
-    pub remote_path: String,
-    pub local_path: String,
-    pub worktree: String,
-    #[tabled(skip)]
-    pub branch: String,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-struct Metadata {
-    patches: Vec<Patch>,
-}
-
-struct PatchSpec {
-    remote: String,
-    remote_path: String,
-    branch: Option<String>,
-    branch_provided: bool,
-}
-
-const METADATA_REL_PATH: &str = ".git/cross/metadata.json";
-const CROSSFILE_REL_PATH: &str = "Crossfile";
-
-fn get_repo_root() -> Result<String> {
-    run_cmd(&["git", "rev-parse", "--show-toplevel"])
-}
-
-fn get_metadata_path() -> Result<std::path::PathBuf> {
-    let root = get_repo_root()?;
-    Ok(Path::new(&root).join(METADATA_REL_PATH))
-}
-
-fn get_crossfile_path() -> Result<std::path::PathBuf> {
-    let root = get_repo_root()?;
-    Ok(Path::new(&root).join(CROSSFILE_REL_PATH))
-}
-
-fn parse_patch_spec(spec: &str) -> Result<PatchSpec> {
-    let parts: Vec<&str> = spec.split(':').collect();
-    if parts.len() < 2 {
-        return Err(anyhow!("Invalid spec. Use remote[:branch]:remote_path"));
-    }
-
-    let remote = parts[0].to_string();
-    let mut remote_path = parts.last().unwrap().to_string();
-    let mut branch: Option<String> = None;
-    let mut branch_provided = false;
-
-    match parts.len() {
-        2 => {}
-        3 => {
-            if !parts[1].is_empty() {
-                branch = Some(parts[1].to_string());
-                branch_provided = true;
-            }
-        }
-        _ => {
-            if !parts[1].is_empty() {
-                branch = Some(parts[1].to_string());
-                branch_provided = true;
-            }
-            remote_path = parts[2..].join(":");
-        }
+impl Commands {
+    /// Whether the command holds the operation lock from planning to the
+    /// end of execution. `replay` and `exec` run commands that may call
+    /// `cross` themselves, so they only lock while executing each plan.
+    fn locks(&self) -> bool {
+        matches!(
+            self,
+            Commands::Use { .. }
+                | Commands::Patch { .. }
+                | Commands::Sync { .. }
+                | Commands::Remove { .. }
+                | Commands::Mv { .. }
+                | Commands::Retarget { .. }
+                | Commands::Prune { .. }
+                | Commands::Push { .. }
+                | Commands::Doctor { fix: true, .. }
+                | Commands::Repair { .. }
+                | Commands::Apply { .. }
+        )
     }
 
-    let remote_path = remote_path
-        .trim_start_matches('/')
-        .trim_end_matches('/')
-        .to_string();
-    if remote_path.is_empty() {
-        return Err(anyhow!("Invalid remote path in spec: {}", spec));
+    /// Whether the command changes the repository, so outdated metadata is
+    /// migrated first. Read-only commands migrate it in memory only.
+    fn migrates(&self) -> bool {
+        self.locks() || matches!(self, Commands::Replay)
     }
-
-    Ok(PatchSpec {
-        remote,
-        remote_path,
-        branch,
-        branch_provided,
-    })
-}
-
-fn canonical_spec(spec: &PatchSpec) -> String {
-    if let Some(branch) = &spec.branch {
-        format!("{}:{}:{}", spec.remote, branch, spec.remote_path)
-    } else {
-        format!("{}:{}", spec.remote, spec.remote_path)
-    }
-}
-
-fn detect_default_branch_from_url(url: &str) -> Result<String> {
-    let symref_result = duct::cmd!("git", "ls-remote", "--symref", url, "HEAD")
-        .stderr_to_stdout()
-        .stdout_capture()
-        .unchecked()
-        .run()?;
-
-    if symref_result.status.success() {
-        let stdout = String::from_utf8_lossy(&symref_result.stdout);
-        for line in stdout.lines() {
-            if let Some(rest) = line.strip_prefix("ref: ") {
-                let mut parts = rest.split('\t');
-                if let (Some(refspec), Some(target)) = (parts.next(), parts.next()) {
-                    if target == "HEAD" && refspec.starts_with("refs/heads/") {
-                        return Ok(refspec.trim_start_matches("refs/heads/").to_string());
-                    }
-                }
-            }
-        }
-    }
-
-    let heads_output = run_cmd(&["git", "ls-remote", "--heads", url])?;
-    for candidate in ["main", "master"] {
-        let needle = format!("\trefs/heads/{}", candidate);
-        if heads_output.contains(&needle) {
-            return Ok(candidate.to_string());
-        }
-    }
-
-    for line in heads_output.lines() {
-        if let Some((_hash, refname)) = line.split_once('\t') {
-            if let Some(stripped) = refname.strip_prefix("refs/heads/") {
-                if !stripped.is_empty() {
-                    return Ok(stripped.to_string());
-                }
-            }
-        }
-    }
-
-    Ok("main".to_string())
-}
-
-fn detect_remote_branch(repo: &git2::Repository, remote: &str) -> Result<String> {
-    if let Ok(mut remote_handle) = repo.find_remote(remote) {
-        if let Some(url) = remote_handle.url() {
-            if let Ok(branch) = detect_default_branch_from_url(url) {
-                return Ok(branch);
-            }
-        }
-
-        if remote_handle.connect(git2::Direction::Fetch).is_ok() {
-            let mut candidate: Option<String> = None;
-
-            if let Ok(buf) = remote_handle.default_branch() {
-                if let Some(name) = buf.as_str() {
-                    let trimmed = name.trim_start_matches("refs/heads/").to_string();
-                    if !trimmed.is_empty() {
-                        candidate = Some(trimmed);
-                    }
-                }
-            }
-
-            if candidate.is_none() {
-                if let Ok(list) = remote_handle.list() {
-                    if candidate.is_none() {
-                        if let Some(target) = list
-                            .iter()
-                            .find(|r| r.name() == "HEAD")
-                            .and_then(|r| r.symref_target())
-                        {
-                            let trimmed = target.trim_start_matches("refs/heads/").to_string();
-                            if !trimmed.is_empty() {
-                                candidate = Some(trimmed);
-                            }
-                        }
-                    }
-
-                    if candidate.is_none() {
-                        for head in list {
-                            if let Some(stripped) = head.name().strip_prefix("refs/heads/") {
-                                if !stripped.is_empty() {
-                                    candidate = Some(stripped.to_string());
-                                    break;
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-
-            let _ = remote_handle.disconnect();
-            if let Some(branch) = candidate {
-                return Ok(branch);
-            }
-        }
-    }
-
-    for candidate in ["main", "master"] {
-        if let Ok(output) = run_cmd(&["git", "ls-remote", remote, candidate]) {
-            if !output.trim().is_empty() {
-                return Ok(candidate.to_string());
-            }
-        }
-    }
-
-    Ok("main".to_string())
 }
 
 fn log_info(msg: &str) {
@@ -305,56 +223,6 @@ fn log_error(msg: &str) {
     eprintln!("\x1b[1;31m==> ERROR:\x1b[0m {}", msg);
 }
 
-fn run_cmd(args: &[&str]) -> Result<String> {
-    use duct::cmd;
-    let output = cmd(args[0], &args[1..])
-        .stderr_to_stdout()
-        .stdout_capture()
-        .unchecked()
-        .run()
-        .context(format!("Failed to execute command: {:?}", args))?;
-
-    let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
-    if !output.status.success() {
-        return Err(anyhow!("Command failed: {:?}\nOutput: {}", args, stdout));
-    }
-
-    Ok(stdout)
-}
-
-fn normalize_local_path(path: &str) -> String {
-    let mut normalized = path.replace('\\', "/");
-    normalized = normalized.trim().to_string();
-    while let Some(stripped) = normalized.strip_prefix("./") {
-        normalized = stripped.to_string();
-    }
-    normalized.trim_matches('/').to_string()
-}
-
-fn find_patch_for_path(metadata: &Metadata, rel: &str) -> Option<Patch> {
-    let rel = normalize_local_path(rel);
-    if rel.is_empty() {
-        return None;
-    }
-
-    let mut selected: Option<Patch> = None;
-    let mut longest = 0usize;
-    for patch in &metadata.patches {
-        let lp = normalize_local_path(&patch.local_path);
-        if lp.is_empty() {
-            continue;
-        }
-        if rel == lp || rel.starts_with(&(lp.clone() + "/")) {
-            if lp.len() > longest {
-                longest = lp.len();
-                selected = Some(patch.clone());
-            }
-        }
-    }
-
-    selected
-}
-
 fn select_patch_interactive(metadata: &Metadata) -> Result<Option<Patch>> {
     if metadata.patches.is_empty() {
         return Ok(None);
@@ -418,7 +286,7 @@ fn select_patch_interactive(metadata: &Metadata) -> Result<Option<Patch>> {
         .iter()
         .find(|p| p.local_path == local_path)
         .cloned()
-        .ok_or_else(|| anyhow!("Selected patch not found: {}", local_path))
+        .ok_or_else(|| CrossError::PatchNotFound { path: local_path }.into())
         .map(Some)
 }
 
@@ -429,10 +297,14 @@ fn copy_to_clipboard(text: &str) -> Result<()> {
         duct::cmd!("pbcopy").stdin_bytes(text).run()?;
     } else if which::which("xclip").is_ok() {
         // Linux with xclip
-        duct::cmd!("xclip", "-selection", "clipboard").stdin_bytes(text).run()?;
+        duct::cmd!("xclip", "-selection", "clipboard")
+            .stdin_bytes(text)
+            .run()?;
     } else if which::which("xsel").is_ok() {
         // Linux with xsel
-        duct::cmd!("xsel", "--clipboard", "--input").stdin_bytes(text).run()?;
+        duct::cmd!("xsel", "--clipboard", "--input")
+            .stdin_bytes(text)
+            .run()?;
     } else {
         return Err(anyhow!("No clipboard tool found (pbcopy/xclip/xsel)"));
     }
@@ -441,30 +313,31 @@ fn copy_to_clipboard(text: &str) -> Result<()> {
 
 fn get_relative_path(target_path: &str) -> String {
     use std::path::PathBuf;
-    
+
     // Get current working directory
     let Ok(pwd) = env::current_dir() else {
         return target_path.to_string();
     };
-    
+
     // Convert target to absolute path (don't use canonicalize - it requires file to exist)
     let target = PathBuf::from(target_path);
-    
+
     // Manual computation: try strip_prefix first (if target is subpath of pwd)
     if let Ok(rel) = target.strip_prefix(&pwd) {
         return rel.to_string_lossy().to_string();
     }
-    
+
     // Otherwise compute relative path by finding common prefix
     let pwd_components: Vec<_> = pwd.components().collect();
     let target_components: Vec<_> = target.components().collect();
-    
+
     // Find common prefix
-    let common = pwd_components.iter()
+    let common = pwd_components
+        .iter()
         .zip(target_components.iter())
         .take_while(|(a, b)| a == b)
         .count();
-    
+
     // Build relative path: ../ for each level up, then remaining target components
     let ups = pwd_components.len() - common;
     let mut rel_path = PathBuf::new();
@@ -474,12 +347,12 @@ fn get_relative_path(target_path: &str) -> String {
     for comp in &target_components[common..] {
         rel_path.push(comp);
     }
-    
+
     rel_path.to_string_lossy().to_string()
 }
 
-fn open_shell_in_dir(path: &str, target_type: &str) -> Result<()> {
-    let metadata = load_metadata()?;
+fn open_shell_in_dir(repo: &CrossRepo, path: &str, target_type: &str) -> Result<()> {
+    let metadata = repo.load_metadata()?;
     if metadata.patches.is_empty() {
         println!("No patches configured.");
         return Ok(());
@@ -487,28 +360,44 @@ fn open_shell_in_dir(path: &str, target_type: &str) -> Result<()> {
 
     // Find patch for provided path
     let path = path.trim();
-    let target_patch = find_patch_for_path(&metadata, path)
-        .or_else(|| metadata.patches.iter().find(|p| p.local_path == path).cloned())
-        .ok_or_else(|| anyhow!("Patch not found for path: {}", path))?;
+    let target_patch = metadata
+        .find_containing(path)
+        .or_else(|| metadata.patches.iter().find(|p| p.local_path == path))
+        .ok_or_else(|| CrossError::PatchNotFound {
+            path: path.to_string(),
+        })?;
 
-    // Determine target directory
-    let target_dir = if target_type == "worktree" {
+    // Determine target directory (the parent directory of a single-file patch)
+    let mut target_dir = repo.join(if target_type == "worktree" {
         &target_patch.worktree
     } else {
         &target_patch.local_path
-    };
+    });
+    if target_type != "worktree" && target_patch.file {
+        target_dir.pop();
+    }
 
     // Check directory exists
-    if !std::path::Path::new(target_dir).exists() {
-        return Err(anyhow!("{} not found: {}", target_type, target_dir));
+    if !target_dir.exists() {
+        if target_type == "worktree" {
+            return Err(CrossError::WorktreeMissing {
+                path: target_patch.local_path.clone(),
+            }
+            .into());
+        }
+        return Err(anyhow!(
+            "{} not found: {}",
+            target_type,
+            target_dir.display()
+        ));
     }
 
     // Open subshell
     let shell = env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string());
-    log_info(&format!("Opening shell in {}", target_dir));
+    log_info(&format!("Opening shell in {}", target_dir.display()));
 
     let status = Command::new(&shell)
-        .current_dir(target_dir)
+        .current_dir(&target_dir)
         .stdin(Stdio::inherit())
         .stdout(Stdio::inherit())
         .stderr(Stdio::inherit())
@@ -521,25 +410,14 @@ fn open_shell_in_dir(path: &str, target_type: &str) -> Result<()> {
     Ok(())
 }
 
-fn repo_relative_path() -> Result<Option<String>> {
-    let prefix = run_cmd(&["git", "rev-parse", "--show-prefix"])?;
-    if prefix.is_empty() {
-        Ok(None)
-    } else {
-        Ok(Some(prefix.trim_end_matches('/').to_string()))
-    }
-}
-
 // resolvePathToRepoRelative converts any path (relative, absolute, or repo-relative)
 // to a repo-relative path for matching against metadata
-fn resolve_path_to_repo_relative(input_path: &str) -> Result<String> {
+fn resolve_path_to_repo_relative(repo: &CrossRepo, input_path: &str) -> Result<String> {
     if input_path.is_empty() {
         return Ok(String::new());
     }
 
-    // Get repo root
-    let repo_root = get_repo_root()?;
-    let repo_root_path = std::path::Path::new(&repo_root);
+    let repo_root_path = repo.root();
 
     // Get current directory
     let cwd = std::env::current_dir()?;
@@ -552,22 +430,20 @@ fn resolve_path_to_repo_relative(input_path: &str) -> Result<String> {
     };
 
     // Canonicalize/clean the path (resolves . and ..)
-    let abs_path = abs_path
-        .canonicalize()
-        .unwrap_or_else(|_| {
-            // If canonicalize fails (path doesn't exist), manually clean it
-            let mut cleaned = std::path::PathBuf::new();
-            for component in abs_path.components() {
-                match component {
-                    std::path::Component::ParentDir => {
-                        cleaned.pop();
-                    }
-                    std::path::Component::CurDir => {}
-                    _ => cleaned.push(component),
+    let abs_path = abs_path.canonicalize().unwrap_or_else(|_| {
+        // If canonicalize fails (path doesn't exist), manually clean it
+        let mut cleaned = std::path::PathBuf::new();
+        for component in abs_path.components() {
+            match component {
+                std::path::Component::ParentDir => {
+                    cleaned.pop();
                 }
+                std::path::Component::CurDir => {}
+                _ => cleaned.push(component),
             }
-            cleaned
-        });
+        }
+        cleaned
+    });
 
     // Get relative path from repo root
     let rel_path = abs_path
@@ -588,910 +464,1013 @@ fn resolve_path_to_repo_relative(input_path: &str) -> Result<String> {
     Ok(rel_path_str.trim_matches('/').to_string())
 }
 
-fn load_metadata() -> Result<Metadata> {
-    let path = get_metadata_path()?;
-    if path.exists() {
-        let content = fs::read_to_string(path)?;
-        Ok(serde_json::from_str(&content)?)
-    } else {
-        Ok(Metadata {
-            patches: Vec::new(),
-        })
-    }
+fn confirm(prompt: &str) -> Result<bool> {
+    print!("{} [y/N]: ", prompt);
+    std::io::stdout().flush()?;
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    Ok(input.trim().eq_ignore_ascii_case("y"))
 }
 
-fn save_metadata(metadata: &Metadata) -> Result<()> {
-    let path = get_metadata_path()?;
-    let dir = path.parent().unwrap();
-    fs::create_dir_all(dir)?;
-    let content = serde_json::to_string_pretty(metadata)?;
-    fs::write(path, content)?;
+/// Print the plan with `--dry`, execute it otherwise.
+fn run_plan(repo: &CrossRepo, plan: &Plan, dry: bool) -> Result<()> {
+    if dry {
+        print!("{}", plan);
+        return Ok(());
+    }
+    print_changes(&repo.execute(plan)?);
     Ok(())
 }
 
-fn update_crossfile(line: &str) -> Result<()> {
-    let path = get_crossfile_path()?;
-    let mut content = if path.exists() {
-        fs::read_to_string(&path)?
-    } else {
-        String::new()
-    };
-
-    let line = line.trim();
-    let line_without_prefix = line.strip_prefix("cross ").unwrap_or(line).trim();
-
-    let already_exists = content.lines().any(|l| {
-        let trimmed_l = l.trim();
-        trimmed_l == line || trimmed_l == format!("cross {}", line) || trimmed_l == line_without_prefix
-    });
-
-    if !already_exists {
-        if !content.is_empty() && !content.ends_with('\n') {
-            content.push('\n');
-        }
-        if !line.starts_with("cross ") {
-            content.push_str("cross ");
+/// Summary and list of the files a plan added (A), modified (M) or removed (D).
+fn print_changes(changes: &TreeChanges) {
+    if changes.is_empty() {
+        return;
+    }
+    log_info(&format!("Files: {}", changes));
+    for (mark, paths) in [
+        ("A", &changes.added),
+        ("M", &changes.modified),
+        ("D", &changes.removed),
+    ] {
+        for path in paths {
+            println!("  {} {}", mark, path);
         }
-        content.push_str(line);
-        content.push('\n');
-        fs::write(path, content)?;
+    }
+}
+
+fn cmd_use(repo: &CrossRepo, name: &str, url: &str, dry: bool) -> Result<()> {
+    log_info(&format!("Adding remote {} ({})", name, url));
+    log_info("Autodetecting default branch...");
+    let UsePlan { remote, plan } = repo.plan_use(name, url)?;
+    log_info(&format!("Detected default branch: {}", remote.branch));
+    run_plan(repo, &plan, dry)?;
+    if !dry {
+        log_success("Remote added and Crossfile updated.");
     }
     Ok(())
 }
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
+fn cmd_patch(repo: &CrossRepo, spec: &str, opts: &PatchOptions, dry: bool) -> Result<()> {
+    let PatchPlan { patch, plan } = repo.plan_patch(spec, opts)?;
+    if !PatchSpec::parse(spec)?.branch_provided {
+        log_info(&format!("Using branch: {}", patch.branch));
+    }
+    log_info(&format!(
+        "Patching {}",
+        plan.title.trim_start_matches("patch ")
+    ));
+    run_plan(repo, &plan, dry)?;
+    if !dry {
+        log_success("Patch successful.");
+    }
+    Ok(())
+}
 
-    match &cli.command {
-        Commands::Use { name, url } => {
-            log_info(&format!("Adding remote {} ({})", name, url));
+fn cmd_sync(repo: &CrossRepo, path: &str, dry: bool) -> Result<()> {
+    let path = (!path.is_empty()).then_some(path);
+    let (plans, skipped) = repo.plan_sync_all(path)?;
+    // The first failure decides the exit code; conflicts only count when
+    // everything else worked.
+    let mut failure = None;
+    for skip in skipped {
+        log_error(&format!("{:#}", skip.error));
+        failure.get_or_insert(skip.error);
+    }
+    if plans.is_empty() {
+        log_info("No patches found to sync.");
+        return failure.map_or(Ok(()), Err);
+    }
 
-            let repo = git2::Repository::open(".")?;
+    let mut conflicted = Vec::new();
+    for sync in plans {
+        if dry {
+            print!("{}", sync.plan);
+            continue;
+        }
 
-            if repo.find_remote(name).is_ok() {
-                repo.remote_set_url(name, url)?;
-            } else {
-                repo.remote(name, url)?;
+        log_info(&format!("Syncing {}...", sync.patch.local_path));
+        if sync.patch.is_pinned() {
+            log_info(&format!(
+                "{} is pinned to {} {}; it stays there (patch a new ref to move it)",
+                sync.patch.local_path,
+                sync.patch.ref_kind.as_str(),
+                sync.patch.ref_name()
+            ));
+        }
+        let SyncResult { patch, outcome } = repo.execute_sync(sync);
+        match outcome {
+            SyncOutcome::Failed { error } | SyncOutcome::Skipped { error } => {
+                let error = error.context(format!("Sync failed for {}", patch.local_path));
+                log_error(&format!("{:#}", error));
+                failure.get_or_insert(error);
+                continue;
             }
+            SyncOutcome::Synced { conflicts, changes } => {
+                print_changes(&changes);
+                if !conflicts.is_empty() {
+                    log_error("Conflicts left in:");
+                    println!("{}", conflicts.join("\n"));
+                    log_info("Resolve the conflict markers, then commit the result.");
+                    conflicted.extend(conflicts);
+                }
+            }
+        }
 
-            log_info("Autodetecting default branch...");
-            let branch = detect_default_branch_from_url(url)
-                .or_else(|_| detect_remote_branch(&repo, name))
-                .unwrap_or_else(|_| "main".to_string());
-            log_info(&format!("Detected default branch: {}", branch));
+        log_success(&format!("Sync completed for {}", patch.local_path));
+    }
+    if let Some(error) = failure {
+        return Err(error);
+    }
+    if !conflicted.is_empty() {
+        return Err(CrossError::SyncConflict { files: conflicted }.into());
+    }
+    Ok(())
+}
 
-            run_cmd(&["git", "fetch", name, &branch])?;
+fn cmd_replay(repo: &CrossRepo, dry: bool) -> Result<()> {
+    log_info("Replaying Crossfile...");
+    let Some(crossfile) = repo.load_crossfile()? else {
+        println!("No Crossfile found.");
+        return Ok(());
+    };
 
-            update_crossfile(&format!("cross use {} {}", name, url))?;
-            log_success("Remote added and Crossfile updated.");
+    let locked = repo.lockfile_path().exists();
+    for (line, statement) in crossfile.statements() {
+        log_info(&format!("{}: {}", line.number, line.raw.trim()));
+        if locked && matches!(statement, Statement::Sync { .. }) {
+            // Replay reproduces the locked state; moving to newer upstream
+            // commits is an explicit `cross sync`.
+            log_info("Skipping sync: Crossfile.lock pins upstream commits.");
+            continue;
         }
-        Commands::Patch { spec, local_path } => {
-            let mut spec = parse_patch_spec(spec)?;
-
-            let repo = git2::Repository::open(".")?;
-            if repo.find_remote(&spec.remote).is_err() {
-                return Err(anyhow!(
-                    "Remote {} not found. Run 'use' first.",
-                    spec.remote
-                ));
-            }
-
-            if !spec.branch_provided {
-                log_info("Autodetecting default branch...");
-                let branch = detect_remote_branch(&repo, &spec.remote)?;
-                log_info(&format!("Using branch: {}", branch));
-                spec.branch = Some(branch);
+        if let Err(e) = run_statement(repo, statement, dry) {
+            let err = e.context(format!("{}: {}", crossfile.location(line), statement));
+            if dry {
+                // Later lines usually depend on earlier ones having run
+                // (e.g. a patch needs its remote), so keep planning.
+                log_error(&format!("{:#}", err));
+                continue;
             }
-            let branch_name = spec.branch.clone().unwrap_or_else(|| "main".to_string());
+            return Err(err);
+        }
+    }
 
-            let canonical = canonical_spec(&spec);
-            let target_path = local_path.clone().unwrap_or_else(|| {
-                Path::new(&spec.remote_path)
-                    .file_name()
-                    .map(|s| s.to_string_lossy().to_string())
-                    .unwrap_or_else(|| spec.remote_path.clone())
-            });
+    if !dry {
+        log_success("Replay completed.");
+    }
+    Ok(())
+}
 
-            log_info(&format!("Patching {} to {}", canonical, target_path));
+fn run_statement(repo: &CrossRepo, statement: &Statement, dry: bool) -> Result<()> {
+    match statement {
+        Statement::Use { name, url } => cmd_use(repo, name, url, dry),
+        Statement::Patch {
+            spec,
+            local_path,
+            filter,
+        } => {
+            let opts = PatchOptions {
+                local_path: local_path.clone(),
+                filter: filter.clone(),
+                ..PatchOptions::default()
+            };
+            cmd_patch(repo, spec, &opts, dry)
+        }
+        Statement::Sync { path } => cmd_sync(repo, path.as_deref().unwrap_or(""), dry),
+        Statement::Exec { args } => cmd_exec(repo, args, dry),
+    }
+}
 
-            run_cmd(&["git", "fetch", &spec.remote, &branch_name])?;
+fn cmd_exec(repo: &CrossRepo, args: &[String], dry: bool) -> Result<()> {
+    if !dry {
+        log_info(&format!("Executing custom command: {}", args.join(" ")));
+    }
+    run_plan(repo, &repo.plan_exec(args), dry)
+}
 
-            use std::collections::hash_map::DefaultHasher;
-            use std::hash::{Hash, Hasher};
-            let mut hasher = DefaultHasher::new();
-            canonical.hash(&mut hasher);
-            branch_name.hash(&mut hasher);
-            let hash = format!("{:016x}", hasher.finish());
-            let hash = &hash[..8];
+fn cmd_remove(repo: &CrossRepo, path: &str, dry: bool) -> Result<()> {
+    let PatchPlan { patch, plan } = repo.plan_remove(path)?;
+    log_info(&format!("Removing patch at {}...", patch.local_path));
+    run_plan(repo, &plan, dry)?;
+    if !dry {
+        log_success("Patch removed successfully.");
+    }
+    Ok(())
+}
 
-            let wt_dir = format!(".git/cross/worktrees/{}_{}", spec.remote, hash);
+fn cmd_mv(repo: &CrossRepo, from: &str, to: &str, dry: bool) -> Result<()> {
+    let PatchPlan { patch, plan } = repo.plan_move(from, to)?;
+    run_plan(repo, &plan, dry)?;
+    if !dry {
+        log_success(&format!("Patch moved to {}.", patch.local_path));
+    }
+    Ok(())
+}
 
-            if !Path::new(&wt_dir).exists() {
-                log_info(&format!("Setting up worktree at {}...", wt_dir));
-                fs::create_dir_all(&wt_dir)?;
+fn cmd_retarget(repo: &CrossRepo, path: &str, spec: &str, dry: bool) -> Result<()> {
+    let sync = repo.plan_retarget(path, spec)?;
+    if dry {
+        print!("{}", sync.plan);
+        return Ok(());
+    }
 
-                run_cmd(&[
-                    "git",
-                    "worktree",
-                    "add",
-                    "--no-checkout",
-                    &wt_dir,
-                    &format!("{}/{}", spec.remote, branch_name),
-                ])?;
-                run_cmd(&["git", "-C", &wt_dir, "sparse-checkout", "init", "--no-cone"])?;
-                run_cmd(&[
-                    "git",
-                    "-C",
-                    &wt_dir,
-                    "sparse-checkout",
-                    "set",
-                    &spec.remote_path,
-                ])?;
-                run_cmd(&["git", "-C", &wt_dir, "checkout"])?;
+    log_info(&format!(
+        "Retargeting {} to {}...",
+        sync.patch.local_path,
+        sync.patch.spec()
+    ));
+    let SyncResult { patch, outcome } = repo.execute_sync(sync);
+    match outcome {
+        SyncOutcome::Failed { error } | SyncOutcome::Skipped { error } => {
+            Err(error.context(format!("Retarget failed for {}", patch.local_path)))
+        }
+        SyncOutcome::Synced { conflicts, changes } => {
+            print_changes(&changes);
+            if !conflicts.is_empty() {
+                log_error("Conflicts left in:");
+                println!("{}", conflicts.join("\n"));
+                log_info("Resolve the conflict markers, then commit the result.");
+                return Err(CrossError::SyncConflict { files: conflicts }.into());
             }
+            log_success(&format!(
+                "{} now tracks {}.",
+                patch.local_path,
+                patch.spec()
+            ));
+            Ok(())
+        }
+    }
+}
 
-            log_info(&format!("Syncing files to {}...", target_path));
-            fs::create_dir_all(&target_path)?;
-
-            let src = format!("{}/{}/", wt_dir, spec.remote_path);
-            let dst = format!("{}/", target_path);
-            run_cmd(&["rsync", "-av", "--delete", "--exclude", ".git", &src, &dst])?;
-
-            let mut metadata = load_metadata()?;
-            if let Some(existing) = metadata
-                .patches
-                .iter_mut()
-                .find(|p| p.local_path == target_path)
-            {
-                existing.id = hash.to_string();
-                existing.remote = spec.remote.clone();
-                existing.remote_path = spec.remote_path.clone();
-                existing.branch = branch_name.clone();
-                existing.worktree = wt_dir.clone();
-            } else {
-                metadata.patches.push(Patch {
-                    id: hash.to_string(),
-                    remote: spec.remote.clone(),
-                    remote_path: spec.remote_path.clone(),
-                    local_path: target_path.clone(),
-                    worktree: wt_dir.clone(),
-                    branch: branch_name.clone(),
-                });
-            }
-            save_metadata(&metadata)?;
-
-            update_crossfile(&format!(
-                "cross patch {} {}",
-                canonical_spec(&spec),
-                target_path
-            ))?;
-            log_success("Patch successful.");
+fn cmd_prune(repo: &CrossRepo, remote: Option<&str>, dry: bool) -> Result<()> {
+    if let Some(remote_name) = remote {
+        // Prune specific remote: remove all its patches and the remote itself
+        log_info(&format!(
+            "Pruning all patches for remote: {}...",
+            remote_name
+        ));
+        let PrunePlan { patches, plan } = repo.plan_prune_remote(remote_name)?;
+        if patches.is_empty() {
+            log_info(&format!("No patches found for remote: {}", remote_name));
+        }
+        run_plan(repo, &plan, dry)?;
+        if !dry {
+            log_success(&format!(
+                "Remote {} and all its patches pruned successfully.",
+                remote_name
+            ));
+        }
+    } else {
+        // Prune all unused remotes (no active patches)
+        log_info("Finding unused remotes...");
+        let unused_remotes = repo.unused_remotes()?;
+        let remotes_plan = repo.plan_remove_remotes(&unused_remotes);
+        let worktrees_plan = repo.plan_prune_worktrees();
+
+        if dry {
+            print!("{}", remotes_plan);
+            print!("{}", worktrees_plan);
+            return Ok(());
         }
-        Commands::Sync { path } => {
-            let metadata = load_metadata()?;
-            let repo_root = run_cmd(&["git", "rev-parse", "--show-toplevel"])?;
-            let repo_root = repo_root.trim();
 
-            let patches_to_sync = if path.is_empty() {
-                metadata.patches.clone()
+        if unused_remotes.is_empty() {
+            log_info("No unused remotes found.");
+        } else {
+            log_info(&format!("Unused remotes: {}", unused_remotes.join(", ")));
+            if confirm("Remove these remotes?")? {
+                repo.execute(&remotes_plan)?;
+                log_success("Unused remotes removed.");
             } else {
-                metadata
-                    .patches
-                    .iter()
-                    .filter(|p| p.local_path == *path)
-                    .cloned()
-                    .collect()
-            };
-
-            if patches_to_sync.is_empty() {
-                log_info("No patches found to sync.");
-                return Ok(());
+                log_info("Pruning cancelled.");
             }
+        }
 
-            for patch in patches_to_sync {
-                log_info(&format!("Syncing {}...", patch.local_path));
+        // Always prune stale worktrees
+        log_info("Pruning stale worktrees...");
+        repo.execute(&worktrees_plan)?;
+        log_success("Worktree pruning complete.");
+    }
+    Ok(())
+}
 
-                if !Path::new(&patch.worktree).exists() {
-                    log_error(&format!(
-                        "Worktree not found for {}. Run patch again.",
-                        patch.local_path
-                    ));
-                    continue;
-                }
+fn cmd_push(repo: &CrossRepo, path: &str, opts: &PushOptions, yes: bool, dry: bool) -> Result<()> {
+    let PushPlan { patch, plan, .. } = repo.plan_push(path, opts)?;
 
-                let local_abs_path = format!("{}/{}", repo_root, patch.local_path);
-
-                // Step 1: Check for uncommitted changes and stash if needed
-                let mut stashed = false;
-                match run_cmd(&["git", "-C", &local_abs_path, "status", "--porcelain"]) {
-                    Ok(status) if !status.trim().is_empty() => {
-                        log_info("Stashing uncommitted changes...");
-                        if let Err(e) = run_cmd(&[
-                            "git",
-                            "-C",
-                            &local_abs_path,
-                            "stash",
-                            "push",
-                            "-m",
-                            "cross-sync-auto-stash",
-                        ]) {
-                            log_error(&format!("Failed to stash changes: {}", e));
-                            continue;
-                        }
-                        stashed = true;
-                    }
-                    _ => {}
-                }
-
-                // Step 2: Rsync git-tracked files from local_path to worktree
-                log_info("Syncing local changes to worktree...");
-                let git_files = run_cmd(&["git", "-C", &local_abs_path, "ls-files", "-z"]);
-                if let Ok(files) = git_files {
-                    if !files.trim().is_empty() {
-                        let wt_remote_path = format!("{}/{}", patch.worktree, patch.remote_path);
-                        let rsync_result = duct::cmd!(
-                            "rsync",
-                            "-av0",
-                            "--files-from=-",
-                            "--relative",
-                            "--exclude",
-                            ".git",
-                            &format!("{}/", local_abs_path),
-                            &format!("{}/", wt_remote_path)
-                        )
-                        .stdin_bytes(files.as_bytes())
-                        .run();
-
-                        if let Err(e) = rsync_result {
-                            log_error(&format!("Failed to rsync local to worktree: {}", e));
-                            if stashed {
-                                let _ = run_cmd(&["git", "-C", &local_abs_path, "stash", "pop"]);
-                            }
-                            continue;
-                        }
-                    }
-                }
+    if dry {
+        print!("{}", plan);
+        return Ok(());
+    }
 
-                // Step 3: Commit local changes in worktree
-                match run_cmd(&["git", "-C", &patch.worktree, "status", "--porcelain"]) {
-                    Ok(wt_status) if !wt_status.trim().is_empty() => {
-                        log_info("Committing local changes in worktree...");
-                        let _ = run_cmd(&["git", "-C", &patch.worktree, "add", "."]);
-                        let _ = run_cmd(&[
-                            "git",
-                            "-C",
-                            &patch.worktree,
-                            "commit",
-                            "-m",
-                            "Sync local changes",
-                        ]);
-                    }
-                    _ => {}
-                }
+    if !yes {
+        print!("{}", plan);
+        if !confirm("Run push?")? {
+            log_info("Push cancelled.");
+            return Ok(());
+        }
+    }
 
-                // Step 4: Pull rebase from upstream
-                log_info("Pulling updates from upstream...");
-                if let Err(e) = run_cmd(&[
-                    "git",
-                    "-C",
-                    &patch.worktree,
-                    "pull",
-                    "--rebase",
-                    &patch.remote,
-                    &patch.branch,
-                ]) {
-                    log_error(&format!("Failed to pull: {}", e));
-                    log_error("Please resolve conflicts manually in worktree:");
-                    log_error(&format!("  cd {}", patch.worktree));
-                    if stashed {
-                        log_info("Note: Local changes are stashed. Run 'git stash pop' in local_path after resolving.");
-                    }
-                    continue;
-                }
+    log_info(&format!(
+        "Pushing changes from {} via {}...",
+        patch.local_path, patch.worktree
+    ));
+    print_changes(&repo.execute(&plan)?);
 
-                // Step 5: Delete tracked files in local_path that were removed upstream
-                log_info("Checking for files deleted upstream...");
-                let wt_remote_path = format!("{}/{}", patch.worktree, patch.remote_path);
-                
-                // Get tracked files in worktree
-                if let Ok(wt_files_str) = run_cmd(&["git", "-C", &wt_remote_path, "ls-files"]) {
-                    let wt_files: std::collections::HashSet<String> = 
-                        wt_files_str.lines().map(|s| s.to_string()).collect();
-                    
-                    // Get tracked files in local_path from main repo
-                    if let Ok(local_files_str) = run_cmd(&["git", "ls-files", &patch.local_path]) {
-                        for local_file in local_files_str.lines() {
-                            if local_file.is_empty() {
-                                continue;
-                            }
-                            // Get relative path (remove local_path prefix)
-                            let rel_file = local_file.strip_prefix(&format!("{}/", patch.local_path))
-                                .unwrap_or(local_file);
-                            
-                            // Check if this tracked file no longer exists in worktree
-                            if !wt_files.contains(rel_file) {
-                                log_info(&format!("Removing deleted file: {}", rel_file));
-                                let full_path = format!("{}/{}", env::current_dir()?.display(), local_file);
-                                let _ = std::fs::remove_file(&full_path);
-                            }
-                        }
-                    }
-                }
-                
-                // Step 6: Rsync worktree → local_path (without --delete, we handle deletions above)
-                log_info(&format!("Syncing files to {}...", patch.local_path));
-                let src = format!("{}/{}/", patch.worktree, patch.remote_path);
-                let dst = format!("{}/", patch.local_path);
-                if let Err(e) = run_cmd(&["rsync", "-av", "--exclude", ".git", &src, &dst]) {
-                    log_error(&format!("Failed to sync files: {}", e));
-                    if stashed {
-                        let _ = run_cmd(&["git", "-C", &local_abs_path, "stash", "pop"]);
-                    }
-                    continue;
-                }
+    log_success("Push completed.");
+    Ok(())
+}
 
-                // Step 7: Restore stashed changes
-                if stashed {
-                    log_info("Restoring stashed local changes...");
-                    if let Err(e) = run_cmd(&["git", "-C", &local_abs_path, "stash", "pop"]) {
-                        log_error("Failed to restore stashed changes. Conflicts may exist.");
-                        log_error(&format!("Resolve manually in: {}", patch.local_path));
-                        log_info("Run 'git status' to see conflicts, then 'git stash drop' when resolved.");
-                    } else {
-                        // Check for conflicts after pop
-                        if let Ok(conflicts) = run_cmd(&[
-                            "git",
-                            "-C",
-                            &local_abs_path,
-                            "diff",
-                            "--name-only",
-                            "--diff-filter=U",
-                        ]) {
-                            if !conflicts.trim().is_empty() {
-                                log_error("Conflicts detected after restoring local changes:");
-                                println!("{}", conflicts);
-                                log_info("Resolve conflicts, then run 'git add' and continue.");
-                            }
-                        }
-                    }
-                }
+/// `cd`/`wt`: open a shell in the patch directory, or pick one with fzf and
+/// copy its path to the clipboard.
+fn cmd_open(repo: &CrossRepo, path: &str, target_type: &str) -> Result<()> {
+    let metadata = repo.load_metadata()?;
+    if metadata.patches.is_empty() {
+        println!("No patches configured.");
+        return Ok(());
+    }
 
-                log_success(&format!("Sync completed for {}", patch.local_path));
-            }
-        }
-        Commands::Cd { path } => {
-            let metadata = load_metadata()?;
-            if metadata.patches.is_empty() {
-                println!("No patches configured.");
-                return Ok(());
-            }
+    if !path.is_empty() {
+        // Path provided: open shell
+        return open_shell_in_dir(repo, path, target_type);
+    }
 
-            if !path.is_empty() {
-                // Path provided: open shell
-                open_shell_in_dir(path, "local_path")?;
+    // No path: use fzf and copy to clipboard
+    match select_patch_interactive(&metadata) {
+        Ok(Some(patch)) => {
+            let target = if target_type == "worktree" {
+                &patch.worktree
             } else {
-                // No path: use fzf and copy to clipboard
-                match select_patch_interactive(&metadata) {
-                    Ok(Some(patch)) => {
-                        let rel_path = get_relative_path(&patch.local_path);
-                        copy_to_clipboard(&rel_path)?;
-                        log_success(&format!("Path copied to clipboard: {}", rel_path));
-                    }
-                    Ok(None) => {
-                        log_info("No selection made.");
-                    }
-                    Err(_) => {
-                        log_info("fzf not available. Showing patch list; rerun with a path.");
-                        println!("{}", Table::new(metadata.patches));
-                    }
-                }
-            }
+                &patch.local_path
+            };
+            let rel_path = get_relative_path(&repo.join(target).to_string_lossy());
+            copy_to_clipboard(&rel_path)?;
+            log_success(&format!("Path copied to clipboard: {}", rel_path));
         }
-        Commands::Wt { path } => {
-            let metadata = load_metadata()?;
-            if metadata.patches.is_empty() {
-                println!("No patches configured.");
-                return Ok(());
-            }
+        Ok(None) => {
+            log_info("No selection made.");
+        }
+        Err(_) => {
+            log_info("fzf not available. Showing patch list; rerun with a path.");
+            println!("{}", Table::new(metadata.patches));
+        }
+    }
+    Ok(())
+}
 
-            if !path.is_empty() {
-                // Path provided: open shell
-                open_shell_in_dir(path, "worktree")?;
-            } else {
-                // No path: use fzf and copy to clipboard
-                match select_patch_interactive(&metadata) {
-                    Ok(Some(patch)) => {
-                        let rel_path = get_relative_path(&patch.worktree);
-                        copy_to_clipboard(&rel_path)?;
-                        log_success(&format!("Path copied to clipboard: {}", rel_path));
-                    }
-                    Ok(None) => {
-                        log_info("No selection made.");
-                    }
-                    Err(_) => {
-                        log_info("fzf not available. Showing patch list; rerun with a path.");
-                        println!("{}", Table::new(metadata.patches));
-                    }
-                }
+/// Print rows as tab separated values under a header row.
+fn print_tsv(header: &[&str], rows: impl IntoIterator<Item = Vec<String>>) {
+    println!("{}", header.join("\t"));
+    for row in rows {
+        println!("{}", row.join("\t"));
+    }
+}
+
+fn print_json<T: Serialize>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+fn cmd_list(repo: &CrossRepo, format: OutputFormat) -> Result<()> {
+    let patches = repo.patches()?;
+    let remotes = repo.patch_remotes().unwrap_or_default();
+
+    match format {
+        OutputFormat::Json => {
+            #[derive(Serialize)]
+            struct ListOutput {
+                remotes: Vec<RemoteUrls>,
+                patches: Vec<Patch>,
             }
+            return print_json(&ListOutput { remotes, patches });
         }
-        Commands::List => {
-            let metadata = load_metadata()?;
-
-            // Collect unique remote names from patches
-            let used_remotes: std::collections::HashSet<String> =
-                metadata.patches.iter().map(|p| p.remote.clone()).collect();
-
-            if !used_remotes.is_empty() {
-                if let Ok(remotes) = run_cmd(&["git", "remote", "-v"]) {
-                    if !remotes.is_empty() {
-                        log_info("Configured Remotes:");
-
-                        // Map to track fetch/push URLs per remote for deduplication
-                        let mut remote_map: std::collections::HashMap<String, (String, String)> =
-                            std::collections::HashMap::new();
-
-                        for line in remotes.lines() {
-                            let fields: Vec<&str> = line.split_whitespace().collect();
-                            if fields.len() >= 3 {
-                                let name = fields[0];
-                                let url = fields[1];
-                                let rtype = fields[2];
-
-                                if !used_remotes.contains(name) {
-                                    continue;
-                                }
-
-                                let entry = remote_map.entry(name.to_string()).or_insert_with(|| (String::new(), String::new()));
-                                if rtype.contains("fetch") {
-                                    entry.0 = url.to_string();
-                                } else if rtype.contains("push") {
-                                    entry.1 = url.to_string();
-                                }
-                            }
-                        }
-
-                        #[derive(Tabled)]
-                        struct RemoteRow {
-                            name: String,
-                            url: String,
-                        }
-                        let mut rows: Vec<RemoteRow> = Vec::new();
-                        for (name, (fetch, push)) in &remote_map {
-                            if fetch == push || push.is_empty() {
-                                rows.push(RemoteRow {
-                                    name: name.clone(),
-                                    url: fetch.clone(),
-                                });
-                            } else {
-                                rows.push(RemoteRow {
-                                    name: name.clone(),
-                                    url: format!("{} (fetch)", fetch),
-                                });
-                                rows.push(RemoteRow {
-                                    name: name.clone(),
-                                    url: format!("{} (push)", push),
-                                });
-                            }
-                        }
-                        println!("{}", Table::new(rows));
-                        println!();
-                    }
-                }
-            }
+        OutputFormat::Tsv => {
+            print_tsv(
+                &[
+                    "id",
+                    "remote",
+                    "branch",
+                    "remote_path",
+                    "local_path",
+                    "worktree",
+                ],
+                patches.into_iter().map(|p| {
+                    vec![
+                        p.id,
+                        p.remote,
+                        p.branch,
+                        p.remote_path,
+                        p.local_path,
+                        p.worktree,
+                    ]
+                }),
+            );
+            return Ok(());
+        }
+        OutputFormat::Text => {}
+    }
+
+    if !remotes.is_empty() {
+        log_info("Configured Remotes:");
 
-            if metadata.patches.is_empty() {
-                println!("No patches configured.");
+        #[derive(Tabled)]
+        struct RemoteRow {
+            name: String,
+            url: String,
+        }
+        let mut rows: Vec<RemoteRow> = Vec::new();
+        for RemoteUrls { name, fetch, push } in remotes {
+            if fetch == push || push.is_empty() {
+                rows.push(RemoteRow { name, url: fetch });
             } else {
-                log_info("Configured Patches:");
-                println!("{}", Table::new(metadata.patches).to_string());
+                rows.push(RemoteRow {
+                    name: name.clone(),
+                    url: format!("{} (fetch)", fetch),
+                });
+                rows.push(RemoteRow {
+                    name,
+                    url: format!("{} (push)", push),
+                });
             }
         }
-        Commands::Status => {
-            let metadata = load_metadata()?;
-            if metadata.patches.is_empty() {
-                println!("No patches configured.");
-                return Ok(());
-            }
+        println!("{}", Table::new(rows));
+        println!();
+    }
 
-            // Get repo root for resolving relative paths
-            let root = get_repo_root()?;
-
-            #[derive(Tabled)]
-            struct StatusRow {
-                #[tabled(rename = "LOCAL PATH")]
-                path: String,
-                #[tabled(rename = "DIFF")]
-                diff: String,
-                #[tabled(rename = "UPSTREAM")]
-                upstream: String,
-                #[tabled(rename = "CONFLICTS")]
-                conflicts: String,
-            }
+    if patches.is_empty() {
+        println!("No patches configured.");
+    } else {
+        log_info("Configured Patches:");
+        println!("{}", Table::new(patches));
+    }
+    Ok(())
+}
 
-            let mut rows = Vec::new();
-            for patch in metadata.patches {
-                let mut row = StatusRow {
-                    path: patch.local_path.clone(),
-                    diff: "Clean".to_string(),
-                    upstream: "Synced".to_string(),
-                    conflicts: "No".to_string(),
-                };
-
-                // Resolve worktree path relative to repo root
-                let worktree_path = Path::new(&root).join(&patch.worktree);
-                if !worktree_path.exists() {
-                    row.diff = "Missing WT".to_string();
-                } else {
-                    // Both paths must be resolved relative to repo root
-                    let upstream_path = worktree_path.join(&patch.remote_path);
-                    let local_path = Path::new(&root).join(&patch.local_path);
-                    
-                    let diff_check = duct::cmd(
-                        "git",
-                        [
-                            "diff",
-                            "--no-index",
-                            "--quiet",
-                            &upstream_path.to_string_lossy(),
-                            &local_path.to_string_lossy(),
-                        ],
-                    )
-                    .unchecked()
-                    .run()?;
-                    if !diff_check.status.success() {
-                        row.diff = "Modified".to_string();
-                    }
-
-                    let behind = run_cmd(&[
-                        "git",
-                        "-C",
-                        &worktree_path.to_string_lossy(),
-                        "rev-list",
-                        "--count",
-                        "HEAD..@{upstream}",
-                    ])
-                    .unwrap_or_else(|_| "0".to_string());
-                    let ahead = run_cmd(&[
-                        "git",
-                        "-C",
-                        &worktree_path.to_string_lossy(),
-                        "rev-list",
-                        "--count",
-                        "@{upstream}..HEAD",
-                    ])
-                    .unwrap_or_else(|_| "0".to_string());
-
-                    if behind != "0" {
-                        row.upstream = format!("{} behind", behind);
-                    } else if ahead != "0" {
-                        row.upstream = format!("{} ahead", ahead);
-                    }
-
-                    match run_cmd(&["git", "-C", &worktree_path.to_string_lossy(), "ls-files", "-u"]) {
-                        Ok(c) if !c.is_empty() => row.conflicts = "YES".to_string(),
-                        _ => (),
-                    }
-                    
-                    // Also check conflicts in local path (from failed stash restore)
-                    let local_abs_path = Path::new(&root).join(&patch.local_path);
-                    match run_cmd(&["git", "-C", &root, "ls-files", "-u", &local_abs_path.to_string_lossy()]) {
-                        Ok(c) if !c.is_empty() => row.conflicts = "YES".to_string(),
-                        _ => (),
-                    }
-                }
-                rows.push(row);
-            }
-            println!("{}", Table::new(rows).to_string());
-        }
-        Commands::Remove { path } => {
-            let path = normalize_local_path(path);
-            let mut metadata = load_metadata()?;
-            let patch_idx = metadata
-                .patches
-                .iter()
-                .position(|p| normalize_local_path(&p.local_path) == path);
-
-            let patch = match patch_idx {
-                Some(idx) => metadata.patches.remove(idx),
-                None => return Err(anyhow!("Patch not found for path: {}", path)),
-            };
+/// Print patch status. With `fail_on` (`--check`), report patches in those
+/// categories and return the most severe one found.
+fn cmd_status(
+    repo: &CrossRepo,
+    format: OutputFormat,
+    fail_on: Option<&[StatusCategory]>,
+) -> Result<Option<StatusCategory>> {
+    let statuses = repo.status()?;
+    print_status(statuses.clone(), format)?;
+    Ok(fail_on.and_then(|fail_on| check_status(&statuses, fail_on)))
+}
 
-            log_info(&format!("Removing patch at {}...", path));
+fn check_status(statuses: &[PatchStatus], fail_on: &[StatusCategory]) -> Option<StatusCategory> {
+    let mut worst: Option<StatusCategory> = None;
+    for status in statuses {
+        let failing: Vec<StatusCategory> = status
+            .categories()
+            .into_iter()
+            .filter(|c| fail_on.contains(c))
+            .collect();
+        if failing.is_empty() {
+            continue;
+        }
+        let names: Vec<&str> = failing.iter().map(|c| c.as_str()).collect();
+        log_error(&format!(
+            "{}: {}",
+            status.patch.local_path,
+            names.join(", ")
+        ));
+        worst = worst.into_iter().chain(failing).min();
+    }
+    worst
+}
 
-            // 1. Remove worktree
-            if Path::new(&patch.worktree).exists() {
-                log_info(&format!("Removing git worktree at {}...", patch.worktree));
-                if let Err(e) = run_cmd(&["git", "worktree", "remove", "--force", &patch.worktree]) {
-                    log_error(&format!("Failed to remove worktree: {}", e));
-                }
-            }
+fn print_status(statuses: Vec<PatchStatus>, format: OutputFormat) -> Result<()> {
+    match format {
+        OutputFormat::Json => return print_json(&statuses),
+        OutputFormat::Tsv => {
+            print_tsv(
+                &[
+                    "local_path",
+                    "diff",
+                    "behind",
+                    "ahead",
+                    "worktree_present",
+                    "worktree_conflicts",
+                    "local_conflicts",
+                ],
+                statuses.into_iter().map(|s| {
+                    vec![
+                        s.patch.local_path,
+                        s.diff.as_str().to_string(),
+                        s.behind.to_string(),
+                        s.ahead.to_string(),
+                        s.worktree_present.to_string(),
+                        s.worktree_conflicts.to_string(),
+                        s.local_conflicts.to_string(),
+                    ]
+                }),
+            );
+            return Ok(());
+        }
+        OutputFormat::Text => {}
+    }
 
-            // 2. Remove from Crossfile
-            log_info("Removing from Crossfile...");
-            if let Ok(cross_path) = get_crossfile_path() {
-                if let Ok(content) = fs::read_to_string(&cross_path) {
-                    let lines: Vec<String> = content
-                        .lines()
-                        .filter(|l| !l.contains("patch") || !l.contains(&path))
-                        .map(|l| l.to_string())
-                        .collect();
-                    let mut new_content = lines.join("\n");
-                    if !new_content.is_empty() {
-                        new_content.push('\n');
-                    }
-                    let _ = fs::write(&cross_path, new_content);
-                }
-            }
+    if statuses.is_empty() {
+        println!("No patches configured.");
+        return Ok(());
+    }
 
-            // 3. Save metadata
-            log_info("Updating metadata...");
-            save_metadata(&metadata)?;
+    #[derive(Tabled)]
+    struct StatusRow {
+        #[tabled(rename = "LOCAL PATH")]
+        path: String,
+        #[tabled(rename = "DIFF")]
+        diff: String,
+        #[tabled(rename = "UPSTREAM")]
+        upstream: String,
+        #[tabled(rename = "CONFLICTS")]
+        conflicts: String,
+    }
 
-            // 4. Remove local directory
-            log_info(&format!("Deleting local directory {}...", path));
-            if let Err(e) = fs::remove_dir_all(&path) {
-                log_error(&format!("Failed to remove local directory: {}", e));
+    let rows: Vec<StatusRow> = statuses
+        .into_iter()
+        .map(|s| StatusRow {
+            diff: match s.diff {
+                DiffState::Clean => "Clean",
+                DiffState::Modified => "Modified",
+                DiffState::MissingWorktree => "Missing WT",
             }
+            .to_string(),
+            upstream: if let Some(summary) = s.ref_summary() {
+                summary
+            } else if s.behind > 0 {
+                format!("{} behind", s.behind)
+            } else if s.ahead > 0 {
+                format!("{} ahead", s.ahead)
+            } else {
+                "Synced".to_string()
+            },
+            conflicts: if s.has_conflicts() { "YES" } else { "No" }.to_string(),
+            path: s.patch.local_path,
+        })
+        .collect();
+    println!("{}", Table::new(rows));
+    Ok(())
+}
 
-            log_success("Patch removed successfully.");
+fn cmd_doctor(repo: &CrossRepo, fix: bool, format: OutputFormat, dry: bool) -> Result<()> {
+    let mut findings = repo.doctor()?;
+    if format == OutputFormat::Text {
+        print_findings(&findings);
+    }
+    let plan = repo.plan_doctor_fix(&findings);
+    if fix && !plan.is_empty() {
+        if format == OutputFormat::Text || dry {
+            print!("{}", plan);
         }
-        Commands::Prune { remote } => {
-            let mut metadata = load_metadata()?;
-            
-            if let Some(remote_name) = remote {
-                // Prune specific remote: remove all its patches
-                log_info(&format!("Pruning all patches for remote: {}...", remote_name));
-                
-                // Find all patches for this remote
-                let patches_to_remove: Vec<Patch> = metadata
-                    .patches
-                    .iter()
-                    .filter(|p| p.remote == *remote_name)
-                    .cloned()
-                    .collect();
-                
-                if patches_to_remove.is_empty() {
-                    log_info(&format!("No patches found for remote: {}", remote_name));
+        if !dry {
+            let changes = repo.execute(&plan)?;
+            findings = repo.doctor()?;
+            if format == OutputFormat::Text {
+                print_changes(&changes);
+                if findings.is_empty() {
+                    log_success("All findings repaired.");
                 } else {
-                    // Remove each patch
-                    for patch in patches_to_remove {
-                        log_info(&format!("Removing patch: {}", patch.local_path));
-                        
-                        // Remove worktree
-                        if Path::new(&patch.worktree).exists() {
-                            let _ = run_cmd(&["git", "worktree", "remove", "--force", &patch.worktree]);
-                        }
-                        
-                        // Remove from Crossfile
-                        if let Ok(cross_path) = get_crossfile_path() {
-                            if let Ok(content) = fs::read_to_string(&cross_path) {
-                                let lines: Vec<String> = content
-                                    .lines()
-                                    .filter(|l| !l.contains("patch") || !l.contains(&patch.local_path))
-                                    .map(|l| l.to_string())
-                                    .collect();
-                                let mut new_content = lines.join("\n");
-                                if !new_content.is_empty() {
-                                    new_content.push('\n');
-                                }
-                                let _ = fs::write(&cross_path, new_content);
-                            }
-                        }
-                        
-                        // Remove from metadata
-                        metadata.patches.retain(|p| p.local_path != patch.local_path);
-                        
-                        // Remove local directory
-                        let _ = fs::remove_dir_all(&patch.local_path);
-                    }
-                    save_metadata(&metadata)?;
-                }
-                
-                // Remove the remote itself
-                if let Ok(remotes) = run_cmd(&["git", "remote"]) {
-                    if remotes.lines().any(|r| r.trim() == remote_name) {
-                        log_info(&format!("Removing git remote: {}", remote_name));
-                        let _ = run_cmd(&["git", "remote", "remove", remote_name]);
-                    }
+                    log_info("Remaining findings:");
+                    print_findings(&findings);
                 }
-                
-                log_success(&format!("Remote {} and all its patches pruned successfully.", remote_name));
-            } else {
-                // Prune all unused remotes (no active patches)
-                log_info("Finding unused remotes...");
-                
-                // Get all remotes used by patches
-                let used_remotes: std::collections::HashSet<String> = 
-                    metadata.patches.iter().map(|p| p.remote.clone()).collect();
-                
-                // Get all git remotes
-                let all_remotes = run_cmd(&["git", "remote"])?;
-                let all_remotes: Vec<String> = all_remotes
-                    .lines()
-                    .map(|s| s.trim().to_string())
-                    .filter(|r| !r.is_empty() && r != "origin" && r != "git-cross")
-                    .collect();
-                
-                // Find unused remotes
-                let unused_remotes: Vec<String> = all_remotes
-                    .into_iter()
-                    .filter(|r| !used_remotes.contains(r))
-                    .collect();
-                
-                if unused_remotes.is_empty() {
-                    log_info("No unused remotes found.");
-                } else {
-                    log_info(&format!("Unused remotes: {}", unused_remotes.join(", ")));
-                    print!("Remove these remotes? [y/N]: ");
-                    std::io::stdout().flush()?;
-                    
-                    let mut input = String::new();
-                    std::io::stdin().read_line(&mut input)?;
-                    
-                    if input.trim().to_lowercase() == "y" {
-                        for remote in unused_remotes {
-                            log_info(&format!("Removing remote: {}", remote));
-                            let _ = run_cmd(&["git", "remote", "remove", &remote]);
-                        }
-                        log_success("Unused remotes removed.");
-                    } else {
-                        log_info("Pruning cancelled.");
-                    }
-                }
-                
-                // Always prune stale worktrees
-                log_info("Pruning stale worktrees...");
-                let _ = run_cmd(&["git", "worktree", "prune", "--verbose"]);
-                log_success("Worktree pruning complete.");
             }
         }
-        Commands::Diff { path } => {
-            // Resolve relative/absolute path to repo-relative
-            let resolved_path = if !path.is_empty() {
-                resolve_path_to_repo_relative(path)?
-            } else {
-                path.clone()
-            };
+    } else if format == OutputFormat::Text && !plan.is_empty() {
+        let fixable = findings.iter().filter(|f| f.fixable()).count();
+        log_info(&format!(
+            "Run 'cross doctor --fix' to repair {} finding(s).",
+            fixable
+        ));
+    }
 
-            // Get repo root for resolving relative paths in metadata
-            let root = get_repo_root()?;
+    match format {
+        OutputFormat::Json => print_json(&findings)?,
+        OutputFormat::Tsv => print_tsv(
+            &["severity", "subject", "message", "fixable"],
+            findings.iter().map(|f| {
+                vec![
+                    f.severity.as_str().to_string(),
+                    f.subject.clone(),
+                    f.message.clone(),
+                    f.fixable().to_string(),
+                ]
+            }),
+        ),
+        OutputFormat::Text => {}
+    }
 
-            let metadata = load_metadata()?;
-            let mut found = false;
-            for patch in metadata.patches {
-                if !resolved_path.is_empty() && patch.local_path != resolved_path {
-                    continue;
-                }
-                found = true;
-                
-                // Resolve worktree path relative to repo root
-                let worktree_path = Path::new(&root).join(&patch.worktree);
-                if !worktree_path.exists() {
-                    log_error(&format!("Worktree not found for {}", patch.local_path));
-                    continue;
-                }
+    let errors = findings
+        .iter()
+        .filter(|f| f.severity == Severity::Error)
+        .count();
+    if errors > 0 {
+        return Err(anyhow!("doctor found {} error(s)", errors));
+    }
+    Ok(())
+}
 
-                // Both paths must be resolved relative to repo root
-                let upstream_path = worktree_path.join(&patch.remote_path);
-                let local_path = Path::new(&root).join(&patch.local_path);
-                
-                // git diff --no-index returns 1 on differences, duct handles it via unchecked() if we want to ignore exit code
-                let _ = duct::cmd("git", ["diff", "--no-index", 
-                    &upstream_path.to_string_lossy(), 
-                    &local_path.to_string_lossy()]).run();
-            }
-            if !found && !resolved_path.is_empty() {
-                return Err(anyhow!("Patch not found for path: {}", resolved_path));
+fn cmd_apply(
+    repo: &CrossRepo,
+    yes: bool,
+    force: bool,
+    format: OutputFormat,
+    dry: bool,
+) -> Result<()> {
+    let changes = repo.apply_changes()?;
+    match format {
+        OutputFormat::Json => print_json(&changes)?,
+        OutputFormat::Tsv => print_tsv(
+            &["action", "kind", "subject", "from", "to", "local_changes"],
+            changes.iter().map(|c| {
+                vec![
+                    c.action.as_str().to_string(),
+                    c.kind.as_str().to_string(),
+                    c.subject.clone(),
+                    c.from.clone().unwrap_or_default(),
+                    c.to.clone().unwrap_or_default(),
+                    c.local_changes.len().to_string(),
+                ]
+            }),
+        ),
+        OutputFormat::Text => print_apply_changes(&changes),
+    }
+    if changes.is_empty() {
+        return Ok(());
+    }
+    if dry {
+        for change in &changes {
+            // Patches of remotes added by earlier changes cannot be planned
+            // yet; keep planning the rest, as `replay --dry` does.
+            match repo.plan_apply(change) {
+                Ok(plan) => print!("{}", plan),
+                Err(e) => log_error(&format!("{:#}", e.context(change.to_string()))),
             }
         }
-        Commands::Replay => {
-            log_info("Replaying Crossfile...");
-            let path = get_crossfile_path()?;
-            if !path.exists() {
-                println!("No Crossfile found.");
-                return Ok(());
-            }
+        return Ok(());
+    }
+    if !force && let Err(err) = check_local_changes(&changes) {
+        log_error("Run 'cross apply --force' to discard local edits.");
+        return Err(err);
+    }
+    if !yes && !confirm("Apply these changes?")? {
+        log_info("Apply cancelled.");
+        return Ok(());
+    }
+    for change in &changes {
+        let plan = repo.plan_apply(change)?;
+        if format == OutputFormat::Text {
+            print!("{}", plan);
+        }
+        let tree = repo.execute(&plan)?;
+        if format == OutputFormat::Text {
+            print_changes(&tree);
+        }
+    }
+    if format == OutputFormat::Text {
+        log_success("Repository matches the Crossfile.");
+    }
+    Ok(())
+}
 
-            let curr_exe = std::env::current_exe()?;
-            let script = format!(
-                r#"cross() {{ "{}" "$@"; }}; source "{}" "#,
-                curr_exe.display(),
-                path.display()
+fn print_apply_changes(changes: &[ApplyChange]) {
+    if changes.is_empty() {
+        println!("No changes: the repository matches the Crossfile.");
+        return;
+    }
+    for change in changes {
+        println!("{}", change);
+        for file in &change.local_changes {
+            println!(
+                "    local edit discarded: {} {}",
+                file.change.as_str(),
+                file.path
             );
-            let status = duct::cmd!("bash", "-c", script).unchecked().run()?;
+        }
+    }
+    let count = |action| changes.iter().filter(|c| c.action == action).count();
+    println!(
+        "Plan: {} to add, {} to update, {} to remove.",
+        count(ApplyAction::Add),
+        count(ApplyAction::Update),
+        count(ApplyAction::Remove)
+    );
+}
 
-            if status.status.success() {
-                log_success("Replay completed.");
-            } else {
-                return Err(anyhow::anyhow!("Replay failed"));
+fn cmd_repair(repo: &CrossRepo, format: OutputFormat, dry: bool) -> Result<()> {
+    let Some(crossfile) = repo.load_crossfile()? else {
+        return Err(anyhow!("No Crossfile found; nothing to repair from"));
+    };
+    let text = format == OutputFormat::Text;
+    for (line, statement) in crossfile.statements() {
+        let plan = match repo.plan_repair(statement) {
+            Ok(Some(plan)) => plan,
+            Ok(None) => continue,
+            Err(e) => {
+                let err = e.context(format!("{}: {}", crossfile.location(line), statement));
+                if dry {
+                    // As in replay: patches need the remotes added before them.
+                    log_error(&format!("{:#}", err));
+                    continue;
+                }
+                return Err(err);
             }
+        };
+        if text || dry {
+            print!("{}", plan);
+        }
+        if !dry {
+            repo.execute(&plan)?;
         }
-        Commands::Init => {
-            let path = "Crossfile";
-            if Path::new(path).exists() {
-                log_info("Crossfile already exists.");
-                return Ok(());
+    }
+    if dry {
+        return Ok(());
+    }
+
+    let report = repo.repair_report(&crossfile)?;
+    match format {
+        OutputFormat::Json => print_json(&report)?,
+        OutputFormat::Tsv => print_tsv(
+            &["local_path", "spec", "state", "files"],
+            report.iter().map(|p| {
+                vec![
+                    p.local_path.clone(),
+                    p.spec.clone(),
+                    p.state.as_str().to_string(),
+                    p.files.len().to_string(),
+                ]
+            }),
+        ),
+        OutputFormat::Text => {
+            for patch in &report {
+                println!("{:<9} {} ({})", patch.state, patch.local_path, patch.spec);
+                for file in &patch.files {
+                    println!("          {} {}", file.change.as_str(), file.path);
+                }
+            }
+            let count = |state| report.iter().filter(|p| p.state == state).count();
+            let modified = count(VendoredState::Modified) + count(VendoredState::Missing);
+            let unpinned = count(VendoredState::Unpinned);
+            if modified > 0 {
+                log_info(&format!(
+                    "{} patch(es) differ from upstream; 'cross diff' shows the changes.",
+                    modified
+                ));
+            }
+            if unpinned > 0 {
+                log_info(&format!(
+                    "{} patch(es) have no Crossfile.lock entry, so the upstream commit they \
+                     came from is unknown; review them before the next 'cross sync'.",
+                    unpinned
+                ));
+            }
+            if modified + unpinned == 0 {
+                log_success("Repair completed; all patches match upstream.");
             }
-            fs::write(path, "# git-cross configuration\n")?;
-            log_success("Crossfile initialized.");
         }
-        Commands::Push {
-            path,
-            branch,
-            force,
-            yes,
-            message,
-        } => {
-            let metadata = load_metadata()?;
-            let patch = metadata
-                .patches
-                .iter()
-                .find(|p| p.local_path == *path || path.is_empty())
-                .context(format!("Could not resolve patch context for '{}'", path))?;
+    }
+    Ok(())
+}
 
-            log_info(&format!(
-                "Syncing changes from {} back to {}...",
-                patch.local_path, patch.worktree
-            ));
+fn print_findings(findings: &[Finding]) {
+    if findings.is_empty() {
+        println!("No problems found.");
+    }
+    for finding in findings {
+        let fixable = if finding.fixable() { " (fixable)" } else { "" };
+        println!(
+            "{:<8} {}: {}{}",
+            finding.severity, finding.subject, finding.message, fixable
+        );
+        if let Some(hint) = &finding.hint
+            && !finding.fixable()
+        {
+            println!("         hint: {}", hint);
+        }
+    }
+}
 
-            let src = format!("{}/", patch.local_path);
-            let dst = format!("{}/{}/", patch.worktree, patch.remote_path);
-            run_cmd(&["rsync", "-av", "--delete", "--exclude", ".git", &src, &dst])?;
+fn cmd_outdated(repo: &CrossRepo, format: OutputFormat) -> Result<()> {
+    let report = repo.outdated()?;
+    match format {
+        OutputFormat::Json => return print_json(&report),
+        OutputFormat::Tsv => {
+            print_tsv(
+                &[
+                    "local_path",
+                    "current",
+                    "branch_tip",
+                    "latest_tag",
+                    "commits",
+                ],
+                report.into_iter().map(|o| {
+                    vec![
+                        o.patch.local_path,
+                        o.current,
+                        o.branch_tip.unwrap_or_default(),
+                        o.latest_tag.map(|t| t.name).unwrap_or_default(),
+                        o.commits.len().to_string(),
+                    ]
+                }),
+            );
+            return Ok(());
+        }
+        OutputFormat::Text => {}
+    }
 
-            log_info("Worktree updated. Status:");
+    if report.is_empty() {
+        println!("No patches configured.");
+        return Ok(());
+    }
+    for o in report {
+        let position = if o.patch.is_pinned() {
+            format!("pinned at {}", o.patch.ref_name())
+        } else if o.patch.ref_kind == RefKind::Range {
+            format!(
+                "{} ({})",
+                o.patch.tag.as_deref().unwrap_or("unresolved"),
+                o.patch.branch
+            )
+        } else {
+            format!("{}/{}", o.patch.remote, o.patch.branch)
+        };
+        let mut summary = if o.is_outdated() {
+            format!("{} new commit(s)", o.commits.len())
+        } else {
+            "up to date".to_string()
+        };
+        if let Some(tag) = &o.latest_match
+            && o.patch.tag.as_deref() != Some(tag.name.as_str())
+        {
+            summary.push_str(&format!(", {} matches {}", tag.name, o.patch.branch));
+        }
+        if let Some(tag) = &o.latest_tag {
+            summary.push_str(&format!(", latest tag {}", tag.name));
+        }
+        println!("{} ({}): {}", o.patch.local_path, position, summary);
+        for commit in &o.commits {
             println!(
-                "{}",
-                run_cmd(&["git", "-C", &patch.worktree, "status", "--short"])?
+                "  {} {}",
+                &commit.commit[..12.min(commit.commit.len())],
+                commit.subject
             );
+        }
+    }
+    Ok(())
+}
 
-            if !*yes {
-                println!("Run push? (y/n)");
-                let mut input = String::new();
-                std::io::stdin().read_line(&mut input)?;
-                if input.trim().to_lowercase() != "y" {
-                    log_info("Push cancelled.");
-                    return Ok(());
-                }
-            }
+fn cmd_diff(repo: &CrossRepo, path: &str, format: OutputFormat) -> Result<()> {
+    // Resolve relative/absolute path to repo-relative
+    let resolved_path = resolve_path_to_repo_relative(repo, path)?;
 
-            let msg = if let Some(m) = message {
-                m.clone()
-            } else {
-                run_cmd(&["git", "log", "-1", "--pretty=%s", "--", &patch.local_path])
-                    .unwrap_or_else(|_| "Update from git-cross".to_string())
-            };
+    let mut found = false;
+    let mut diffs = Vec::new();
+    for patch in repo.patches()? {
+        if !resolved_path.is_empty() && patch.local_path != resolved_path {
+            continue;
+        }
+        found = true;
 
-            log_info("Committing and pushing...");
-            run_cmd(&["git", "-C", &patch.worktree, "add", "."])?;
-            let _ = run_cmd(&["git", "-C", &patch.worktree, "commit", "-m", &msg]);
+        match repo.diff(&patch) {
+            Ok(diff) if format == OutputFormat::Text => print!("{}", diff.patch),
+            Ok(diff) => diffs.push(diff),
+            Err(e) => log_error(&format!("{:#}", e)),
+        }
+    }
+    if !found && !resolved_path.is_empty() {
+        return Err(CrossError::PatchNotFound {
+            path: resolved_path,
+        }
+        .into());
+    }
 
-            let target_branch = branch.as_ref().unwrap_or(&patch.branch);
+    match format {
+        OutputFormat::Json => print_json(&diffs)?,
+        OutputFormat::Tsv => print_tsv(
+            &["local_path", "change", "path"],
+            diffs.into_iter().flat_map(|d| {
+                let local_path = d.local_path;
+                d.files
+                    .into_iter()
+                    .map(move |f| vec![local_path.clone(), f.change.as_str().to_string(), f.path])
+            }),
+        ),
+        OutputFormat::Text => {}
+    }
+    Ok(())
+}
 
-            let mut push_args = vec!["-C", &patch.worktree, "push"];
-            if *force {
-                push_args.push("--force");
-            }
-            push_args.push(&patch.remote);
+/// Backend selected by `GIT_CROSS_BACKEND`: `libgit2` (default) or `cli`.
+fn git_backend() -> Result<Arc<dyn GitBackend>> {
+    match env::var("GIT_CROSS_BACKEND").as_deref() {
+        Err(_) | Ok("") | Ok("libgit2") => Ok(Arc::new(Libgit2Backend)),
+        Ok("cli") => Ok(Arc::new(CliBackend)),
+        Ok(other) => Err(anyhow!(
+            "Unknown GIT_CROSS_BACKEND '{}' (expected libgit2 or cli)",
+            other
+        )),
+    }
+}
 
-            let refspec = if target_branch.starts_with("refs/") {
-                format!("HEAD:{}", target_branch)
-            } else {
-                format!("HEAD:refs/heads/{}", target_branch)
-            };
-            push_args.push(&refspec);
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("Error: {:#}", err);
+        std::process::exit(CrossError::find(&err).map_or(1, CrossError::exit_code));
+    }
+}
 
-            let mut full_push_args = vec!["git"];
-            full_push_args.extend(push_args);
-            run_cmd(&full_push_args)?;
+fn run() -> Result<()> {
+    let cli = Cli::parse();
+    if let Commands::Init = cli.command {
+        // `init` works in the current directory, even outside a repository.
+        if Crossfile::init(Path::new(CROSSFILE_REL_PATH))? {
+            log_success("Crossfile initialized.");
+        } else {
+            log_info("Crossfile already exists.");
+        }
+        return Ok(());
+    }
+    let repo = CrossRepo::open_with(".", git_backend()?)?;
+    let dry = cli.dry;
+    let lock = if cli.command.locks() && !dry {
+        Some(repo.operation_lock()?)
+    } else {
+        None
+    };
+    if let Some(pid) = lock.as_ref().and_then(|lock| lock.stale()) {
+        log_info(&format!(
+            "Took over stale lock of pid {} (the process exited without releasing it)",
+            pid
+        ));
+    }
+    if cli.command.migrates() {
+        let migration = repo.plan_migration()?;
+        if !migration.is_empty() {
+            if !dry {
+                log_info("Migrating .git/cross/metadata.json");
+            }
+            run_plan(&repo, &migration, dry)?;
+        }
+    }
 
-            log_success("Push completed.");
+    match &cli.command {
+        Commands::Use { name, url } => cmd_use(&repo, name, url, dry)?,
+        Commands::Patch {
+            spec,
+            local_path,
+            include,
+            exclude,
+        } => {
+            let opts = PatchOptions {
+                local_path: local_path.clone(),
+                filter: PathFilter {
+                    include: include.clone(),
+                    exclude: exclude.clone(),
+                },
+                ..PatchOptions::default()
+            };
+            cmd_patch(&repo, spec, &opts, dry)?
         }
-        Commands::Exec { args } => {
-            let full_cmd = args.join(" ");
-            log_info(&format!("Executing custom command: {}", full_cmd));
-            let _ = duct::cmd("bash", ["-c", &full_cmd]).run();
+        Commands::Sync { path } => cmd_sync(&repo, path, dry)?,
+        Commands::Cd { path } => cmd_open(&repo, path, "local_path")?,
+        Commands::Wt { path } => cmd_open(&repo, path, "worktree")?,
+        Commands::List { format } => cmd_list(&repo, *format)?,
+        Commands::Status {
+            format,
+            check,
+            fail_on,
+        } => {
+            let failed = cmd_status(&repo, *format, check.then_some(fail_on.as_slice()))?;
+            if let Some(category) = failed {
+                std::process::exit(category.exit_code());
+            }
+        }
+        Commands::Remove { path } => cmd_remove(&repo, path, dry)?,
+        Commands::Mv { from, to } => cmd_mv(&repo, from, to, dry)?,
+        Commands::Retarget { path, spec } => cmd_retarget(&repo, path, spec, dry)?,
+        Commands::Prune { remote } => cmd_prune(&repo, remote.as_deref(), dry)?,
+        Commands::Outdated { format } => cmd_outdated(&repo, *format)?,
+        Commands::Diff { path, format } => cmd_diff(&repo, path, *format)?,
+        Commands::Replay => cmd_replay(&repo, dry)?,
+        Commands::Init => unreachable!("handled before opening the repository"),
+        Commands::Push {
+            path,
+            branch,
+            force,
+            yes,
+            message,
+        } => {
+            let opts = PushOptions {
+                branch: branch.clone(),
+                force: *force,
+                message: message.clone(),
+            };
+            cmd_push(&repo, path, &opts, *yes, dry)?;
         }
+        Commands::Doctor { fix, format } => cmd_doctor(&repo, *fix, *format, dry)?,
+        Commands::Repair { format, .. } => cmd_repair(&repo, *format, dry)?,
+        Commands::Apply { yes, force, format } => cmd_apply(&repo, *yes, *force, *format, dry)?,
+        Commands::Exec { args } => cmd_exec(&repo, args, dry)?,
     }
 
     Ok(())
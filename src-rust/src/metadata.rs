@@ -0,0 +1,391 @@
+//! `.git/cross/metadata.json`: the patches of a repository.
+//!
+//! The file carries a schema `version`. Older files are upgraded on load by
+//! running the [`MIGRATIONS`] after their version in order on the raw JSON,
+//! so each migration only needs to know the schema it starts from. Files
+//! without a version predate versioning and are version 0. Files newer than
+//! [`METADATA_VERSION`] are refused rather than misread.
+
+use crate::crossfile::Statement;
+use crate::error::CrossError;
+use crate::filter::PathFilter;
+use crate::spec::PatchSpec;
+use crate::util::{normalize_local_path, write_atomic};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tabled::Tabled;
+
+/// Schema version written by this build.
+pub const METADATA_VERSION: u32 = 2;
+
+/// `MIGRATIONS[n]` upgrades the JSON of schema version `n` to `n + 1`.
+const MIGRATIONS: [fn(&mut Value); METADATA_VERSION as usize] = [migrate_v0, migrate_v1];
+
+/// Patch objects of the metadata JSON.
+fn patches_mut(metadata: &mut Value) -> impl Iterator<Item = &mut Map<String, Value>> {
+    metadata
+        .get_mut("patches")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_object_mut)
+}
+
+/// Version 0 files may lack patch ids; the id is the hash suffix of the
+/// worktree directory name (`<remote>_<id>`).
+fn migrate_v0(metadata: &mut Value) {
+    for patch in patches_mut(metadata) {
+        if patch
+            .get("id")
+            .and_then(Value::as_str)
+            .is_some_and(|id| !id.is_empty())
+        {
+            continue;
+        }
+        let id = patch
+            .get("worktree")
+            .and_then(Value::as_str)
+            .and_then(|wt| wt.rsplit('/').next())
+            .and_then(|name| name.rsplit_once('_'))
+            .map(|(_, id)| id.to_string())
+            .unwrap_or_default();
+        patch.insert("id".to_string(), Value::String(id));
+    }
+}
+
+/// Version 1 ids hashed the spec with `DefaultHasher`, whose output may
+/// change with the Rust release; ids are now [`PatchSpec::id`]. Worktrees
+/// keep their directory until `CrossRepo::plan_migration` moves them.
+fn migrate_v1(metadata: &mut Value) {
+    for patch in patches_mut(metadata) {
+        let field = |name: &str| {
+            patch
+                .get(name)
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string()
+        };
+        let spec = PatchSpec {
+            remote: field("remote"),
+            remote_path: field("remote_path"),
+            branch: Some(field("branch")),
+            branch_provided: true,
+        };
+        patch.insert("id".to_string(), Value::String(spec.id()));
+    }
+}
+
+/// Kind of upstream ref a patch tracks.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RefKind {
+    /// `sync` follows the branch tip.
+    #[default]
+    Branch,
+    /// Pinned to the tagged commit.
+    Tag,
+    /// Pinned to a commit SHA.
+    Commit,
+    /// Semver range (`^1.4`, `~2.0.3`); `sync` moves to the highest
+    /// matching tag.
+    Range,
+}
+
+impl RefKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RefKind::Branch => "branch",
+            RefKind::Tag => "tag",
+            RefKind::Commit => "commit",
+            RefKind::Range => "range",
+        }
+    }
+
+    pub fn is_branch(&self) -> bool {
+        *self == RefKind::Branch
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Tabled, Clone, PartialEq, Eq)]
+pub struct Patch {
+    #[serde(default)]
+    pub id: String,
+    pub remote: String,
+    pub remote_path: String,
+    pub local_path: String,
+    /// Worktree directory, relative to the repository root.
+    pub worktree: String,
+    /// Branch the patch follows, the tag/commit it is pinned to, or its
+    /// version range.
+    #[tabled(skip)]
+    pub branch: String,
+    /// What `branch` names upstream.
+    #[serde(default, skip_serializing_if = "RefKind::is_branch")]
+    #[tabled(skip)]
+    pub ref_kind: RefKind,
+    /// Tag a version range currently resolves to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[tabled(skip)]
+    pub tag: Option<String>,
+    /// `remote_path` is a single file, vendored to the file `local_path`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    #[tabled(skip)]
+    pub file: bool,
+    /// Include/exclude globs, relative to the patch root.
+    #[serde(flatten)]
+    #[tabled(skip)]
+    pub filter: PathFilter,
+    /// Upstream commit the local content was last synced from; the merge
+    /// base of the next `sync`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[tabled(skip)]
+    pub base: Option<String>,
+    /// Files (repo relative) left with conflict markers by the last `sync`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[tabled(skip)]
+    pub conflicts: Vec<String>,
+}
+
+impl Patch {
+    /// Whether the patch is pinned to a tag or commit.
+    pub fn is_pinned(&self) -> bool {
+        matches!(self.ref_kind, RefKind::Tag | RefKind::Commit)
+    }
+
+    /// `branch` for display: tags as is, commits abbreviated.
+    pub fn ref_name(&self) -> &str {
+        match self.ref_kind {
+            RefKind::Commit => crate::plan::short_sha(&self.branch),
+            _ => &self.branch,
+        }
+    }
+
+    /// Canonical spec (`remote:ref:path`) of the patch.
+    pub fn spec(&self) -> String {
+        format!("{}:{}:{}", self.remote, self.branch, self.remote_path)
+    }
+
+    /// Crossfile `patch` statement recreating the patch.
+    pub fn statement(&self) -> Statement {
+        Statement::Patch {
+            spec: self.spec(),
+            local_path: Some(self.local_path.clone()),
+            filter: self.filter.clone(),
+        }
+    }
+
+    /// Repo relative local path of `rel`, a path relative to the patch root
+    /// (empty for the file of a single-file patch).
+    pub fn local_file(&self, rel: &str) -> String {
+        if rel.is_empty() {
+            self.local_path.clone()
+        } else {
+            format!("{}/{}", self.local_path, rel)
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Metadata {
+    pub version: u32,
+    pub patches: Vec<Patch>,
+}
+
+impl Default for Metadata {
+    fn default() -> Self {
+        Metadata {
+            version: METADATA_VERSION,
+            patches: Vec::new(),
+        }
+    }
+}
+
+/// Raw JSON of a metadata file and its schema version.
+fn read_versioned(path: &Path) -> Result<(u32, Value)> {
+    let corrupt = |reason: String| CrossError::MetadataCorrupt {
+        path: path.to_path_buf(),
+        reason,
+    };
+    let content = fs::read_to_string(path)?;
+    let value: Value = serde_json::from_str(&content).map_err(|e| corrupt(e.to_string()))?;
+    let version = match value.get("version") {
+        None => 0,
+        Some(v) => v
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| corrupt(format!("invalid version {}", v)))?,
+    };
+    if version > METADATA_VERSION {
+        return Err(CrossError::UnsupportedVersion {
+            path: path.to_path_buf(),
+            version,
+            supported: METADATA_VERSION,
+        }
+        .into());
+    }
+    Ok((version, value))
+}
+
+impl Metadata {
+    /// Load the metadata at `path`, migrating older schema versions in
+    /// memory. A missing file is empty metadata.
+    pub fn load(path: &Path) -> Result<Metadata> {
+        if !path.exists() {
+            return Ok(Metadata::default());
+        }
+        let (version, mut value) = read_versioned(path)?;
+        for migrate in &MIGRATIONS[version as usize..] {
+            migrate(&mut value);
+        }
+        value["version"] = METADATA_VERSION.into();
+        serde_json::from_value(value).map_err(|e| {
+            CrossError::MetadataCorrupt {
+                path: path.to_path_buf(),
+                reason: e.to_string(),
+            }
+            .into()
+        })
+    }
+
+    /// Schema version of the file at `path`; `None` when it does not exist.
+    pub fn file_version(path: &Path) -> Result<Option<u32>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(read_versioned(path)?.0))
+    }
+
+    /// Rewrite an older metadata file in the current schema, keeping the
+    /// original as `<file>.v<version>.bak` next to it. Returns the backup,
+    /// or `None` when the file is missing or already current.
+    pub fn upgrade(path: &Path) -> Result<Option<PathBuf>> {
+        let version = match Metadata::file_version(path)? {
+            Some(version) if version < METADATA_VERSION => version,
+            _ => return Ok(None),
+        };
+        let metadata = Metadata::load(path)?;
+        let mut backup = path.as_os_str().to_owned();
+        backup.push(format!(".v{}.bak", version));
+        let backup = PathBuf::from(backup);
+        fs::copy(path, &backup)?;
+        metadata.save(path)?;
+        Ok(Some(backup))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let content = serde_json::to_string_pretty(self)?;
+        write_atomic(path, content.as_bytes())
+    }
+
+    /// Insert or replace the patch with the same local path.
+    pub fn upsert(&mut self, patch: Patch) {
+        match self
+            .patches
+            .iter_mut()
+            .find(|p| p.local_path == patch.local_path)
+        {
+            Some(existing) => *existing = patch,
+            None => self.patches.push(patch),
+        }
+    }
+
+    /// Whether a patch other than the one at `local_path` uses `worktree`
+    /// (same spec, vendored twice).
+    pub fn shares_worktree(&self, worktree: &str, local_path: &str) -> bool {
+        self.patches
+            .iter()
+            .any(|p| p.worktree == worktree && p.local_path != local_path)
+    }
+
+    /// Patch whose local path is exactly `local_path`.
+    pub fn find(&self, local_path: &str) -> Option<&Patch> {
+        let local_path = normalize_local_path(local_path);
+        self.patches
+            .iter()
+            .find(|p| normalize_local_path(&p.local_path) == local_path)
+    }
+
+    /// Patch containing the repo-relative path `rel` (innermost wins).
+    pub fn find_containing(&self, rel: &str) -> Option<&Patch> {
+        let rel = normalize_local_path(rel);
+        if rel.is_empty() {
+            return None;
+        }
+
+        let mut selected: Option<&Patch> = None;
+        let mut longest = 0usize;
+        for patch in &self.patches {
+            let lp = normalize_local_path(&patch.local_path);
+            if lp.is_empty() {
+                continue;
+            }
+            if (rel == lp || rel.starts_with(&(lp.clone() + "/"))) && lp.len() > longest {
+                longest = lp.len();
+                selected = Some(patch);
+            }
+        }
+
+        selected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{METADATA_VERSION, Metadata};
+    use crate::error::CrossError;
+    use crate::spec::PatchSpec;
+    use std::fs;
+
+    const V0: &str = r#"{
+  "patches": [
+    {
+      "remote": "demo",
+      "remote_path": "docs",
+      "local_path": "vendor/docs",
+      "worktree": ".git/cross/worktrees/demo_1a2b3c4d",
+      "branch": "main"
+    }
+  ]
+}"#;
+
+    #[test]
+    fn upgrade_migrates_v0_and_keeps_a_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("metadata.json");
+        fs::write(&path, V0).unwrap();
+
+        let metadata = Metadata::load(&path).unwrap();
+        assert_eq!(metadata.version, METADATA_VERSION);
+        let spec = PatchSpec::parse("demo:main:docs").unwrap();
+        assert_eq!(metadata.patches[0].id, spec.id());
+        assert_eq!(Metadata::file_version(&path).unwrap(), Some(0));
+
+        let backup = Metadata::upgrade(&path).unwrap().unwrap();
+        assert_eq!(backup, dir.path().join("metadata.json.v0.bak"));
+        assert_eq!(fs::read_to_string(&backup).unwrap(), V0);
+        assert_eq!(
+            Metadata::file_version(&path).unwrap(),
+            Some(METADATA_VERSION)
+        );
+        assert_eq!(Metadata::load(&path).unwrap().patches, metadata.patches);
+        assert_eq!(Metadata::upgrade(&path).unwrap(), None);
+    }
+
+    #[test]
+    fn newer_version_is_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("metadata.json");
+        let newer = METADATA_VERSION + 1;
+        fs::write(&path, format!(r#"{{"version": {}, "patches": []}}"#, newer)).unwrap();
+
+        let err = Metadata::load(&path).unwrap_err();
+        assert!(matches!(
+            CrossError::find(&err),
+            Some(CrossError::UnsupportedVersion { version, .. }) if *version == newer
+        ));
+        assert!(Metadata::upgrade(&path).is_err());
+    }
+}
@@ -0,0 +1,125 @@
+//! `.git/cross/operation.lock`: serializes mutating `cross` operations.
+//!
+//! The lock is an advisory lock (`flock`) on the file, so the operating
+//! system releases it when its holder exits, even by crashing. The holder
+//! writes its pid into the file and empties it again when done; a pid left
+//! behind in an unlocked file is a stale lock of a process that died.
+
+use crate::error::CrossError;
+use anyhow::{Context, Result};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+#[derive(Debug)]
+pub struct OperationLock {
+    file: File,
+    path: PathBuf,
+    stale: Option<String>,
+}
+
+impl OperationLock {
+    /// Lock `path`, failing with [`CrossError::Locked`] when another process
+    /// holds it.
+    pub fn acquire(path: &Path) -> Result<OperationLock> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .with_context(|| format!("Cannot open {}", path.display()))?;
+        let mut holder = String::new();
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                file.read_to_string(&mut holder)?;
+                let pid = holder.trim();
+                return Err(CrossError::Locked {
+                    path: path.to_path_buf(),
+                    pid: if pid.is_empty() { "unknown" } else { pid }.to_string(),
+                }
+                .into());
+            }
+            Err(TryLockError::Error(e)) => {
+                return Err(e).with_context(|| format!("Cannot lock {}", path.display()));
+            }
+        }
+
+        file.read_to_string(&mut holder)?;
+        let stale = Some(holder.trim().to_string()).filter(|pid| !pid.is_empty());
+        file.set_len(0)?;
+        file.rewind()?;
+        writeln!(file, "{}", process::id())?;
+        Ok(OperationLock {
+            file,
+            path: path.to_path_buf(),
+            stale,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Pid recorded by a process that exited without releasing the lock.
+    pub fn stale(&self) -> Option<&str> {
+        self.stale.as_deref()
+    }
+}
+
+impl Drop for OperationLock {
+    fn drop(&mut self) {
+        // The file stays: removing it would let a process that already
+        // opened it lock an unlinked file next to a new one.
+        let _ = self.file.set_len(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OperationLock;
+    use crate::error::CrossError;
+    use std::fs;
+
+    #[test]
+    fn second_acquire_fails_while_held() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cross/operation.lock");
+        let lock = OperationLock::acquire(&path).unwrap();
+        assert_eq!(lock.stale(), None);
+
+        let err = OperationLock::acquire(&path).unwrap_err();
+        let pid = std::process::id().to_string();
+        assert!(matches!(
+            CrossError::find(&err),
+            Some(CrossError::Locked { pid: holder, .. }) if *holder == pid
+        ));
+        assert!(
+            err.to_string()
+                .contains("another cross operation is running")
+        );
+
+        drop(lock);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "");
+        OperationLock::acquire(&path).unwrap();
+    }
+
+    #[test]
+    fn pid_left_in_unlocked_file_is_stale() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("operation.lock");
+        fs::write(&path, "4242\n").unwrap();
+
+        let lock = OperationLock::acquire(&path).unwrap();
+        assert_eq!(lock.stale(), Some("4242"));
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            format!("{}\n", std::process::id())
+        );
+    }
+}
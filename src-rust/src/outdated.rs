@@ -0,0 +1,137 @@
+//! `outdated`: how far each patch is behind its upstream branch and newest tag.
+//!
+//! Every remote is fetched once. Only commits touching the patch's
+//! `remote_path` are listed, so upstream activity elsewhere in the repository
+//! does not make a patch look outdated.
+
+use crate::error::CrossError;
+use crate::metadata::{Patch, RefKind};
+use crate::repo::CrossRepo;
+use crate::tags::{UpstreamTag, highest_matching, newest};
+use anyhow::{Context, Result};
+use semver::VersionReq;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct UpstreamCommit {
+    pub commit: String,
+    pub subject: String,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct OutdatedPatch {
+    pub patch: Patch,
+    /// Upstream commit the patch was last synced from.
+    pub current: String,
+    /// Tip of the followed branch; `None` for tag-based patches.
+    pub branch_tip: Option<String>,
+    /// Newest semver tag of the remote.
+    pub latest_tag: Option<UpstreamTag>,
+    /// Highest tag matching the range of a version-range patch.
+    pub latest_match: Option<UpstreamTag>,
+    /// Commits touching `remote_path` since `current`, newest first: up to the
+    /// branch tip, the highest matching tag for version ranges, or the newest
+    /// tag for pinned patches.
+    pub commits: Vec<UpstreamCommit>,
+}
+
+impl OutdatedPatch {
+    pub fn is_outdated(&self) -> bool {
+        !self.commits.is_empty()
+    }
+}
+
+impl CrossRepo {
+    /// Fetch every remote used by a patch once and report each patch.
+    pub fn outdated(&self) -> Result<Vec<OutdatedPatch>> {
+        let patches = self.patches()?;
+        let remotes: BTreeSet<&str> = patches.iter().map(|p| p.remote.as_str()).collect();
+        let mut tags: HashMap<&str, Vec<UpstreamTag>> = HashMap::new();
+        for remote in remotes {
+            let remote_tags = self.remote_tags(remote)?;
+            // Tags compared against are fetched without creating local tags.
+            let mut wanted: BTreeSet<&str> = BTreeSet::new();
+            wanted.extend(newest(&remote_tags).map(|t| t.name.as_str()));
+            for patch in patches.iter().filter(|p| p.remote == remote) {
+                if let Some(tag) = range_match(patch, &remote_tags) {
+                    wanted.insert(tag.name.as_str());
+                }
+            }
+            let mut refspecs = vec![format!("+refs/heads/*:refs/remotes/{}/*", remote)];
+            refspecs.extend(wanted.iter().map(|t| format!("refs/tags/{}", t)));
+            self.git().fetch(self.root(), remote, &refspecs)?;
+            tags.insert(remote, remote_tags);
+        }
+
+        patches
+            .iter()
+            .map(|patch| {
+                let remote_tags = tags.get(patch.remote.as_str()).map(Vec::as_slice);
+                self.patch_outdated(patch, remote_tags.unwrap_or_default())
+            })
+            .collect()
+    }
+
+    /// Compare one patch with already fetched upstream refs.
+    fn patch_outdated(&self, patch: &Patch, tags: &[UpstreamTag]) -> Result<OutdatedPatch> {
+        let current = match &patch.base {
+            Some(base) => base.clone(),
+            None if self.join(&patch.worktree).exists() => {
+                self.git().commit_id(&self.join(&patch.worktree), "HEAD")?
+            }
+            None => {
+                return Err(CrossError::WorktreeMissing {
+                    path: patch.local_path.clone(),
+                })
+                .context("No sync base recorded");
+            }
+        };
+        let latest_tag = newest(tags).cloned();
+        let latest_match = range_match(patch, tags).cloned();
+
+        let branch_tip = if patch.ref_kind.is_branch() {
+            let tip = format!("refs/remotes/{}/{}", patch.remote, patch.branch);
+            Some(self.git().commit_id(self.root(), &tip)?)
+        } else {
+            None
+        };
+        let target = branch_tip
+            .clone()
+            .or_else(|| latest_match.as_ref().map(|t| t.commit.clone()))
+            .or_else(|| latest_tag.as_ref().map(|t| t.commit.clone()));
+
+        let commits = match target {
+            Some(target) if target != current => self.commits_touching(patch, &current, &target)?,
+            _ => Vec::new(),
+        };
+
+        Ok(OutdatedPatch {
+            patch: patch.clone(),
+            current,
+            branch_tip,
+            latest_tag,
+            latest_match,
+            commits,
+        })
+    }
+
+    /// Commits in `from..to` touching the remote path of `patch`.
+    fn commits_touching(&self, patch: &Patch, from: &str, to: &str) -> Result<Vec<UpstreamCommit>> {
+        Ok(self
+            .git()
+            .log(self.root(), from, to, &patch.remote_path)?
+            .into_iter()
+            .map(|(commit, subject)| UpstreamCommit { commit, subject })
+            .collect())
+    }
+}
+
+/// Highest tag matching the range of a version-range patch.
+fn range_match<'a>(patch: &Patch, tags: &'a [UpstreamTag]) -> Option<&'a UpstreamTag> {
+    if patch.ref_kind != RefKind::Range {
+        return None;
+    }
+    let req = VersionReq::parse(&patch.branch).ok()?;
+    highest_matching(tags, &req)
+}
@@ -0,0 +1,479 @@
+//! Planned operations.
+//!
+//! Mutating commands first describe everything they are going to change as a
+//! [`Plan`]. With `--dry` the plan is printed and nothing else happens,
+//! otherwise the very same plan is executed op by op. Building a plan only
+//! reads state; it may fetch remotes so that the plan reflects upstream.
+
+use crate::crossfile::{Prefix, Statement};
+use crate::filter::PathFilter;
+use crate::lock::LockedPatch;
+use crate::metadata::{METADATA_VERSION, Metadata, Patch};
+use crate::repo::CrossRepo;
+use crate::tree::{
+    MirrorOptions, TreeChanges, is_executable, mirror, sync_file, write_file, write_symlink,
+};
+use anyhow::{Context, Result, anyhow};
+use std::fmt;
+use std::fs;
+
+#[derive(Debug, Clone)]
+pub enum Op {
+    /// Add a remote, or point an existing one at a new URL.
+    SetRemote {
+        name: String,
+        url: String,
+        exists: bool,
+    },
+    RemoveRemote(String),
+    /// Fetch a branch into its remote-tracking ref.
+    Fetch {
+        remote: String,
+        branch: String,
+    },
+    /// Create a worktree detached at `commit` with only `sparse` checked out.
+    AddWorktree {
+        path: String,
+        commit: String,
+        sparse: String,
+    },
+    /// Move a worktree to `commit`, checking out only `sparse`. `force`
+    /// discards local changes in the worktree.
+    Checkout {
+        worktree: String,
+        commit: String,
+        sparse: String,
+        force: bool,
+    },
+    /// Remove a worktree, including local changes.
+    RemoveWorktree(String),
+    /// Forget worktrees whose directory is gone.
+    PruneWorktrees,
+    /// Stage everything below `path` in a worktree and commit it if anything
+    /// changed.
+    Commit {
+        worktree: String,
+        path: String,
+        message: String,
+    },
+    /// Push the worktree `HEAD` with `refspec`.
+    Push {
+        worktree: String,
+        remote: String,
+        refspec: String,
+        force: bool,
+    },
+    /// Mirror `src/` into `dst/`, optionally deleting extra files.
+    Mirror {
+        src: String,
+        dst: String,
+        delete: bool,
+        /// Only files passing the filter are copied or deleted.
+        filter: PathFilter,
+        /// Files (repo relative) the mirror deletes, as found when planning.
+        removes: Vec<String>,
+    },
+    /// Copy a single file, keeping its executable bit.
+    CopyFile {
+        src: String,
+        dst: String,
+    },
+    /// Write the blob `blob` to `path`, replacing any existing file.
+    WriteBlob {
+        path: String,
+        blob: String,
+        executable: bool,
+        /// The blob is a symlink target.
+        symlink: bool,
+    },
+    /// Write the result of a three-way merge to `path`.
+    WriteMerged {
+        path: String,
+        content: Vec<u8>,
+        conflicted: bool,
+    },
+    /// Move a path of the main repository and its index entries (`git mv`).
+    MovePath {
+        src: String,
+        dst: String,
+    },
+    CreateDir(String),
+    RemoveFile(String),
+    RemoveDir {
+        path: String,
+        /// Files (repo relative) deleted with it, as found when planning.
+        /// Empty for directories outside the tree, such as worktrees.
+        removes: Vec<String>,
+    },
+    /// Rewrite `.git/cross/metadata.json` of schema version `from` in the
+    /// current schema, keeping the original as `metadata.json.v<from>.bak`.
+    UpgradeMetadata {
+        from: u32,
+    },
+    /// Write `.git/cross/metadata.json`.
+    SaveMetadata {
+        metadata: Metadata,
+        change: String,
+    },
+    /// Insert or replace one patch in `.git/cross/metadata.json`.
+    SavePatch {
+        patch: Patch,
+        change: String,
+    },
+    /// Record a command in the Crossfile (no-op if an equivalent line exists).
+    AddToCrossfile(Statement),
+    /// Rewrite the `patch` line for a local path, keeping prefix and comments.
+    ReplaceInCrossfile {
+        local_path: String,
+        statement: Statement,
+    },
+    /// Drop the `patch` line for a local path from the Crossfile.
+    RemoveFromCrossfile {
+        local_path: String,
+    },
+    /// Record the upstream commit of a patch in `Crossfile.lock`.
+    LockPatch(LockedPatch),
+    /// Drop a patch from `Crossfile.lock`.
+    UnlockPatch {
+        local_path: String,
+    },
+    /// Run a shell command through `bash -c`.
+    Exec(String),
+}
+
+impl Op {
+    /// Execute the op, adding the files it wrote or deleted to `changes`.
+    /// Paths are relative to the repository root.
+    fn execute(&self, repo: &CrossRepo, changes: &mut TreeChanges) -> Result<()> {
+        let (git, root) = (repo.git(), repo.root());
+        match self {
+            Op::SetRemote { name, url, .. } => git.set_remote(root, name, url)?,
+            Op::RemoveRemote(name) => git.remove_remote(root, name)?,
+            Op::Fetch { remote, branch } => git.fetch_branch(root, remote, branch)?,
+            Op::AddWorktree {
+                path,
+                commit,
+                sparse,
+            } => git.add_worktree(root, &repo.join(path), commit, sparse)?,
+            Op::Checkout {
+                worktree,
+                commit,
+                sparse,
+                force,
+            } => git.checkout(&repo.join(worktree), commit, sparse, *force)?,
+            Op::RemoveWorktree(path) => git.remove_worktree(root, &repo.join(path))?,
+            Op::PruneWorktrees => {
+                git.prune_worktrees(root)?;
+            }
+            Op::Commit {
+                worktree,
+                path,
+                message,
+            } => {
+                git.commit_path(&repo.join(worktree), path, message)?;
+            }
+            Op::Push {
+                worktree,
+                remote,
+                refspec,
+                force,
+            } => git.push(&repo.join(worktree), remote, refspec, *force)?,
+            Op::Mirror {
+                src,
+                dst,
+                delete,
+                filter,
+                ..
+            } => {
+                let opts = MirrorOptions {
+                    delete: *delete,
+                    filter: filter.clone(),
+                };
+                changes.append(mirror(&repo.join(src), &repo.join(dst), &opts)?.prefixed(dst));
+            }
+            Op::CopyFile { src, dst } => {
+                if let Some(existed) = sync_file(&repo.join(src), &repo.join(dst))? {
+                    changes.record(dst, existed);
+                }
+            }
+            Op::WriteBlob {
+                path,
+                blob,
+                executable,
+                symlink,
+            } => {
+                let full = repo.join(path);
+                let existed = fs::symlink_metadata(&full).is_ok();
+                let content = repo.read_blob(blob)?;
+                if *symlink {
+                    write_symlink(&full, &content)?;
+                } else {
+                    write_file(&full, &content, *executable)?;
+                }
+                changes.record(path, existed);
+            }
+            Op::WriteMerged { path, content, .. } => {
+                let full = repo.join(path);
+                let executable = is_executable(&full);
+                write_file(&full, content, executable)?;
+                changes.modified.push(path.clone());
+            }
+            Op::MovePath { src, dst } => git.move_path(root, src, dst)?,
+            Op::CreateDir(path) => fs::create_dir_all(repo.join(path))?,
+            Op::RemoveFile(path) => {
+                fs::remove_file(repo.join(path))?;
+                changes.removed.push(path.clone());
+            }
+            Op::RemoveDir { path, removes } => {
+                fs::remove_dir_all(repo.join(path))?;
+                changes.removed.extend(removes.iter().cloned());
+            }
+            Op::UpgradeMetadata { .. } => {
+                Metadata::upgrade(&repo.metadata_path())?;
+            }
+            Op::SaveMetadata { metadata, .. } => repo.save_metadata(metadata)?,
+            Op::SavePatch { patch, .. } => {
+                let mut metadata = repo.load_metadata()?;
+                metadata.upsert(patch.clone());
+                repo.save_metadata(&metadata)?;
+            }
+            Op::AddToCrossfile(statement) => repo.update_crossfile(statement)?,
+            Op::ReplaceInCrossfile {
+                local_path,
+                statement,
+            } => repo.replace_crossfile_patch(local_path, statement)?,
+            Op::RemoveFromCrossfile { local_path } => repo.remove_crossfile_patch(local_path)?,
+            Op::LockPatch(entry) => {
+                let path = repo.lockfile_path();
+                let mut lock = repo.load_lock()?;
+                lock.upsert(entry.clone());
+                lock.save(&path)?;
+            }
+            Op::UnlockPatch { local_path } => {
+                let path = repo.lockfile_path();
+                let mut lock = repo.load_lock()?;
+                if lock.find(local_path).is_some() {
+                    lock.remove(local_path);
+                    lock.save(&path)?;
+                }
+            }
+            Op::Exec(command) => {
+                let output = duct::cmd("bash", ["-c", command])
+                    .dir(repo.root())
+                    .unchecked()
+                    .run()?;
+                if !output.status.success() {
+                    return Err(anyhow!("Command exited with {}", output.status));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Op::SetRemote { name, url, exists } => {
+                let action = if *exists { "set-url" } else { "add" };
+                write!(f, "git remote {} {} {}", action, name, url)
+            }
+            Op::RemoveRemote(name) => write!(f, "git remote remove {}", name),
+            Op::Fetch { remote, branch } => write!(f, "git fetch {} {}", remote, branch),
+            Op::AddWorktree {
+                path,
+                commit,
+                sparse,
+            } => write!(
+                f,
+                "git worktree add --detach {} {} (checkout {})",
+                shell_words::quote(path),
+                commit,
+                sparse
+            ),
+            Op::Checkout {
+                worktree,
+                commit,
+                force,
+                ..
+            } => {
+                let force = if *force { " --force" } else { "" };
+                write!(
+                    f,
+                    "git -C {} checkout{} --detach {}",
+                    shell_words::quote(worktree),
+                    force,
+                    commit
+                )
+            }
+            Op::RemoveWorktree(path) => {
+                write!(
+                    f,
+                    "git worktree remove --force {}",
+                    shell_words::quote(path)
+                )
+            }
+            Op::PruneWorktrees => write!(f, "git worktree prune"),
+            Op::Commit {
+                worktree, message, ..
+            } => write!(
+                f,
+                "git -C {} commit -m {} (if anything changed)",
+                shell_words::quote(worktree),
+                shell_words::quote(message)
+            ),
+            Op::Push {
+                worktree,
+                remote,
+                refspec,
+                force,
+            } => {
+                let force = if *force { " --force" } else { "" };
+                write!(
+                    f,
+                    "git -C {} push{} {} {}",
+                    shell_words::quote(worktree),
+                    force,
+                    remote,
+                    refspec
+                )
+            }
+            Op::Mirror {
+                src,
+                dst,
+                delete,
+                filter,
+                removes,
+            } => {
+                let mode = if *delete { "mirror" } else { "copy" };
+                write!(f, "{} {}/ -> {}/", mode, src, dst)?;
+                if !filter.is_empty() {
+                    write!(f, " ({})", filter)?;
+                }
+                for path in removes {
+                    write!(f, "\n    delete {}", path)?;
+                }
+                Ok(())
+            }
+            Op::CopyFile { src, dst } => write!(f, "copy {} -> {}", src, dst),
+            Op::WriteBlob { path, blob, .. } => {
+                write!(f, "update {} from upstream {}", path, short_sha(blob))
+            }
+            Op::WriteMerged {
+                path, conflicted, ..
+            } => {
+                if *conflicted {
+                    write!(f, "merge {} (conflicts)", path)
+                } else {
+                    write!(f, "merge {}", path)
+                }
+            }
+            Op::MovePath { src, dst } => write!(
+                f,
+                "git mv {} {}",
+                shell_words::quote(src),
+                shell_words::quote(dst)
+            ),
+            Op::CreateDir(path) => write!(f, "mkdir -p {}", path),
+            Op::RemoveFile(path) => write!(f, "delete {}", path),
+            Op::RemoveDir { path, removes } => {
+                write!(f, "delete directory {}", path)?;
+                for path in removes {
+                    write!(f, "\n    delete {}", path)?;
+                }
+                Ok(())
+            }
+            Op::UpgradeMetadata { from } => write!(
+                f,
+                "metadata: upgrade from version {} to {} (backup metadata.json.v{}.bak)",
+                from, METADATA_VERSION, from
+            ),
+            Op::SaveMetadata { change, .. } | Op::SavePatch { change, .. } => {
+                write!(f, "metadata: {}", change)
+            }
+            Op::AddToCrossfile(statement) => {
+                write!(
+                    f,
+                    "Crossfile: add '{} {}'",
+                    Prefix::Cross.as_str(),
+                    statement
+                )
+            }
+            Op::ReplaceInCrossfile {
+                local_path,
+                statement,
+            } => write!(
+                f,
+                "Crossfile: rewrite patch for {} as '{}'",
+                local_path, statement
+            ),
+            Op::RemoveFromCrossfile { local_path } => {
+                write!(f, "Crossfile: remove patch for {}", local_path)
+            }
+            Op::LockPatch(entry) => write!(
+                f,
+                "Crossfile.lock: pin {} at {}",
+                entry.local_path,
+                short_sha(&entry.commit)
+            ),
+            Op::UnlockPatch { local_path } => {
+                write!(f, "Crossfile.lock: remove {}", local_path)
+            }
+            Op::Exec(command) => write!(f, "bash -c {}", shell_words::quote(command)),
+        }
+    }
+}
+
+/// An ordered list of operations for one unit of work (usually one patch).
+#[derive(Debug, Clone)]
+pub struct Plan {
+    pub title: String,
+    pub ops: Vec<Op>,
+}
+
+impl Plan {
+    pub fn new(title: impl Into<String>) -> Plan {
+        Plan {
+            title: title.into(),
+            ops: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, op: Op) {
+        self.ops.push(op);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// Execute ops in order, stopping at the first failure, while holding
+    /// the operation lock. Returns the files written or deleted by copy,
+    /// mirror and merge ops.
+    pub fn execute(&self, repo: &CrossRepo) -> Result<TreeChanges> {
+        let _lock = repo.operation_lock()?;
+        let mut changes = TreeChanges::default();
+        for op in &self.ops {
+            op.execute(repo, &mut changes)
+                .with_context(|| op.to_string())?;
+        }
+        Ok(changes)
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}:", self.title)?;
+        if self.ops.is_empty() {
+            writeln!(f, "  (nothing to do)")?;
+        }
+        for op in &self.ops {
+            writeln!(f, "  {}", op)?;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn short_sha(sha: &str) -> &str {
+    &sha[..sha.len().min(12)]
+}
@@ -0,0 +1,197 @@
+//! `push`: send local changes of a patch back to its upstream branch.
+
+use crate::error::CrossError;
+use crate::metadata::Patch;
+use crate::plan::{Op, Plan};
+use crate::repo::CrossRepo;
+use crate::tree;
+use anyhow::{Result, anyhow};
+
+#[derive(Debug, Clone, Default)]
+pub struct PushOptions {
+    /// Target branch (or full ref); defaults to the patch branch.
+    pub branch: Option<String>,
+    pub force: bool,
+    /// Commit message; defaults to the last commit subject touching the patch.
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PushPlan {
+    pub patch: Patch,
+    /// `HEAD:refs/heads/<branch>`.
+    pub refspec: String,
+    pub plan: Plan,
+}
+
+impl CrossRepo {
+    /// Plan pushing the patch at `path` (the first patch when `path` is empty).
+    pub fn plan_push(&self, path: &str, opts: &PushOptions) -> Result<PushPlan> {
+        let metadata = self.load_metadata()?;
+        let patch = metadata
+            .patches
+            .iter()
+            .find(|p| p.local_path == *path || path.is_empty())
+            .ok_or_else(|| CrossError::PatchNotFound {
+                path: path.to_string(),
+            })?;
+
+        let msg = match &opts.message {
+            Some(m) => m.clone(),
+            None => self
+                .git()
+                .last_subject(self.root(), &patch.local_path)
+                .ok()
+                .flatten()
+                .unwrap_or_else(|| "Update from git-cross".to_string()),
+        };
+
+        if !patch.ref_kind.is_branch() && opts.branch.is_none() {
+            return Err(anyhow!(
+                "{} is pinned to {} {}; pass a branch to push to",
+                patch.local_path,
+                patch.ref_kind.as_str(),
+                patch.ref_name()
+            ));
+        }
+        let target_branch = opts.branch.as_deref().unwrap_or(&patch.branch);
+        let refspec = if target_branch.starts_with("refs/") {
+            format!("HEAD:{}", target_branch)
+        } else {
+            format!("HEAD:refs/heads/{}", target_branch)
+        };
+
+        let mut plan = Plan::new(format!(
+            "push {} -> {} {}",
+            patch.local_path, patch.remote, refspec
+        ));
+        let upstream = format!("{}/{}", patch.worktree, patch.remote_path);
+        if patch.file {
+            if !self.join(&patch.local_path).is_file() {
+                return Err(anyhow!("Local file {} not found", patch.local_path));
+            }
+            plan.push(Op::CopyFile {
+                src: patch.local_path.clone(),
+                dst: upstream,
+            });
+        } else {
+            let local = tree::files(&self.join(&patch.local_path))?;
+            let removes = tree::mirror_removals(&local, &self.join(&upstream), &patch.filter)?
+                .into_iter()
+                .map(|rel| format!("{}/{}", upstream, rel))
+                .collect();
+            plan.push(Op::Mirror {
+                src: patch.local_path.clone(),
+                dst: upstream,
+                delete: true,
+                filter: patch.filter.clone(),
+                removes,
+            });
+        }
+        plan.push(Op::Commit {
+            worktree: patch.worktree.clone(),
+            path: patch.remote_path.clone(),
+            message: msg,
+        });
+        plan.push(Op::Push {
+            worktree: patch.worktree.clone(),
+            remote: patch.remote.clone(),
+            refspec: refspec.clone(),
+            force: opts.force,
+        });
+
+        Ok(PushPlan {
+            patch: patch.clone(),
+            refspec,
+            plan,
+        })
+    }
+
+    /// Commit the local content of a patch in its worktree and push it upstream.
+    pub fn push(&self, path: &str, opts: &PushOptions) -> Result<PushPlan> {
+        let push = self.plan_push(path, opts)?;
+        self.execute(&push.plan)?;
+        Ok(push)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PushOptions;
+    use crate::git::FakePush;
+    use crate::git::testing::{URL, repo};
+    use crate::repo::PatchOptions;
+    use std::fs;
+
+    fn push_opts(branch: Option<&str>) -> PushOptions {
+        PushOptions {
+            branch: branch.map(str::to_string),
+            ..PushOptions::default()
+        }
+    }
+
+    #[test]
+    fn refspec_targets_patch_branch_or_given_ref() {
+        let (_dir, fake, repo) = repo();
+        fake.upstream_commit(URL, "develop", &[("docs/a.md", "a\n")], "Add docs");
+        repo.use_remote("demo", URL).unwrap();
+        repo.add_patch("demo:docs", &PatchOptions::default())
+            .unwrap();
+
+        let refspec = |branch| repo.plan_push("docs", &push_opts(branch)).unwrap().refspec;
+        assert_eq!(refspec(None), "HEAD:refs/heads/develop");
+        assert_eq!(refspec(Some("feature/x")), "HEAD:refs/heads/feature/x");
+        assert_eq!(refspec(Some("refs/for/main")), "HEAD:refs/for/main");
+    }
+
+    #[test]
+    fn pinned_patch_needs_a_branch() {
+        let (_dir, fake, repo) = repo();
+        let commit = fake.upstream_commit(URL, "main", &[("docs/a.md", "a\n")], "Add docs");
+        fake.tag(URL, "v1.0.0", &commit);
+        repo.use_remote("demo", URL).unwrap();
+        repo.add_patch("demo:v1.0.0:docs", &PatchOptions::default())
+            .unwrap();
+
+        let err = repo.plan_push("docs", &push_opts(None)).unwrap_err();
+        assert!(err.to_string().contains("pinned to tag v1.0.0"), "{}", err);
+        let push = repo.plan_push("docs", &push_opts(Some("fix"))).unwrap();
+        assert_eq!(push.refspec, "HEAD:refs/heads/fix");
+    }
+
+    #[test]
+    fn push_commits_local_changes_and_pushes_refspec() {
+        let (_dir, fake, repo) = repo();
+        let base = fake.upstream_commit(URL, "main", &[("docs/a.md", "a\n")], "Add docs");
+        repo.use_remote("demo", URL).unwrap();
+        repo.add_patch("demo:docs", &PatchOptions::default())
+            .unwrap();
+        fs::write(repo.join("docs/a.md"), "a (local)\n").unwrap();
+
+        let opts = PushOptions {
+            message: Some("Fix docs".to_string()),
+            ..push_opts(Some("feature/x"))
+        };
+        repo.push("docs", &opts).unwrap();
+        assert_eq!(
+            fake.pushes(),
+            [FakePush {
+                remote: "demo".to_string(),
+                refspec: "HEAD:refs/heads/feature/x".to_string(),
+                force: false,
+            }]
+        );
+        let pushed = fake.upstream_ref(URL, "refs/heads/feature/x").unwrap();
+        assert_ne!(pushed, base);
+        let git = repo.git();
+        let blobs = git.blobs(repo.root(), &pushed, "docs").unwrap();
+        assert_eq!(
+            git.read_blob(repo.root(), &blobs["a.md"].oid).unwrap(),
+            b"a (local)\n"
+        );
+        assert_eq!(
+            git.log(repo.root(), &base, &pushed, "docs").unwrap()[0].1,
+            "Fix docs"
+        );
+    }
+}
@@ -0,0 +1,249 @@
+//! `repair --from-crossfile`: rebuild local state from the Crossfile.
+//!
+//! A fresh clone has the Crossfile, `Crossfile.lock` and the vendored
+//! directories, but none of `.git/cross`. Repair recreates the remotes,
+//! worktrees and metadata the Crossfile describes, at the locked commits,
+//! without touching vendored files. It then compares every patch with its
+//! upstream content so local edits are known before the next `sync`.
+//!
+//! A patch without a lock entry cannot be placed: the vendored files may come
+//! from any upstream commit. Repair records it without a sync base or lock
+//! entry and reports it as unpinned instead of guessing the current tip,
+//! which would make upstream changes since then look like local edits.
+
+use crate::crossfile::{Crossfile, Statement};
+use crate::diff::FileChange;
+use crate::plan::{Op, Plan};
+use crate::repo::{CrossRepo, PatchOptions, PatchPlan};
+use anyhow::Result;
+use serde::Serialize;
+use std::fmt;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum VendoredState {
+    /// Identical to the upstream content at the recorded commit.
+    Pristine,
+    /// Edited locally.
+    Modified,
+    /// The local path does not exist.
+    Missing,
+    /// No `Crossfile.lock` entry: the upstream commit it was vendored from
+    /// is unknown, so it is not compared.
+    Unpinned,
+}
+
+impl VendoredState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VendoredState::Pristine => "pristine",
+            VendoredState::Modified => "modified",
+            VendoredState::Missing => "missing",
+            VendoredState::Unpinned => "unpinned",
+        }
+    }
+}
+
+impl fmt::Display for VendoredState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+/// A patch recorded by repair and how its vendored copy compares to upstream.
+#[derive(Serialize, Debug, Clone)]
+pub struct RepairedPatch {
+    pub local_path: String,
+    pub spec: String,
+    pub state: VendoredState,
+    /// Files that differ from upstream, empty when pristine.
+    pub files: Vec<FileChange>,
+}
+
+impl CrossRepo {
+    /// Plan recreating what one Crossfile statement records, leaving vendored
+    /// files alone. `None` when there is nothing to do: the remote or patch
+    /// already exists, or the statement is a `sync` or `exec`, which would
+    /// change files. Patches without a lock entry get no sync base.
+    pub fn plan_repair(&self, statement: &Statement) -> Result<Option<Plan>> {
+        match statement {
+            Statement::Use { name, url } => {
+                if self.git().has_remote(self.root(), name) {
+                    return Ok(None);
+                }
+                Ok(Some(self.plan_use(name, url)?.plan))
+            }
+            Statement::Patch {
+                spec,
+                local_path,
+                filter,
+            } => {
+                if let Some(path) = statement.local_path()
+                    && let Some(patch) = self.load_metadata()?.find(&path)
+                    && self.join(&patch.worktree).exists()
+                {
+                    return Ok(None);
+                }
+                let opts = PatchOptions {
+                    local_path: local_path.clone(),
+                    filter: filter.clone(),
+                    keep_local: true,
+                };
+                let PatchPlan { patch, mut plan } = self.plan_patch(spec, &opts)?;
+                let pinned = self
+                    .load_lock()?
+                    .find(&patch.local_path)
+                    .is_some_and(|l| l.matches(&patch.remote, &patch.branch, &patch.remote_path));
+                if !pinned {
+                    // The worktree is at the current tip, which says nothing
+                    // about where the vendored files came from.
+                    plan.ops.retain(|op| !matches!(op, Op::LockPatch(_)));
+                    for op in &mut plan.ops {
+                        if let Op::SavePatch { patch, .. } = op {
+                            patch.base = None;
+                        }
+                    }
+                }
+                Ok(Some(plan))
+            }
+            Statement::Sync { .. } | Statement::Exec { .. } => Ok(None),
+        }
+    }
+
+    /// Compare the vendored copy of every recorded Crossfile patch with its
+    /// upstream content. Patches without metadata are left out.
+    pub fn repair_report(&self, crossfile: &Crossfile) -> Result<Vec<RepairedPatch>> {
+        let metadata = self.load_metadata()?;
+        let mut report = Vec::new();
+        for (_, statement) in crossfile.statements() {
+            let Some(patch) = statement.local_path().and_then(|path| metadata.find(&path)) else {
+                continue;
+            };
+            let (state, files) = if !self.join(&patch.local_path).exists() {
+                (VendoredState::Missing, self.changed_files(patch)?)
+            } else if patch.base.is_none() {
+                (VendoredState::Unpinned, Vec::new())
+            } else {
+                let files = self.changed_files(patch)?;
+                let state = if files.is_empty() {
+                    VendoredState::Pristine
+                } else {
+                    VendoredState::Modified
+                };
+                (state, files)
+            };
+            report.push(RepairedPatch {
+                local_path: patch.local_path.clone(),
+                spec: patch.spec(),
+                state,
+                files,
+            });
+        }
+        Ok(report)
+    }
+
+    /// Recreate remotes, worktrees and metadata from the Crossfile and report
+    /// how each vendored patch compares to upstream. Without a Crossfile
+    /// there is nothing to repair.
+    pub fn repair_from_crossfile(&self) -> Result<Vec<RepairedPatch>> {
+        let Some(crossfile) = self.load_crossfile()? else {
+            return Ok(Vec::new());
+        };
+        for (_, statement) in crossfile.statements() {
+            if let Some(plan) = self.plan_repair(statement)? {
+                self.execute(&plan)?;
+            }
+        }
+        self.repair_report(&crossfile)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VendoredState;
+    use crate::git::testing::{URL, repo};
+    use crate::repo::PatchOptions;
+    use std::fs;
+
+    #[test]
+    fn repair_rebuilds_a_fresh_clone_and_keeps_local_edits() {
+        let (_dir, fake, repo) = repo();
+        fake.upstream_commit(
+            URL,
+            "main",
+            &[("docs/a.md", "a\n"), ("src/x.rs", "x\n")],
+            "Add docs",
+        );
+        repo.use_remote("demo", URL).unwrap();
+        let docs = repo
+            .add_patch("demo:docs", &PatchOptions::default())
+            .unwrap();
+        let src = repo
+            .add_patch("demo:src", &PatchOptions::default())
+            .unwrap();
+        fake.upstream_commit(URL, "main", &[("docs/a.md", "newer\n")], "Update");
+
+        // What a clone brings along: Crossfile, lock and vendored files.
+        for patch in [&docs, &src] {
+            repo.git()
+                .remove_worktree(repo.root(), &repo.join(&patch.worktree))
+                .unwrap();
+        }
+        repo.git().remove_remote(repo.root(), "demo").unwrap();
+        fs::remove_file(repo.metadata_path()).unwrap();
+        fs::write(repo.join("src/x.rs"), "edited\n").unwrap();
+
+        let report = repo.repair_from_crossfile().unwrap();
+        let states: Vec<(&str, VendoredState)> = report
+            .iter()
+            .map(|p| (p.local_path.as_str(), p.state))
+            .collect();
+        assert_eq!(
+            states,
+            [
+                ("docs", VendoredState::Pristine),
+                ("src", VendoredState::Modified)
+            ]
+        );
+        assert_eq!(report[1].files[0].path, "x.rs");
+        assert_eq!(
+            fs::read_to_string(repo.join("src/x.rs")).unwrap(),
+            "edited\n"
+        );
+
+        let metadata = repo.load_metadata().unwrap();
+        assert_eq!(metadata.patches.len(), 2);
+        assert_eq!(metadata.find("docs").unwrap().base, docs.base);
+        assert!(repo.join(&docs.worktree).join("docs/a.md").exists());
+
+        // A second run finds everything in place.
+        let crossfile = repo.load_crossfile().unwrap().unwrap();
+        for (_, statement) in crossfile.statements() {
+            assert!(repo.plan_repair(statement).unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn repair_without_a_lock_entry_records_no_base() {
+        let (_dir, fake, repo) = repo();
+        fake.upstream_commit(URL, "main", &[("docs/a.md", "a\n")], "Add docs");
+        repo.use_remote("demo", URL).unwrap();
+        let docs = repo
+            .add_patch("demo:docs", &PatchOptions::default())
+            .unwrap();
+        fake.upstream_commit(URL, "main", &[("docs/a.md", "newer\n")], "Update");
+
+        repo.git()
+            .remove_worktree(repo.root(), &repo.join(&docs.worktree))
+            .unwrap();
+        fs::remove_file(repo.metadata_path()).unwrap();
+        fs::remove_file(repo.lockfile_path()).unwrap();
+
+        let report = repo.repair_from_crossfile().unwrap();
+        assert_eq!(report[0].state, VendoredState::Unpinned);
+        assert!(report[0].files.is_empty());
+        assert_eq!(repo.find_patch("docs").unwrap().unwrap().base, None);
+        assert!(repo.load_lock().unwrap().find("docs").is_none());
+        assert_eq!(fs::read_to_string(repo.join("docs/a.md")).unwrap(), "a\n");
+    }
+}
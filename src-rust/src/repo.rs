@@ -0,0 +1,1066 @@
+//! [`CrossRepo`]: a git repository together with its git-cross state.
+
+use crate::crossfile::{Crossfile, Statement};
+use crate::error::CrossError;
+use crate::filter::PathFilter;
+use crate::git::{GitBackend, Libgit2Backend};
+use crate::lock::{LockedPatch, Lockfile};
+use crate::metadata::{METADATA_VERSION, Metadata, Patch, RefKind};
+use crate::oplock::OperationLock;
+use crate::plan::{Op, Plan};
+use crate::spec::PatchSpec;
+use crate::tags::is_range;
+use crate::tree::{self, TreeChanges};
+use crate::util::normalize_local_path;
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError, Weak};
+
+pub const METADATA_REL_PATH: &str = ".git/cross/metadata.json";
+pub const CROSSFILE_REL_PATH: &str = "Crossfile";
+pub const LOCKFILE_REL_PATH: &str = "Crossfile.lock";
+pub const WORKTREES_REL_PATH: &str = ".git/cross/worktrees";
+pub const OPERATION_LOCK_REL_PATH: &str = ".git/cross/operation.lock";
+
+/// Worktree directory of the patch `id` of `remote`, relative to the
+/// repository root.
+pub fn worktree_path(remote: &str, id: &str) -> String {
+    format!("{}/{}_{}", WORKTREES_REL_PATH, remote, id)
+}
+
+/// Remotes that `prune` never removes.
+const PROTECTED_REMOTES: [&str; 2] = ["origin", "git-cross"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Remote {
+    pub name: String,
+    pub url: String,
+    /// Default branch detected when the remote was added.
+    pub branch: String,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct RemoteUrls {
+    pub name: String,
+    pub fetch: String,
+    pub push: String,
+}
+
+/// Plan for adding a remote, with the remote it will configure.
+#[derive(Debug, Clone)]
+pub struct UsePlan {
+    pub remote: Remote,
+    pub plan: Plan,
+}
+
+/// Options of `patch` besides the spec.
+#[derive(Debug, Clone, Default)]
+pub struct PatchOptions {
+    /// Defaults to the last component of the remote path.
+    pub local_path: Option<String>,
+    pub filter: PathFilter,
+    /// Leave the local path as it is: only create the worktree and record
+    /// the patch (used by `repair`).
+    pub keep_local: bool,
+}
+
+/// Plan for vendoring one patch, with the patch it will record.
+#[derive(Debug, Clone)]
+pub struct PatchPlan {
+    pub patch: Patch,
+    pub plan: Plan,
+}
+
+/// Plan for dropping every patch of a remote together with the remote.
+#[derive(Debug, Clone)]
+pub struct PrunePlan {
+    pub patches: Vec<Patch>,
+    pub plan: Plan,
+}
+
+#[derive(Debug, Clone)]
+pub struct CrossRepo {
+    root: PathBuf,
+    git: Arc<dyn GitBackend>,
+    /// Operation lock held by this repository (and its clones), if any.
+    operation_lock: Arc<Mutex<Weak<OperationLock>>>,
+}
+
+impl CrossRepo {
+    /// Open the repository containing the current directory.
+    pub fn discover() -> Result<CrossRepo> {
+        CrossRepo::open(".")
+    }
+
+    /// Open the repository containing `path` with the libgit2 backend.
+    pub fn open(path: impl AsRef<Path>) -> Result<CrossRepo> {
+        CrossRepo::open_with(path, Arc::new(Libgit2Backend))
+    }
+
+    /// Open the repository containing `path`, running git operations
+    /// through `git`.
+    pub fn open_with(path: impl AsRef<Path>, git: Arc<dyn GitBackend>) -> Result<CrossRepo> {
+        let root = git.toplevel(path.as_ref())?;
+        Ok(CrossRepo {
+            root,
+            git,
+            operation_lock: Arc::default(),
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Resolve a repository relative path (absolute paths are kept as is).
+    pub fn join(&self, path: &str) -> PathBuf {
+        self.root.join(path)
+    }
+
+    /// The git backend; every call names the repository or worktree it acts on.
+    pub(crate) fn git(&self) -> &dyn GitBackend {
+        self.git.as_ref()
+    }
+
+    /// Raw content of a blob.
+    pub(crate) fn read_blob(&self, oid: &str) -> Result<Vec<u8>> {
+        self.git.read_blob(&self.root, oid)
+    }
+
+    pub fn metadata_path(&self) -> PathBuf {
+        self.join(METADATA_REL_PATH)
+    }
+
+    pub fn crossfile_path(&self) -> PathBuf {
+        self.join(CROSSFILE_REL_PATH)
+    }
+
+    pub fn lockfile_path(&self) -> PathBuf {
+        self.join(LOCKFILE_REL_PATH)
+    }
+
+    /// Lock out other `cross` processes until the returned lock is dropped.
+    /// Executing a plan takes the lock itself; hold it across planning and
+    /// execution so no other process changes the state in between. Taking
+    /// it again while held by this repository returns the same lock.
+    pub fn operation_lock(&self) -> Result<Arc<OperationLock>> {
+        let mut held = self
+            .operation_lock
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(lock) = held.upgrade() {
+            return Ok(lock);
+        }
+        let lock = Arc::new(OperationLock::acquire(&self.join(OPERATION_LOCK_REL_PATH))?);
+        *held = Arc::downgrade(&lock);
+        Ok(lock)
+    }
+
+    /// Load the metadata. An older schema is migrated in memory only; the
+    /// file is rewritten and worktrees are moved by
+    /// [`CrossRepo::plan_migration`].
+    pub fn load_metadata(&self) -> Result<Metadata> {
+        Metadata::load(&self.metadata_path())
+    }
+
+    /// Plan bringing the metadata file up to date: rewrite an older schema
+    /// (see [`Metadata::upgrade`]) and move worktrees to the directory named
+    /// after their patch id, checked out at the same commit. Worktrees only
+    /// mirror upstream commits, so nothing is lost. Empty when the metadata
+    /// is current.
+    pub fn plan_migration(&self) -> Result<Plan> {
+        let mut plan = Plan::new("migrate metadata");
+        if let Some(from) =
+            Metadata::file_version(&self.metadata_path())?.filter(|v| *v < METADATA_VERSION)
+        {
+            plan.push(Op::UpgradeMetadata { from });
+        }
+
+        let mut metadata = self.load_metadata()?;
+        let mut moved = false;
+        for patch in &mut metadata.patches {
+            let target = worktree_path(&patch.remote, &patch.id);
+            if patch.worktree == target {
+                continue;
+            }
+            // Patches of the same spec share a worktree; the first one moves it.
+            let planned = plan
+                .ops
+                .iter()
+                .any(|op| matches!(op, Op::AddWorktree { path, .. } if *path == target));
+            let old = self.join(&patch.worktree);
+            if old.exists() && !planned && !self.join(&target).exists() {
+                let commit = self.git().commit_id(&old, "HEAD")?;
+                plan.push(Op::RemoveWorktree(patch.worktree.clone()));
+                plan.push(Op::AddWorktree {
+                    path: target.clone(),
+                    commit,
+                    sparse: patch.remote_path.clone(),
+                });
+            }
+            patch.worktree = target;
+            moved = true;
+        }
+        if moved {
+            plan.push(Op::SaveMetadata {
+                metadata,
+                change: "name worktrees after their patch ids".to_string(),
+            });
+        }
+        Ok(plan)
+    }
+
+    pub fn save_metadata(&self, metadata: &Metadata) -> Result<()> {
+        metadata.save(&self.metadata_path())
+    }
+
+    pub fn load_lock(&self) -> Result<Lockfile> {
+        Lockfile::load(&self.lockfile_path())
+    }
+
+    /// Parsed Crossfile, or `None` when the repository has none.
+    pub fn load_crossfile(&self) -> Result<Option<Crossfile>> {
+        let path = self.crossfile_path();
+        if !path.exists() {
+            return Ok(None);
+        }
+        Crossfile::load(&path).map(Some)
+    }
+
+    pub fn patches(&self) -> Result<Vec<Patch>> {
+        Ok(self.load_metadata()?.patches)
+    }
+
+    /// Patch whose local path is exactly `local_path`.
+    pub fn find_patch(&self, local_path: &str) -> Result<Option<Patch>> {
+        Ok(self.load_metadata()?.find(local_path).cloned())
+    }
+
+    /// Record a command in the Crossfile unless an equivalent line exists
+    /// (see [`Crossfile::record`]).
+    pub fn update_crossfile(&self, statement: &Statement) -> Result<()> {
+        let mut crossfile = Crossfile::load_for_edit(&self.crossfile_path())?;
+        if crossfile.record(statement) {
+            crossfile.save()?;
+        }
+        Ok(())
+    }
+
+    /// Rewrite the Crossfile line of the patch at `local_path`, if any.
+    pub fn replace_crossfile_patch(&self, local_path: &str, statement: &Statement) -> Result<()> {
+        let mut crossfile = Crossfile::load_for_edit(&self.crossfile_path())?;
+        if crossfile.replace_patch(local_path, statement) {
+            crossfile.save()?;
+        }
+        Ok(())
+    }
+
+    pub fn remove_crossfile_patch(&self, local_path: &str) -> Result<()> {
+        let mut crossfile = Crossfile::load_for_edit(&self.crossfile_path())?;
+        if crossfile.remove_patch(local_path) {
+            crossfile.save()?;
+        }
+        Ok(())
+    }
+
+    /// Execute a plan, returning the files it added, modified or removed.
+    pub fn execute(&self, plan: &Plan) -> Result<TreeChanges> {
+        plan.execute(self)
+    }
+
+    /// Resolve `rev` to a commit and the tree hash of `remote_path` in it.
+    pub(crate) fn resolve_upstream(
+        &self,
+        rev: &str,
+        remote_path: &str,
+    ) -> Result<(String, String)> {
+        let commit = self.git.commit_id(&self.root, rev)?;
+        let tree = self.git.path_id(&self.root, &commit, remote_path)?;
+        Ok((commit, tree))
+    }
+
+    /// Classify a ref written in a patch spec as a branch, tag or commit of
+    /// `remote`. Commits are returned as full SHAs.
+    pub(crate) fn resolve_ref(&self, remote: &str, name: &str) -> Result<(RefKind, String)> {
+        if is_range(name) {
+            return Ok((RefKind::Range, name.to_string()));
+        }
+        let (git, root) = (self.git(), self.root());
+        let refs = git.ls_remote(root, remote)?;
+        let has = |full: String| refs.iter().any(|(_, r)| *r == full);
+        if has(format!("refs/heads/{}", name)) {
+            return Ok((RefKind::Branch, name.to_string()));
+        }
+        if has(format!("refs/tags/{}", name)) {
+            return Ok((RefKind::Tag, name.to_string()));
+        }
+        if name.len() >= 4 && name.len() <= 40 && name.chars().all(|c| c.is_ascii_hexdigit()) {
+            if !git.has_commit(root, name) {
+                // Abbreviated SHAs can only be found in fetched history.
+                git.fetch(root, remote, &[])?;
+                if name.len() == 40 {
+                    let _ = git.fetch(root, remote, &[name.to_string()]);
+                }
+            }
+            if let Ok(commit) = git.commit_id(root, name) {
+                return Ok((RefKind::Commit, commit));
+            }
+        }
+        Err(CrossError::InvalidSpec {
+            spec: format!("{}:{}", remote, name),
+            reason: format!(
+                "'{}' is not a branch, tag, commit or version range of remote {}",
+                name, remote
+            ),
+        }
+        .into())
+    }
+
+    /// Fetch the upstream ref of a patch and return the commit it points to,
+    /// with the tag a version range resolved to.
+    pub(crate) fn fetch_upstream(
+        &self,
+        remote: &str,
+        name: &str,
+        kind: RefKind,
+    ) -> Result<(String, Option<String>)> {
+        let (git, root) = (self.git(), self.root());
+        match kind {
+            RefKind::Branch => {
+                git.fetch_branch(root, remote, name)?;
+                let tip = git.commit_id(root, &format!("refs/remotes/{}/{}", remote, name))?;
+                Ok((tip, None))
+            }
+            RefKind::Tag => Ok((git.fetch_tag(root, remote, name)?, None)),
+            RefKind::Commit => {
+                self.ensure_commit(remote, name)?;
+                Ok((name.to_string(), None))
+            }
+            RefKind::Range => {
+                let tag = self.resolve_range(remote, name)?;
+                Ok((git.fetch_tag(root, remote, &tag.name)?, Some(tag.name)))
+            }
+        }
+    }
+
+    /// Make sure `commit` is in the object database, fetching it if needed.
+    pub(crate) fn ensure_commit(&self, remote: &str, commit: &str) -> Result<()> {
+        if self.git.has_commit(&self.root, commit) {
+            return Ok(());
+        }
+        self.git
+            .fetch(&self.root, remote, &[commit.to_string()])
+            .map_err(|_| anyhow!("Commit {} is not available from {}", commit, remote))
+    }
+
+    /// Make sure the locked commit is available locally and still has the
+    /// recorded content for its remote path.
+    fn ensure_locked_commit(&self, remote: &str, locked: &LockedPatch) -> Result<()> {
+        let (git, root) = (self.git(), self.root());
+        if !git.has_commit(root, &locked.commit) {
+            git.fetch(root, remote, std::slice::from_ref(&locked.commit)).map_err(|_| {
+                anyhow!(
+                    "Locked commit {} for {} is not available from {}. Run 'sync' to update Crossfile.lock.",
+                    locked.commit,
+                    locked.local_path,
+                    remote
+                )
+            })?;
+        }
+        let (_, tree) = self.resolve_upstream(&locked.commit, &locked.remote_path)?;
+        if tree != locked.tree {
+            return Err(anyhow!(
+                "Crossfile.lock mismatch for {}: {}:{} has tree {}, lock expects {}",
+                locked.local_path,
+                locked.commit,
+                locked.remote_path,
+                tree,
+                locked.tree
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn plan_use(&self, name: &str, url: &str) -> Result<UsePlan> {
+        let (git, root) = (self.git(), self.root());
+        let branch = git
+            .default_branch(root, url)
+            .or_else(|_| git.default_branch(root, name))
+            .unwrap_or_else(|_| "main".to_string());
+
+        let mut plan = Plan::new(format!("use {} ({})", name, url));
+        plan.push(Op::SetRemote {
+            name: name.to_string(),
+            url: url.to_string(),
+            exists: git.has_remote(root, name),
+        });
+        plan.push(Op::Fetch {
+            remote: name.to_string(),
+            branch: branch.clone(),
+        });
+        plan.push(Op::AddToCrossfile(Statement::Use {
+            name: name.to_string(),
+            url: url.to_string(),
+        }));
+        Ok(UsePlan {
+            remote: Remote {
+                name: name.to_string(),
+                url: url.to_string(),
+                branch,
+            },
+            plan,
+        })
+    }
+
+    /// Add (or re-point) a remote, fetch its default branch and record it.
+    pub fn use_remote(&self, name: &str, url: &str) -> Result<Remote> {
+        let UsePlan { remote, plan } = self.plan_use(name, url)?;
+        self.execute(&plan)?;
+        Ok(remote)
+    }
+
+    pub fn plan_patch(&self, spec: &str, opts: &PatchOptions) -> Result<PatchPlan> {
+        let mut spec = PatchSpec::parse(spec)?;
+        opts.filter.matcher()?;
+
+        let (git, root) = (self.git(), self.root());
+        if !git.has_remote(root, &spec.remote) {
+            return Err(CrossError::RemoteNotFound {
+                name: spec.remote.clone(),
+            }
+            .into());
+        }
+
+        let mut ref_kind = RefKind::Branch;
+        if let Some(name) = spec.branch.clone().filter(|_| spec.branch_provided) {
+            let (kind, resolved) = self.resolve_ref(&spec.remote, &name)?;
+            ref_kind = kind;
+            spec.branch = Some(resolved);
+        } else {
+            spec.branch = Some(git.default_branch(root, &spec.remote)?);
+        }
+        let branch_name = spec.branch.clone().unwrap_or_else(|| "main".to_string());
+
+        let canonical = spec.to_string();
+        let target_path = opts
+            .local_path
+            .as_deref()
+            .map(normalize_local_path)
+            .unwrap_or_else(|| spec.default_local_path());
+
+        let id = spec.id();
+        let wt_dir = worktree_path(&spec.remote, &id);
+
+        let mut plan = Plan::new(format!("patch {} -> {}", canonical, target_path));
+
+        // Fetch while planning so the exact upstream commit can be locked.
+        let (tip, tag) = self.fetch_upstream(&spec.remote, &branch_name, ref_kind)?;
+        let lock = self.load_lock()?;
+        let locked = lock
+            .find(&target_path)
+            .filter(|l| l.matches(&spec.remote, &branch_name, &spec.remote_path))
+            .cloned();
+        let entry = match locked {
+            Some(locked) => {
+                self.ensure_locked_commit(&spec.remote, &locked)?;
+                locked
+            }
+            None => {
+                let (commit, tree) = self.resolve_upstream(&tip, &spec.remote_path)?;
+                LockedPatch {
+                    local_path: target_path.clone(),
+                    remote: spec.remote.clone(),
+                    branch: branch_name.clone(),
+                    remote_path: spec.remote_path.clone(),
+                    commit,
+                    tree,
+                    tag,
+                }
+            }
+        };
+
+        let file = git.is_file(root, &entry.commit, &spec.remote_path)?;
+        if file {
+            if !opts.filter.is_empty() {
+                return Err(anyhow!(
+                    "--include/--exclude only apply to directory patches; {} is a file",
+                    spec.remote_path
+                ));
+            }
+            if self.join(&target_path).is_dir() {
+                return Err(anyhow!(
+                    "{} is a file but {} is a directory; give the target file path (e.g. {}/{})",
+                    spec.remote_path,
+                    target_path,
+                    target_path,
+                    spec.default_local_path()
+                ));
+            }
+        }
+
+        // Only the remote path is checked out in the worktree.
+        if !self.join(&wt_dir).exists() {
+            plan.push(Op::AddWorktree {
+                path: wt_dir.clone(),
+                commit: entry.commit.clone(),
+                sparse: spec.remote_path.clone(),
+            });
+        } else {
+            plan.push(Op::Checkout {
+                worktree: wt_dir.clone(),
+                commit: entry.commit.clone(),
+                sparse: spec.remote_path.clone(),
+                force: false,
+            });
+        }
+
+        if opts.keep_local {
+            // Recorded as is; `repair` compares it with upstream afterwards.
+        } else if file {
+            plan.push(Op::CopyFile {
+                src: format!("{}/{}", wt_dir, spec.remote_path),
+                dst: target_path.clone(),
+            });
+        } else {
+            let upstream: BTreeSet<String> = git
+                .blobs(root, &entry.commit, &spec.remote_path)?
+                .into_keys()
+                .collect();
+            let removes = tree::mirror_removals(&upstream, &self.join(&target_path), &opts.filter)?
+                .into_iter()
+                .map(|rel| format!("{}/{}", target_path, rel))
+                .collect();
+            plan.push(Op::CreateDir(target_path.clone()));
+            plan.push(Op::Mirror {
+                src: format!("{}/{}", wt_dir, spec.remote_path),
+                dst: target_path.clone(),
+                delete: true,
+                filter: opts.filter.clone(),
+                removes,
+            });
+        }
+
+        let patch = Patch {
+            id,
+            remote: spec.remote.clone(),
+            remote_path: spec.remote_path.clone(),
+            local_path: target_path.clone(),
+            worktree: wt_dir,
+            branch: branch_name,
+            ref_kind,
+            tag: entry.tag.clone(),
+            file,
+            filter: opts.filter.clone(),
+            base: Some(entry.commit.clone()),
+            conflicts: Vec::new(),
+        };
+        plan.push(Op::SavePatch {
+            patch: patch.clone(),
+            change: format!("record patch {}", target_path),
+        });
+        plan.push(Op::LockPatch(entry));
+
+        plan.push(Op::AddToCrossfile(patch.statement()));
+        Ok(PatchPlan { patch, plan })
+    }
+
+    /// Vendor `spec` into `local_path` and record it in metadata, lock and Crossfile.
+    pub fn add_patch(&self, spec: &str, opts: &PatchOptions) -> Result<Patch> {
+        let PatchPlan { patch, plan } = self.plan_patch(spec, opts)?;
+        self.execute(&plan)?;
+        Ok(patch)
+    }
+
+    /// Ops that delete one patch: worktree, Crossfile and lock entries and the
+    /// local directory (or file). The worktree stays while a patch left in
+    /// `remaining` uses it, and is removed once when several removed patches do.
+    fn plan_patch_removal(
+        &self,
+        plan: &mut Plan,
+        patch: &Patch,
+        remaining: &Metadata,
+    ) -> Result<()> {
+        let planned = plan
+            .ops
+            .iter()
+            .any(|op| matches!(op, Op::RemoveWorktree(w) if *w == patch.worktree));
+        if self.join(&patch.worktree).exists()
+            && !planned
+            && !remaining.shares_worktree(&patch.worktree, &patch.local_path)
+        {
+            plan.push(Op::RemoveWorktree(patch.worktree.clone()));
+        }
+        plan.push(Op::RemoveFromCrossfile {
+            local_path: patch.local_path.clone(),
+        });
+        plan.push(Op::UnlockPatch {
+            local_path: patch.local_path.clone(),
+        });
+        let local = self.join(&patch.local_path);
+        if local.is_dir() {
+            let removes = tree::files(&local)?
+                .into_iter()
+                .map(|rel| format!("{}/{}", patch.local_path, rel))
+                .collect();
+            plan.push(Op::RemoveDir {
+                path: patch.local_path.clone(),
+                removes,
+            });
+        } else if local.exists() {
+            plan.push(Op::RemoveFile(patch.local_path.clone()));
+        }
+        Ok(())
+    }
+
+    pub fn plan_remove(&self, path: &str) -> Result<PatchPlan> {
+        let path = normalize_local_path(path);
+        let mut metadata = self.load_metadata()?;
+        let patch = metadata
+            .find(&path)
+            .cloned()
+            .ok_or_else(|| CrossError::PatchNotFound { path: path.clone() })?;
+        metadata.patches.retain(|p| p != &patch);
+
+        let mut plan = Plan::new(format!("remove {}", path));
+        self.plan_patch_removal(&mut plan, &patch, &metadata)?;
+        plan.push(Op::SaveMetadata {
+            metadata,
+            change: format!("forget patch {}", path),
+        });
+        Ok(PatchPlan { patch, plan })
+    }
+
+    /// Plan moving the patch at `from` to the local path `to`. The worktree
+    /// and sync state stay; metadata, lock and Crossfile follow the move.
+    pub fn plan_move(&self, from: &str, to: &str) -> Result<PatchPlan> {
+        let (from, to) = (normalize_local_path(from), normalize_local_path(to));
+        let mut metadata = self.load_metadata()?;
+        let old = metadata
+            .find(&from)
+            .cloned()
+            .ok_or_else(|| CrossError::PatchNotFound { path: from.clone() })?;
+        let from = old.local_path.clone();
+        if to.is_empty() {
+            return Err(anyhow!("Cannot move {} to the repository root", from));
+        }
+        if to == from {
+            return Err(anyhow!("{} is already at {}", from, to));
+        }
+        let overlaps = |a: &str, b: &str| a == b || a.starts_with(&format!("{}/", b));
+        if overlaps(&to, &from) {
+            return Err(anyhow!("Cannot move {} into itself ({})", from, to));
+        }
+        if let Some(other) = metadata
+            .patches
+            .iter()
+            .filter(|p| p.local_path != from)
+            .find(|p| overlaps(&to, &p.local_path) || overlaps(&p.local_path, &to))
+        {
+            return Err(if other.local_path == to {
+                anyhow!("{} is the local path of another patch", to)
+            } else {
+                anyhow!("{} would nest with patch {}", to, other.local_path)
+            });
+        }
+        if fs::symlink_metadata(self.join(&to)).is_ok() {
+            return Err(anyhow!("{} already exists", to));
+        }
+
+        let mut patch = old.clone();
+        patch.local_path = to.clone();
+        for file in &mut patch.conflicts {
+            if let Some(rest) = file.strip_prefix(&from) {
+                *file = format!("{}{}", to, rest);
+            }
+        }
+        metadata.patches.retain(|p| p.local_path != from);
+        metadata.upsert(patch.clone());
+
+        let mut plan = Plan::new(format!("move {} -> {}", from, to));
+        plan.push(Op::MovePath {
+            src: from.clone(),
+            dst: to.clone(),
+        });
+        plan.push(Op::SaveMetadata {
+            metadata,
+            change: format!("move patch {} to {}", from, to),
+        });
+        if let Some(locked) = self.load_lock()?.find(&from) {
+            let mut entry = locked.clone();
+            entry.local_path = to.clone();
+            plan.push(Op::UnlockPatch {
+                local_path: from.clone(),
+            });
+            plan.push(Op::LockPatch(entry));
+        }
+        // Keep the line as written (e.g. without a ref) with the new path.
+        if let Some(Statement::Patch { spec, filter, .. }) =
+            Crossfile::load_for_edit(&self.crossfile_path())?.find_patch(&from)
+        {
+            plan.push(Op::ReplaceInCrossfile {
+                local_path: from,
+                statement: Statement::Patch {
+                    spec: spec.clone(),
+                    local_path: Some(to),
+                    filter: filter.clone(),
+                },
+            });
+        }
+        Ok(PatchPlan { patch, plan })
+    }
+
+    /// Remove a patch, its worktree and its local directory.
+    pub fn remove_patch(&self, path: &str) -> Result<Patch> {
+        let PatchPlan { patch, plan } = self.plan_remove(path)?;
+        self.execute(&plan)?;
+        Ok(patch)
+    }
+
+    pub fn remote_names(&self) -> Result<Vec<String>> {
+        self.git.remote_names(&self.root)
+    }
+
+    /// Fetch and push URLs of the remotes used by patches.
+    pub fn patch_remotes(&self) -> Result<Vec<RemoteUrls>> {
+        let used: HashSet<String> = self.patches()?.into_iter().map(|p| p.remote).collect();
+        if used.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut urls = Vec::new();
+        for name in self.remote_names()? {
+            if used.contains(&name) {
+                let (fetch, push) = self.git.remote_urls(&self.root, &name)?;
+                urls.push(RemoteUrls { name, fetch, push });
+            }
+        }
+        urls.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(urls)
+    }
+
+    /// Plan removing all patches of `remote` and then the remote itself.
+    pub fn plan_prune_remote(&self, remote: &str) -> Result<PrunePlan> {
+        let mut metadata = self.load_metadata()?;
+        let (removed, kept): (Vec<Patch>, Vec<Patch>) = metadata
+            .patches
+            .into_iter()
+            .partition(|p| p.remote == remote);
+        metadata.patches = kept;
+
+        let mut plan = Plan::new(format!("prune {}", remote));
+        if !removed.is_empty() {
+            for patch in &removed {
+                self.plan_patch_removal(&mut plan, patch, &metadata)?;
+            }
+            let paths: Vec<&str> = removed.iter().map(|p| p.local_path.as_str()).collect();
+            plan.push(Op::SaveMetadata {
+                metadata,
+                change: format!("forget patches {}", paths.join(", ")),
+            });
+        }
+
+        if self.remote_names()?.iter().any(|r| r == remote) {
+            plan.push(Op::RemoveRemote(remote.to_string()));
+        }
+        Ok(PrunePlan {
+            patches: removed,
+            plan,
+        })
+    }
+
+    /// Remove all patches of `remote` and the remote itself.
+    pub fn prune_remote(&self, remote: &str) -> Result<Vec<Patch>> {
+        let PrunePlan { patches, plan } = self.plan_prune_remote(remote)?;
+        self.execute(&plan)?;
+        Ok(patches)
+    }
+
+    /// Remotes other than `origin`/`git-cross` that no patch uses.
+    pub fn unused_remotes(&self) -> Result<Vec<String>> {
+        let used: HashSet<String> = self.patches()?.into_iter().map(|p| p.remote).collect();
+        Ok(self
+            .remote_names()?
+            .into_iter()
+            .filter(|r| !PROTECTED_REMOTES.contains(&r.as_str()) && !used.contains(r))
+            .collect())
+    }
+
+    pub fn plan_remove_remotes(&self, remotes: &[String]) -> Plan {
+        let mut plan = Plan::new("prune unused remotes");
+        for remote in remotes {
+            plan.push(Op::RemoveRemote(remote.clone()));
+        }
+        plan
+    }
+
+    pub fn plan_prune_worktrees(&self) -> Plan {
+        let mut plan = Plan::new("prune stale worktrees");
+        plan.push(Op::PruneWorktrees);
+        plan
+    }
+
+    /// Plans for one Crossfile statement, in execution order.
+    pub fn plan_statement(&self, statement: &Statement) -> Result<Vec<Plan>> {
+        Ok(match statement {
+            Statement::Use { name, url } => vec![self.plan_use(name, url)?.plan],
+            Statement::Patch {
+                spec,
+                local_path,
+                filter,
+            } => {
+                let opts = PatchOptions {
+                    local_path: local_path.clone(),
+                    filter: filter.clone(),
+                    ..PatchOptions::default()
+                };
+                vec![self.plan_patch(spec, &opts)?.plan]
+            }
+            Statement::Sync { path } => self
+                .plan_sync(path.as_deref())?
+                .into_iter()
+                .map(|s| s.plan)
+                .collect(),
+            Statement::Exec { args } => vec![self.plan_exec(args)],
+        })
+    }
+
+    pub fn plan_exec(&self, args: &[String]) -> Plan {
+        let mut plan = Plan::new("exec");
+        plan.push(Op::Exec(args.join(" ")));
+        plan
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PatchOptions;
+    use crate::filter::PathFilter;
+    use crate::git::testing::{URL, repo};
+    use crate::metadata::{METADATA_VERSION, Metadata};
+    use crate::plan::Op;
+    use serde_json::Value;
+    use std::fs;
+
+    const OTHER: &str = "https://example.com/other.git";
+
+    #[test]
+    fn patch_plan_lists_the_files_mirror_deletes() {
+        let (_dir, fake, repo) = repo();
+        fake.upstream_commit(URL, "main", &[("docs/a.md", "a\n")], "Add docs");
+        repo.use_remote("demo", URL).unwrap();
+        fs::create_dir_all(repo.join("docs")).unwrap();
+        for (path, content) in [("a.md", "old\n"), ("stale.md", "x\n"), ("keep.txt", "k\n")] {
+            fs::write(repo.join("docs").join(path), content).unwrap();
+        }
+        let opts = PatchOptions {
+            filter: PathFilter {
+                include: vec!["*.md".to_string()],
+                exclude: Vec::new(),
+            },
+            ..PatchOptions::default()
+        };
+
+        let plan = repo.plan_patch("demo:docs", &opts).unwrap().plan;
+        let listed = plan.to_string();
+        assert!(
+            listed.contains("\n    delete docs/stale.md\n"),
+            "{}",
+            listed
+        );
+        assert!(!listed.contains("keep.txt"), "{}", listed);
+        let changes = repo.execute(&plan).unwrap();
+        assert_eq!(changes.removed, ["docs/stale.md"]);
+        assert!(repo.join("docs/keep.txt").exists());
+    }
+
+    #[test]
+    fn move_keeps_the_worktree_and_refuses_overlapping_paths() {
+        let (_dir, fake, repo) = repo();
+        fake.upstream_commit(
+            URL,
+            "main",
+            &[("docs/a.md", "a\n"), ("lib/l.rs", "l\n")],
+            "Add docs",
+        );
+        repo.use_remote("demo", URL).unwrap();
+        let opts = |path: &str| PatchOptions {
+            local_path: Some(path.to_string()),
+            ..PatchOptions::default()
+        };
+        let docs = repo.add_patch("demo:docs", &opts("vendor/docs")).unwrap();
+        repo.add_patch("demo:lib", &opts("vendor/lib")).unwrap();
+
+        for (to, error) in [
+            ("vendor/lib", "is the local path of another patch"),
+            ("vendor/lib/docs", "would nest with patch vendor/lib"),
+            ("vendor", "would nest with patch vendor/lib"),
+            ("vendor/docs/sub", "into itself"),
+        ] {
+            let err = repo.plan_move("vendor/docs", to).unwrap_err();
+            assert!(err.to_string().contains(error), "{}: {}", to, err);
+        }
+
+        let plan = repo.plan_move("vendor/docs", "third_party/docs").unwrap();
+        repo.execute(&plan.plan).unwrap();
+        let moved = repo.find_patch("third_party/docs").unwrap().unwrap();
+        assert_eq!(moved.worktree, docs.worktree);
+        assert_eq!(moved.base, docs.base);
+        assert!(repo.join("third_party/docs/a.md").exists());
+        assert!(!repo.join("vendor/docs").exists());
+        assert!(repo.find_patch("vendor/docs").unwrap().is_none());
+        assert!(repo.load_lock().unwrap().find("third_party/docs").is_some());
+        let crossfile = fs::read_to_string(repo.crossfile_path()).unwrap();
+        assert!(crossfile.contains("third_party/docs"));
+        assert!(!crossfile.contains("vendor/docs"));
+        assert!(repo.changed_files(&moved).unwrap().is_empty());
+    }
+
+    #[test]
+    fn prune_removes_patches_worktrees_and_remote() {
+        let (_dir, fake, repo) = repo();
+        fake.upstream_commit(URL, "main", &[("docs/a.md", "a\n")], "Add docs");
+        fake.upstream_commit(OTHER, "master", &[("lib/x.rs", "x\n")], "Add lib");
+        repo.use_remote("demo", URL).unwrap();
+        repo.use_remote("other", OTHER).unwrap();
+        let opts = |path: &str| PatchOptions {
+            local_path: Some(path.to_string()),
+            ..PatchOptions::default()
+        };
+        repo.add_patch("demo:docs", &opts("vendor/docs")).unwrap();
+        repo.add_patch("other:lib", &opts("vendor/lib")).unwrap();
+        assert_eq!(fake.worktrees().len(), 2);
+
+        let pruned = repo.prune_remote("demo").unwrap();
+        assert_eq!(pruned.len(), 1);
+        assert_eq!(pruned[0].local_path, "vendor/docs");
+        assert_eq!(repo.remote_names().unwrap(), ["other"]);
+        assert!(!repo.join("vendor/docs").exists());
+        assert!(repo.join("vendor/lib/x.rs").exists());
+        let patches = repo.patches().unwrap();
+        assert_eq!(patches.len(), 1);
+        assert_eq!(fake.worktrees(), [repo.join(&patches[0].worktree)]);
+        assert!(repo.load_lock().unwrap().find("vendor/docs").is_none());
+        let crossfile = fs::read_to_string(repo.crossfile_path()).unwrap();
+        assert!(!crossfile.contains("vendor/docs"), "{}", crossfile);
+        assert!(crossfile.contains("vendor/lib"), "{}", crossfile);
+    }
+
+    #[test]
+    fn remove_keeps_a_worktree_another_patch_uses() {
+        let (_dir, fake, repo) = repo();
+        fake.upstream_commit(URL, "main", &[("docs/a.md", "a\n")], "Add docs");
+        repo.use_remote("demo", URL).unwrap();
+        let opts = |path: &str| PatchOptions {
+            local_path: Some(path.to_string()),
+            ..PatchOptions::default()
+        };
+        let first = repo.add_patch("demo:docs", &opts("vendor/a")).unwrap();
+        let second = repo.add_patch("demo:docs", &opts("vendor/b")).unwrap();
+        assert_eq!(first.worktree, second.worktree);
+
+        // The local files are listed up front and reported as deleted.
+        fs::write(repo.join("vendor/a/notes.md"), "mine\n").unwrap();
+        let remove = repo.plan_remove("vendor/a").unwrap();
+        let shown = remove.plan.to_string();
+        assert!(shown.contains("delete vendor/a/a.md"), "{}", shown);
+        assert!(shown.contains("delete vendor/a/notes.md"), "{}", shown);
+        let changes = repo.execute(&remove.plan).unwrap();
+        assert_eq!(changes.removed, ["vendor/a/a.md", "vendor/a/notes.md"]);
+        assert!(!repo.join("vendor/a").exists());
+        assert!(repo.join(&second.worktree).join("docs/a.md").exists());
+        assert_eq!(fake.worktrees(), [repo.join(&second.worktree)]);
+
+        // The last patch using it takes the worktree along, once.
+        repo.add_patch("demo:docs", &opts("vendor/a")).unwrap();
+        let prune = repo.plan_prune_remote("demo").unwrap();
+        let removals = prune
+            .plan
+            .ops
+            .iter()
+            .filter(|op| matches!(op, Op::RemoveWorktree(_)))
+            .count();
+        assert_eq!(removals, 1);
+        repo.execute(&prune.plan).unwrap();
+        assert!(fake.worktrees().is_empty());
+    }
+
+    #[test]
+    fn prune_of_unknown_remote_plans_nothing() {
+        let (_dir, _fake, repo) = repo();
+        let prune = repo.plan_prune_remote("missing").unwrap();
+        assert!(prune.patches.is_empty());
+        assert!(prune.plan.is_empty());
+    }
+
+    #[test]
+    fn prune_worktrees_forgets_deleted_worktrees() {
+        let (_dir, fake, repo) = repo();
+        fake.upstream_commit(URL, "main", &[("docs/a.md", "a\n")], "Add docs");
+        repo.use_remote("demo", URL).unwrap();
+        let patch = repo
+            .add_patch("demo:docs", &PatchOptions::default())
+            .unwrap();
+        fs::remove_dir_all(repo.join(&patch.worktree)).unwrap();
+
+        repo.execute(&repo.plan_prune_worktrees()).unwrap();
+        assert!(fake.worktrees().is_empty());
+    }
+
+    #[test]
+    fn migration_moves_worktrees_named_after_old_ids() {
+        let (_dir, fake, repo) = repo();
+        fake.upstream_commit(URL, "main", &[("docs/a.md", "a\n")], "Add docs");
+        repo.use_remote("demo", URL).unwrap();
+        let patch = repo
+            .add_patch("demo:docs", &PatchOptions::default())
+            .unwrap();
+
+        // A version 1 patch whose worktree is named after its old id.
+        let old = ".git/cross/worktrees/demo_1a2b3c4d";
+        let git = repo.git();
+        let head = git.commit_id(&repo.join(&patch.worktree), "HEAD").unwrap();
+        git.remove_worktree(repo.root(), &repo.join(&patch.worktree))
+            .unwrap();
+        git.add_worktree(repo.root(), &repo.join(old), &head, "docs")
+            .unwrap();
+        let mut json: Value =
+            serde_json::from_str(&fs::read_to_string(repo.metadata_path()).unwrap()).unwrap();
+        json["version"] = 1.into();
+        json["patches"][0]["id"] = "1a2b3c4d".into();
+        json["patches"][0]["worktree"] = old.into();
+        fs::write(repo.metadata_path(), json.to_string()).unwrap();
+        let written = fs::read_to_string(repo.metadata_path()).unwrap();
+
+        // Loading migrates in memory only.
+        let patches = repo.patches().unwrap();
+        assert_eq!(patches[0].id, patch.id);
+        assert_eq!(patches[0].worktree, old);
+        assert_eq!(fs::read_to_string(repo.metadata_path()).unwrap(), written);
+        assert_eq!(fake.worktrees(), [repo.join(old)]);
+
+        let migration = repo.plan_migration().unwrap();
+        let shown = migration.to_string();
+        assert!(shown.contains("upgrade from version 1"), "{}", shown);
+        assert!(shown.contains(old), "{}", shown);
+        assert!(!repo.join(".git/cross/metadata.json.v1.bak").exists());
+        repo.execute(&migration).unwrap();
+
+        let patches = repo.patches().unwrap();
+        assert_eq!(patches.len(), 1);
+        assert_eq!(patches[0], patch);
+        assert_eq!(fake.worktrees(), [repo.join(&patch.worktree)]);
+        assert_eq!(
+            git.commit_id(&repo.join(&patch.worktree), "HEAD").unwrap(),
+            head
+        );
+        assert!(repo.join(".git/cross/metadata.json.v1.bak").exists());
+        assert_eq!(
+            Metadata::file_version(&repo.metadata_path()).unwrap(),
+            Some(METADATA_VERSION)
+        );
+        assert!(repo.plan_migration().unwrap().is_empty());
+    }
+}
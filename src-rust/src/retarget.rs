@@ -0,0 +1,188 @@
+//! `retarget`: point a patch at another remote, branch or upstream path.
+//!
+//! Removing a patch and adding it again from the new source deletes the local
+//! directory and every local edit with it. Retarget keeps the local path and
+//! merges like `sync` does, except that the two upstream sides differ in
+//! where they come from: the base is what the patch was last synced from
+//! (old remote path at `Patch::base`) and "theirs" is the new source. Files
+//! unchanged locally take the new upstream content, local edits are carried
+//! over, and edits the new source also touches are merged with conflict
+//! markers where needed.
+
+use crate::crossfile::{Crossfile, Statement};
+use crate::error::CrossError;
+use crate::plan::{Op, Plan};
+use crate::repo::{CrossRepo, PatchOptions, PatchPlan};
+use crate::sync::SyncPlan;
+use crate::util::normalize_local_path;
+use anyhow::{Result, anyhow};
+
+impl CrossRepo {
+    /// Plan switching the patch at `path` to `spec`, keeping its local path,
+    /// filter and local edits. The returned plan's `upstream` is the commit
+    /// of the new source; `conflicts` the files left conflicted.
+    pub fn plan_retarget(&self, path: &str, spec: &str) -> Result<SyncPlan> {
+        let path = normalize_local_path(path);
+        let metadata = self.load_metadata()?;
+        let old = metadata
+            .find(&path)
+            .cloned()
+            .ok_or_else(|| CrossError::PatchNotFound { path: path.clone() })?;
+
+        let opts = PatchOptions {
+            local_path: Some(old.local_path.clone()),
+            filter: old.filter.clone(),
+            keep_local: true,
+        };
+        let PatchPlan {
+            patch: mut new,
+            plan: patch_plan,
+        } = self.plan_patch(spec, &opts)?;
+        if new.id == old.id {
+            return Err(anyhow!(
+                "{} already tracks {}; use 'cross sync' to update it",
+                old.local_path,
+                old.spec()
+            ));
+        }
+        if new.file != old.file {
+            let kind = |file: bool| if file { "a file" } else { "a directory" };
+            return Err(anyhow!(
+                "{} is {} but {} is {}",
+                old.local_path,
+                kind(old.file),
+                new.remote_path,
+                kind(new.file)
+            ));
+        }
+
+        // Patches recorded before the base was tracked fall back to the
+        // commit their worktree is at.
+        let old_worktree = self.join(&old.worktree);
+        let base = match &old.base {
+            Some(base) => base.clone(),
+            None if old_worktree.exists() => self.git().commit_id(&old_worktree, "HEAD")?,
+            None => {
+                return Err(CrossError::WorktreeMissing {
+                    path: old.local_path.clone(),
+                }
+                .into());
+            }
+        };
+        self.ensure_commit(&old.remote, &base)?;
+        let upstream = new.base.clone().unwrap_or_default();
+
+        let mut plan = Plan::new(format!("retarget {} -> {}", old.local_path, new.spec()));
+        // Another patch of the same source keeps using the old worktree.
+        if old_worktree.exists() && !metadata.shares_worktree(&old.worktree, &old.local_path) {
+            plan.push(Op::RemoveWorktree(old.worktree.clone()));
+        }
+        // The new worktree, without the records: those follow the merge.
+        let mut lock = None;
+        for op in patch_plan.ops {
+            match op {
+                Op::SavePatch { .. } | Op::AddToCrossfile(_) => {}
+                Op::LockPatch(entry) => lock = Some(entry),
+                op => plan.push(op),
+            }
+        }
+
+        let conflicts = self.plan_merge(
+            &mut plan,
+            &old,
+            (&base, &old.remote_path),
+            (&upstream, &new.remote_path),
+        )?;
+        new.conflicts = conflicts.clone();
+        plan.push(Op::SavePatch {
+            patch: new.clone(),
+            change: format!("retarget patch {} to {}", new.local_path, new.spec()),
+        });
+        if let Some(entry) = lock {
+            plan.push(Op::LockPatch(entry));
+        }
+
+        // Replace the existing line, keeping its prefix and comment; the
+        // new spec is recorded as given (e.g. without a ref).
+        if let Some(Statement::Patch { filter, .. }) =
+            Crossfile::load_for_edit(&self.crossfile_path())?.find_patch(&old.local_path)
+        {
+            plan.push(Op::ReplaceInCrossfile {
+                local_path: old.local_path.clone(),
+                statement: Statement::Patch {
+                    spec: spec.trim().to_string(),
+                    local_path: Some(old.local_path.clone()),
+                    filter: filter.clone(),
+                },
+            });
+        } else {
+            plan.push(Op::AddToCrossfile(new.statement()));
+        }
+
+        Ok(SyncPlan {
+            patch: new,
+            plan,
+            upstream,
+            conflicts,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::git::testing::{URL, repo};
+    use crate::repo::PatchOptions;
+    use crate::sync::SyncOutcome;
+    use std::fs;
+
+    #[test]
+    fn retarget_follows_a_moved_directory_and_keeps_local_edits() {
+        let (_dir, fake, repo) = repo();
+        fake.upstream_commit(
+            URL,
+            "main",
+            &[("docs/a.md", "a\n"), ("docs/b.md", "b1\nb2\nb3\n")],
+            "Add docs",
+        );
+        repo.use_remote("demo", URL).unwrap();
+        let old = repo
+            .add_patch("demo:docs", &PatchOptions::default())
+            .unwrap();
+        fs::write(repo.join("docs/b.md"), "local\nb2\nb3\n").unwrap();
+        fs::write(repo.join("docs/notes.md"), "mine\n").unwrap();
+
+        // Upstream moves the directory and edits a.md on the way.
+        fake.upstream_commit(
+            URL,
+            "main",
+            &[("manual/a.md", "a2\n"), ("manual/b.md", "b1\nb2\nb3\n")],
+            "Move docs to manual",
+        );
+        let sync = repo.plan_retarget("docs", "demo:main:manual").unwrap();
+        assert!(sync.conflicts.is_empty());
+        assert_eq!(sync.patch.remote_path, "manual");
+
+        let result = repo.execute_sync(sync);
+        assert!(matches!(result.outcome, SyncOutcome::Synced { .. }));
+        let read = |p: &str| fs::read_to_string(repo.join(p)).unwrap();
+        assert_eq!(read("docs/a.md"), "a2\n");
+        assert_eq!(read("docs/b.md"), "local\nb2\nb3\n");
+        assert_eq!(read("docs/notes.md"), "mine\n");
+
+        let metadata = repo.load_metadata().unwrap();
+        let patch = metadata.find("docs").unwrap();
+        assert_eq!(patch.spec(), "demo:main:manual");
+        assert!(!repo.join(&old.worktree).exists());
+        assert!(repo.join(&patch.worktree).join("manual/a.md").exists());
+        assert_eq!(
+            repo.load_lock().unwrap().find("docs").unwrap().remote_path,
+            "manual"
+        );
+        let crossfile = fs::read_to_string(repo.crossfile_path()).unwrap();
+        assert!(crossfile.contains("patch demo:main:manual docs"));
+
+        // Nothing left to switch.
+        let err = repo.plan_retarget("docs", "demo:main:manual").unwrap_err();
+        assert!(err.to_string().contains("already tracks"));
+    }
+}
@@ -0,0 +1,111 @@
+//! Patch specs: `remote[:ref]:path`, where `ref` is a branch, tag or commit.
+
+use crate::error::CrossError;
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatchSpec {
+    pub remote: String,
+    pub remote_path: String,
+    /// Branch, tag or commit SHA.
+    pub branch: Option<String>,
+    /// Whether the ref was written in the spec (as opposed to autodetected).
+    pub branch_provided: bool,
+}
+
+impl PatchSpec {
+    pub fn parse(spec: &str) -> Result<PatchSpec> {
+        let parts: Vec<&str> = spec.split(':').collect();
+        if parts.len() < 2 {
+            return Err(CrossError::InvalidSpec {
+                spec: spec.to_string(),
+                reason: "use remote[:ref]:remote_path".to_string(),
+            }
+            .into());
+        }
+
+        let remote = parts[0].to_string();
+        let mut remote_path = parts.last().unwrap().to_string();
+        let mut branch: Option<String> = None;
+        let mut branch_provided = false;
+
+        match parts.len() {
+            2 => {}
+            3 => {
+                if !parts[1].is_empty() {
+                    branch = Some(parts[1].to_string());
+                    branch_provided = true;
+                }
+            }
+            _ => {
+                if !parts[1].is_empty() {
+                    branch = Some(parts[1].to_string());
+                    branch_provided = true;
+                }
+                remote_path = parts[2..].join(":");
+            }
+        }
+
+        let remote_path = remote_path
+            .trim_start_matches('/')
+            .trim_end_matches('/')
+            .to_string();
+        if remote_path.is_empty() {
+            return Err(CrossError::InvalidSpec {
+                spec: spec.to_string(),
+                reason: "the remote path is empty".to_string(),
+            }
+            .into());
+        }
+
+        Ok(PatchSpec {
+            remote,
+            remote_path,
+            branch,
+            branch_provided,
+        })
+    }
+
+    /// Stable id of the patch: the first 16 hex digits of the SHA-256 of the
+    /// canonical spec. Worktree directories are named after it, so it must
+    /// not change between releases.
+    pub fn id(&self) -> String {
+        let digest = Sha256::digest(self.to_string().as_bytes());
+        digest[..8].iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// Local path used when none is given: the last component of the remote path.
+    pub fn default_local_path(&self) -> String {
+        Path::new(&self.remote_path)
+            .file_name()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| self.remote_path.clone())
+    }
+}
+
+/// Canonical `remote[:ref]:path` form.
+impl fmt::Display for PatchSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(branch) = &self.branch {
+            write!(f, "{}:{}:{}", self.remote, branch, self.remote_path)
+        } else {
+            write!(f, "{}:{}", self.remote, self.remote_path)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PatchSpec;
+
+    #[test]
+    fn id_is_sha256_of_the_canonical_spec() {
+        // Worktree directories are named after ids: this value must never change.
+        let spec = PatchSpec::parse("demo:main:/docs/").unwrap();
+        assert_eq!(spec.id(), "4d7d55d39205ad36");
+        assert_ne!(PatchSpec::parse("demo:dev:docs").unwrap().id(), spec.id());
+    }
+}
@@ -0,0 +1,213 @@
+//! `status`: how each vendored patch relates to its worktree and upstream.
+
+use crate::metadata::{Patch, RefKind};
+use crate::repo::CrossRepo;
+use crate::sync::has_conflict_markers;
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+
+/// Local content compared to the worktree copy of the remote path.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffState {
+    Clean,
+    Modified,
+    /// No worktree to compare against.
+    MissingWorktree,
+}
+
+impl DiffState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DiffState::Clean => "clean",
+            DiffState::Modified => "modified",
+            DiffState::MissingWorktree => "missing_worktree",
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct PatchStatus {
+    pub patch: Patch,
+    pub worktree_present: bool,
+    pub diff: DiffState,
+    /// Upstream commits not yet in the worktree.
+    pub behind: u32,
+    /// Worktree commits not yet upstream.
+    pub ahead: u32,
+    /// Newest tag of the remote, reported for patches pinned to a tag or commit.
+    pub latest_tag: Option<String>,
+    /// Unmerged entries in the worktree (failed rebase).
+    pub worktree_conflicts: bool,
+    /// Conflict markers left by `sync`, or unmerged entries, in the local path.
+    pub local_conflicts: bool,
+}
+
+/// Drift categories checked by `status --check`, most severe first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum StatusCategory {
+    Conflicts,
+    MissingWorktree,
+    Behind,
+    Modified,
+}
+
+impl StatusCategory {
+    pub const ALL: [StatusCategory; 4] = [
+        StatusCategory::Conflicts,
+        StatusCategory::MissingWorktree,
+        StatusCategory::Behind,
+        StatusCategory::Modified,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StatusCategory::Conflicts => "conflicts",
+            StatusCategory::MissingWorktree => "missing",
+            StatusCategory::Behind => "behind",
+            StatusCategory::Modified => "modified",
+        }
+    }
+
+    /// Process exit code of `status --check` when this is the most severe
+    /// failing category.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            StatusCategory::Conflicts => 10,
+            StatusCategory::MissingWorktree => 11,
+            StatusCategory::Behind => 12,
+            StatusCategory::Modified => 13,
+        }
+    }
+}
+
+impl fmt::Display for StatusCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for StatusCategory {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<StatusCategory> {
+        StatusCategory::ALL
+            .into_iter()
+            .find(|c| c.as_str() == s)
+            .ok_or_else(|| {
+                let names: Vec<&str> = StatusCategory::ALL.iter().map(|c| c.as_str()).collect();
+                anyhow!(
+                    "unknown category '{}', expected one of: {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+impl PatchStatus {
+    /// Position of a patch that does not follow a branch: `pinned at v1.4.2`
+    /// or `v1.4.3 (^1.4)`, plus the newest tag when it is a different one.
+    pub fn ref_summary(&self) -> Option<String> {
+        let (summary, current) = match self.patch.ref_kind {
+            RefKind::Branch => return None,
+            RefKind::Range => {
+                let tag = self.patch.tag.as_deref().unwrap_or("unresolved");
+                (format!("{} ({})", tag, self.patch.branch), tag)
+            }
+            _ => {
+                let pinned = self.patch.ref_name();
+                (format!("pinned at {}", pinned), pinned)
+            }
+        };
+        Some(match &self.latest_tag {
+            Some(latest) if latest != current => format!("{}, latest tag {}", summary, latest),
+            _ => summary,
+        })
+    }
+
+    pub fn has_conflicts(&self) -> bool {
+        self.worktree_conflicts || self.local_conflicts
+    }
+
+    /// Categories this patch falls into, most severe first.
+    pub fn categories(&self) -> Vec<StatusCategory> {
+        let mut categories = Vec::new();
+        if self.has_conflicts() {
+            categories.push(StatusCategory::Conflicts);
+        }
+        if !self.worktree_present {
+            categories.push(StatusCategory::MissingWorktree);
+        }
+        if self.behind > 0 {
+            categories.push(StatusCategory::Behind);
+        }
+        if self.diff == DiffState::Modified {
+            categories.push(StatusCategory::Modified);
+        }
+        categories
+    }
+}
+
+impl CrossRepo {
+    pub fn status(&self) -> Result<Vec<PatchStatus>> {
+        self.patches()?
+            .into_iter()
+            .map(|p| self.patch_status(&p))
+            .collect()
+    }
+
+    pub fn patch_status(&self, patch: &Patch) -> Result<PatchStatus> {
+        let mut status = PatchStatus {
+            patch: patch.clone(),
+            worktree_present: false,
+            diff: DiffState::MissingWorktree,
+            behind: 0,
+            ahead: 0,
+            latest_tag: None,
+            worktree_conflicts: false,
+            local_conflicts: false,
+        };
+
+        let worktree_path = self.join(&patch.worktree);
+        if !worktree_path.exists() {
+            return Ok(status);
+        }
+        status.worktree_present = true;
+
+        status.diff = if self.changed_files(patch)?.is_empty() {
+            DiffState::Clean
+        } else {
+            DiffState::Modified
+        };
+
+        if !patch.ref_kind.is_branch() {
+            // Tag-based patches do not follow a branch; point at the newest tag.
+            status.latest_tag = self.latest_tag(&patch.remote).ok().flatten();
+        }
+
+        let git = self.git();
+        let worktree = self.join(&patch.worktree);
+        // Worktrees are detached, so compare against the remote-tracking
+        // branch as of the last fetch.
+        if patch.ref_kind.is_branch() {
+            let upstream = format!("refs/remotes/{}/{}", patch.remote, patch.branch);
+            let count = |from: &str, to: &str| git.count(&worktree, from, to).unwrap_or(0);
+            status.behind = count("HEAD", &upstream);
+            status.ahead = count(&upstream, "HEAD");
+        }
+
+        status.worktree_conflicts = git.has_conflicts(&worktree, None).unwrap_or(false);
+        status.local_conflicts = git
+            .has_conflicts(self.root(), Some(&patch.local_path))
+            .unwrap_or(false)
+            || patch
+                .conflicts
+                .iter()
+                .any(|file| has_conflict_markers(&self.join(file)));
+
+        Ok(status)
+    }
+}
@@ -0,0 +1,447 @@
+//! `sync`: pull upstream changes into vendored patches, keeping local edits.
+//!
+//! Sync is a three-way merge scoped to the patch directory. The base is the
+//! upstream commit the patch was last synced from (`Patch::base`), "theirs"
+//! is the new upstream tip and "ours" is whatever is on disk under
+//! `local_path`. Files only upstream changed are updated, files only changed
+//! locally are kept, and files changed on both sides are merged like
+//! `git merge-file` does, leaving standard conflict markers where needed. Nothing
+//! outside `local_path` is touched.
+//!
+//! Patches pinned to a tag or commit are "synced" to that same commit, which
+//! only reapplies it; moving a pin is a new `patch`. Version-range patches
+//! sync to the highest tag matching their range.
+
+use crate::error::CrossError;
+use crate::lock::LockedPatch;
+use crate::metadata::Patch;
+use crate::plan::{Op, Plan};
+use crate::repo::CrossRepo;
+use crate::tree::TreeChanges;
+use crate::util::normalize_local_path;
+use anyhow::{Result, anyhow};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+
+/// Planned sync of one patch.
+#[derive(Debug, Clone)]
+pub struct SyncPlan {
+    pub patch: Patch,
+    pub plan: Plan,
+    /// Upstream commit the patch is synced to.
+    pub upstream: String,
+    /// Files (repo relative) that will be left with conflicts.
+    pub conflicts: Vec<String>,
+}
+
+/// Patch that could not be planned, with the reason.
+#[derive(Debug)]
+pub struct SyncSkip {
+    pub patch: Patch,
+    pub error: anyhow::Error,
+}
+
+#[derive(Debug)]
+pub enum SyncOutcome {
+    /// Upstream changes were applied; `conflicts` lists files left with
+    /// conflict markers (or modified on one side and deleted on the other),
+    /// `changes` the local files written or deleted.
+    Synced {
+        conflicts: Vec<String>,
+        changes: TreeChanges,
+    },
+    /// The patch was not synced (e.g. its worktree is missing).
+    Skipped { error: anyhow::Error },
+    /// An operation failed part way through.
+    Failed { error: anyhow::Error },
+}
+
+#[derive(Debug)]
+pub struct SyncResult {
+    pub patch: Patch,
+    pub outcome: SyncOutcome,
+}
+
+/// Whether `path` contains a `<<<<<<< ` conflict marker line.
+pub(crate) fn has_conflict_markers(path: &Path) -> bool {
+    fs::read(path)
+        .map(|content| {
+            content
+                .split(|b| *b == b'\n')
+                .any(|line| line.starts_with(b"<<<<<<< "))
+        })
+        .unwrap_or(false)
+}
+
+impl CrossRepo {
+    /// Three-way merge of the local file with base and upstream blobs.
+    /// Returns the merged content and whether it has conflicts.
+    fn merge_file(
+        &self,
+        local: &Path,
+        base: Option<&str>,
+        upstream: Option<&str>,
+    ) -> Result<(Vec<u8>, bool)> {
+        let blob = |oid: Option<&str>| oid.map(|oid| self.read_blob(oid)).transpose();
+        let (base, upstream) = (blob(base)?, blob(upstream)?);
+        let local_content = fs::read(local)?;
+        self.git()
+            .merge_file(
+                self.root(),
+                &local_content,
+                base.as_deref(),
+                upstream.as_deref(),
+            )
+            .map_err(|e| anyhow!("Cannot merge {}: {:#}", local.display(), e))
+    }
+
+    /// Ops merging the upstream change from `base` to `upstream`, each a
+    /// `(commit, remote path)` pair, into the local copy of `patch`. Returns
+    /// the files left conflicted, including conflicts of an earlier merge
+    /// whose markers are still there.
+    pub(crate) fn plan_merge(
+        &self,
+        plan: &mut Plan,
+        patch: &Patch,
+        base: (&str, &str),
+        upstream: (&str, &str),
+    ) -> Result<Vec<String>> {
+        let (git, root) = (self.git(), self.root());
+        let mut conflicts = Vec::new();
+        if base != upstream {
+            let base_tree = git.blobs(root, base.0, base.1)?;
+            let upstream_tree = git.blobs(root, upstream.0, upstream.1)?;
+            // Files outside the patch filter are neither updated nor merged.
+            let matcher = patch.filter.matcher()?;
+            let paths: BTreeSet<&String> = base_tree
+                .keys()
+                .chain(upstream_tree.keys())
+                .filter(|rel| matcher.matches(rel))
+                .collect();
+
+            let mut local_oids: HashMap<String, String> = HashMap::new();
+            for rel in &paths {
+                let file = patch.local_file(rel);
+                let full = self.join(&file);
+                if fs::symlink_metadata(&full).is_ok_and(|m| m.is_file()) {
+                    local_oids.insert(file, git.hash_file(root, &full)?);
+                }
+            }
+
+            for rel in paths {
+                let file = patch.local_file(rel);
+                let b = base_tree.get(rel);
+                let u = upstream_tree.get(rel);
+                let l = local_oids.get(&file).map(String::as_str);
+                let b_oid = b.map(|e| e.oid.as_str());
+                let u_oid = u.map(|e| e.oid.as_str());
+
+                if b == u || l == u_oid {
+                    // Upstream did not change it, or local already matches.
+                    continue;
+                }
+                if l == b_oid {
+                    // Only upstream changed it.
+                    match u {
+                        Some(entry) => plan.push(Op::WriteBlob {
+                            path: file,
+                            blob: entry.oid.clone(),
+                            executable: entry.executable(),
+                            symlink: entry.symlink(),
+                        }),
+                        None => plan.push(Op::RemoveFile(file)),
+                    }
+                    continue;
+                }
+                if l.is_none() || u.is_none() {
+                    // Modified on one side, deleted on the other: keep the
+                    // local state and report it.
+                    conflicts.push(file);
+                    continue;
+                }
+                let Ok((content, conflicted)) = self.merge_file(&self.join(&file), b_oid, u_oid)
+                else {
+                    // Not mergeable as text (e.g. binary): keep the local file.
+                    conflicts.push(file);
+                    continue;
+                };
+                if conflicted {
+                    conflicts.push(file.clone());
+                }
+                plan.push(Op::WriteMerged {
+                    path: file,
+                    content,
+                    conflicted,
+                });
+            }
+        }
+
+        // Conflicts of an earlier sync stay listed until their markers are gone.
+        for file in &patch.conflicts {
+            if !conflicts.contains(file) && has_conflict_markers(&self.join(file)) {
+                conflicts.push(file.clone());
+            }
+        }
+        conflicts.sort();
+        Ok(conflicts)
+    }
+
+    pub fn plan_sync_patch(&self, patch: &Patch) -> Result<SyncPlan> {
+        let mut plan = Plan::new(format!("sync {}", patch.local_path));
+
+        // Fetch while planning so the plan lists exactly what changes. Pinned
+        // patches resolve to their tag or commit, so they never move.
+        let (tip, tag) = self.fetch_upstream(&patch.remote, &patch.branch, patch.ref_kind)?;
+        let (upstream, tree) = self.resolve_upstream(&tip, &patch.remote_path)?;
+        let git = self.git();
+        let worktree = self.join(&patch.worktree);
+
+        // Patches recorded before the base was tracked fall back to the
+        // commit their worktree is at.
+        let base = match &patch.base {
+            Some(base) => base.clone(),
+            None => git.commit_id(&worktree, "HEAD")?,
+        };
+        self.ensure_commit(&patch.remote, &base)?;
+
+        let conflicts = self.plan_merge(
+            &mut plan,
+            patch,
+            (&base, &patch.remote_path),
+            (&upstream, &patch.remote_path),
+        )?;
+
+        // The worktree always mirrors the upstream commit the patch is synced to.
+        if git.commit_id(&worktree, "HEAD")? != upstream {
+            plan.push(Op::Checkout {
+                worktree: patch.worktree.clone(),
+                commit: upstream.clone(),
+                sparse: patch.remote_path.clone(),
+                force: true,
+            });
+        }
+
+        if patch.base.as_deref() != Some(upstream.as_str())
+            || patch.conflicts != conflicts
+            || patch.tag != tag
+        {
+            let mut updated = patch.clone();
+            updated.base = Some(upstream.clone());
+            updated.conflicts = conflicts.clone();
+            updated.tag = tag.clone();
+            plan.push(Op::SavePatch {
+                patch: updated,
+                change: format!(
+                    "record sync base {} for {}",
+                    crate::plan::short_sha(&upstream),
+                    patch.local_path
+                ),
+            });
+        }
+
+        plan.push(Op::LockPatch(LockedPatch {
+            local_path: patch.local_path.clone(),
+            remote: patch.remote.clone(),
+            branch: patch.branch.clone(),
+            remote_path: patch.remote_path.clone(),
+            commit: upstream.clone(),
+            tree,
+            tag,
+        }));
+
+        Ok(SyncPlan {
+            patch: patch.clone(),
+            plan,
+            upstream,
+            conflicts,
+        })
+    }
+
+    /// Plan syncing the patch at `path`, or every patch when `path` is `None`.
+    /// Patches that cannot be planned are returned separately.
+    pub fn plan_sync_all(&self, path: Option<&str>) -> Result<(Vec<SyncPlan>, Vec<SyncSkip>)> {
+        let path = path.map(normalize_local_path).unwrap_or_default();
+        let mut plans = Vec::new();
+        let mut skipped = Vec::new();
+        for patch in self
+            .patches()?
+            .into_iter()
+            .filter(|p| path.is_empty() || p.local_path == path)
+        {
+            if !self.join(&patch.worktree).exists() {
+                skipped.push(SyncSkip {
+                    error: CrossError::WorktreeMissing {
+                        path: patch.local_path.clone(),
+                    }
+                    .into(),
+                    patch,
+                });
+                continue;
+            }
+            match self.plan_sync_patch(&patch) {
+                Ok(plan) => plans.push(plan),
+                Err(e) => skipped.push(SyncSkip {
+                    error: e.context(format!("Failed to plan sync for {}", patch.local_path)),
+                    patch,
+                }),
+            }
+        }
+        Ok((plans, skipped))
+    }
+
+    /// Sync plans only; used where skipped patches need no report.
+    pub fn plan_sync(&self, path: Option<&str>) -> Result<Vec<SyncPlan>> {
+        Ok(self.plan_sync_all(path)?.0)
+    }
+
+    /// Execute one sync plan.
+    pub fn execute_sync(&self, sync: SyncPlan) -> SyncResult {
+        let SyncPlan {
+            patch,
+            plan,
+            conflicts,
+            ..
+        } = sync;
+        let outcome = match self.execute(&plan) {
+            Ok(changes) => SyncOutcome::Synced { conflicts, changes },
+            Err(error) => SyncOutcome::Failed { error },
+        };
+        SyncResult { patch, outcome }
+    }
+
+    /// Sync the patch at `path` (or all patches). Failures of one patch do not
+    /// stop the others; each gets its own [`SyncResult`].
+    pub fn sync(&self, path: Option<&str>) -> Result<Vec<SyncResult>> {
+        let (plans, skipped) = self.plan_sync_all(path)?;
+        let mut results: Vec<SyncResult> = skipped
+            .into_iter()
+            .map(|s| SyncResult {
+                patch: s.patch,
+                outcome: SyncOutcome::Skipped { error: s.error },
+            })
+            .collect();
+        results.extend(plans.into_iter().map(|p| self.execute_sync(p)));
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::git::testing::{URL, repo};
+    use crate::repo::PatchOptions;
+    use crate::sync::SyncOutcome;
+    use std::fs;
+
+    fn patch_opts(local_path: &str) -> PatchOptions {
+        PatchOptions {
+            local_path: Some(local_path.to_string()),
+            ..PatchOptions::default()
+        }
+    }
+
+    #[test]
+    fn sync_merges_upstream_and_reports_conflicts() {
+        let (_dir, fake, repo) = repo();
+        fake.upstream_commit(
+            URL,
+            "main",
+            &[
+                ("docs/a.md", "one\n"),
+                ("docs/b.md", "b\n"),
+                ("docs/c.md", "c\n"),
+            ],
+            "Add docs",
+        );
+        repo.use_remote("demo", URL).unwrap();
+        repo.add_patch("demo:docs", &patch_opts("vendor/docs"))
+            .unwrap();
+
+        fs::write(repo.join("vendor/docs/a.md"), "local\n").unwrap();
+        fs::write(repo.join("vendor/docs/c.md"), "c (local)\n").unwrap();
+        let tip = fake.upstream_commit(
+            URL,
+            "main",
+            &[
+                ("docs/a.md", "upstream\n"),
+                ("docs/b.md", "b2\n"),
+                ("docs/c.md", "c\n"),
+            ],
+            "Edit docs",
+        );
+
+        let results = repo.sync(Some("vendor/docs")).unwrap();
+        let SyncOutcome::Synced { conflicts, changes } = &results[0].outcome else {
+            panic!("not synced: {:?}", results[0].outcome);
+        };
+        assert_eq!(conflicts, &["vendor/docs/a.md"]);
+        assert_eq!(changes.modified, ["vendor/docs/a.md", "vendor/docs/b.md"]);
+
+        let read = |file: &str| fs::read_to_string(repo.join(file)).unwrap();
+        assert_eq!(read("vendor/docs/b.md"), "b2\n");
+        assert_eq!(read("vendor/docs/c.md"), "c (local)\n");
+        let merged = read("vendor/docs/a.md");
+        assert!(merged.starts_with("<<<<<<< local\nlocal\n"), "{}", merged);
+        assert!(
+            merged.contains("upstream\n>>>>>>> upstream\n"),
+            "{}",
+            merged
+        );
+
+        let patch = repo.find_patch("vendor/docs").unwrap().unwrap();
+        assert_eq!(patch.base.as_deref(), Some(tip.as_str()));
+        assert_eq!(patch.conflicts, ["vendor/docs/a.md"]);
+        assert_eq!(
+            repo.load_lock()
+                .unwrap()
+                .find("vendor/docs")
+                .unwrap()
+                .commit,
+            tip
+        );
+    }
+
+    #[test]
+    fn sync_keeps_local_edits_of_files_deleted_upstream() {
+        let (_dir, fake, repo) = repo();
+        fake.upstream_commit(
+            URL,
+            "main",
+            &[("docs/a.md", "a\n"), ("docs/old.md", "old\n")],
+            "Add docs",
+        );
+        repo.use_remote("demo", URL).unwrap();
+        repo.add_patch("demo:docs", &patch_opts("vendor/docs"))
+            .unwrap();
+
+        fs::write(repo.join("vendor/docs/old.md"), "old (local)\n").unwrap();
+        fake.upstream_commit(URL, "main", &[("docs/a.md", "a\n")], "Drop old");
+
+        let plans = repo.plan_sync(Some("vendor/docs")).unwrap();
+        assert_eq!(plans[0].conflicts, ["vendor/docs/old.md"]);
+        repo.execute(&plans[0].plan).unwrap();
+        assert_eq!(
+            fs::read_to_string(repo.join("vendor/docs/old.md")).unwrap(),
+            "old (local)\n"
+        );
+    }
+
+    #[test]
+    fn sync_without_upstream_changes_only_locks() {
+        let (_dir, fake, repo) = repo();
+        fake.upstream_commit(URL, "main", &[("docs/a.md", "a\n")], "Add docs");
+        repo.use_remote("demo", URL).unwrap();
+        repo.add_patch("demo:docs", &patch_opts("vendor/docs"))
+            .unwrap();
+
+        let plans = repo.plan_sync(Some("vendor/docs")).unwrap();
+        assert!(plans[0].conflicts.is_empty());
+        let ops: Vec<String> = plans[0].plan.ops.iter().map(|op| op.to_string()).collect();
+        assert_eq!(ops.len(), 1, "{:?}", ops);
+        assert!(
+            ops[0].starts_with("Crossfile.lock: pin vendor/docs"),
+            "{:?}",
+            ops
+        );
+    }
+}
@@ -0,0 +1,179 @@
+//! Upstream tags and their semantic versions.
+//!
+//! Tags are listed from the remote's advertised refs, so nothing is added to
+//! the local tag namespace. A tag counts as a version when its name, without
+//! a leading `v`, parses as semver.
+
+use crate::error::CrossError;
+use crate::repo::CrossRepo;
+use anyhow::{Result, anyhow};
+use semver::{Version, VersionReq};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct UpstreamTag {
+    pub name: String,
+    /// Commit the tag points to (peeled for annotated tags).
+    pub commit: String,
+}
+
+impl UpstreamTag {
+    pub fn version(&self) -> Option<Version> {
+        parse_version(&self.name)
+    }
+}
+
+/// `v1.4.2` / `1.4.2` as a semver version.
+pub fn parse_version(name: &str) -> Option<Version> {
+    let stripped = name
+        .strip_prefix('v')
+        .or_else(|| name.strip_prefix('V'))
+        .unwrap_or(name);
+    Version::parse(stripped).ok()
+}
+
+/// Whether a spec ref is a version range (`^1.4`, `~2.0.3`, `>=1, <2`, ...)
+/// rather than a branch, tag or commit. Bare versions are not ranges.
+pub fn is_range(name: &str) -> bool {
+    name.starts_with(['^', '~', '=', '<', '>', '*']) && VersionReq::parse(name).is_ok()
+}
+
+/// Highest tag satisfying `req`.
+pub fn highest_matching<'a>(tags: &'a [UpstreamTag], req: &VersionReq) -> Option<&'a UpstreamTag> {
+    tags.iter()
+        .filter_map(|t| t.version().map(|v| (v, t)))
+        .filter(|(v, _)| req.matches(v))
+        .max_by(|a, b| a.0.cmp(&b.0))
+        .map(|(_, t)| t)
+}
+
+/// Highest released (non pre-release) version among `tags`.
+pub fn newest(tags: &[UpstreamTag]) -> Option<&UpstreamTag> {
+    tags.iter()
+        .filter_map(|t| t.version().map(|v| (v, t)))
+        .filter(|(v, _)| v.pre.is_empty())
+        .max_by(|a, b| a.0.cmp(&b.0))
+        .map(|(_, t)| t)
+}
+
+impl CrossRepo {
+    /// Tags of `remote` with the commits they point to.
+    pub fn remote_tags(&self, remote: &str) -> Result<Vec<UpstreamTag>> {
+        // Annotated tags are listed twice: the tag object, then `<name>^{}`
+        // with the commit; the latter wins.
+        let mut tags: BTreeMap<String, String> = BTreeMap::new();
+        for (sha, name) in self.git().ls_remote(self.root(), remote)? {
+            let Some(name) = name.strip_prefix("refs/tags/") else {
+                continue;
+            };
+            match name.strip_suffix("^{}") {
+                Some(name) => {
+                    tags.insert(name.to_string(), sha);
+                }
+                None => {
+                    tags.entry(name.to_string()).or_insert(sha);
+                }
+            }
+        }
+        Ok(tags
+            .into_iter()
+            .map(|(name, commit)| UpstreamTag { name, commit })
+            .collect())
+    }
+
+    /// Highest tag of `remote` satisfying the version range `range`.
+    pub fn resolve_range(&self, remote: &str, range: &str) -> Result<UpstreamTag> {
+        let req = VersionReq::parse(range).map_err(|e| CrossError::InvalidSpec {
+            spec: range.to_string(),
+            reason: format!("invalid version range: {}", e),
+        })?;
+        let tags = self.remote_tags(remote)?;
+        highest_matching(&tags, &req)
+            .cloned()
+            .ok_or_else(|| anyhow!("No tag of {} matches {}", remote, range))
+    }
+
+    /// Newest semver tag of `remote`, if it has any.
+    pub fn latest_tag(&self, remote: &str) -> Result<Option<String>> {
+        Ok(newest(&self.remote_tags(remote)?).map(|t| t.name.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{UpstreamTag, highest_matching, is_range, newest, parse_version};
+    use crate::git::testing::{URL, repo};
+    use semver::VersionReq;
+
+    fn tags(names: &[&str]) -> Vec<UpstreamTag> {
+        names
+            .iter()
+            .map(|name| UpstreamTag {
+                name: name.to_string(),
+                commit: format!("commit-{}", name),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn ranges_need_an_operator() {
+        assert!(is_range("^1.4"));
+        assert!(is_range("~2.0.3"));
+        assert!(is_range(">=1, <2"));
+        assert!(!is_range("1.2"));
+        assert!(!is_range("v1.4.2"));
+        assert!(!is_range("main"));
+        assert!(!is_range("^not-a-version"));
+    }
+
+    #[test]
+    fn versions_accept_v_prefixes() {
+        assert_eq!(parse_version("v1.4.2"), parse_version("1.4.2"));
+        assert_eq!(parse_version("V1.4.2"), parse_version("1.4.2"));
+        assert!(parse_version("1.4.2").is_some());
+        assert!(parse_version("release-1.4").is_none());
+    }
+
+    #[test]
+    fn highest_matching_and_newest_skip_pre_releases() {
+        let all = tags(&["v1.4.0", "V1.4.7", "1.5.0-rc.1", "v2.0.0-beta", "docs"]);
+        let pick = |tags: &[UpstreamTag], req: &str| {
+            highest_matching(tags, &VersionReq::parse(req).unwrap()).map(|t| t.name.clone())
+        };
+        assert_eq!(pick(&all, "^1.4").as_deref(), Some("V1.4.7"));
+        assert_eq!(pick(&all, "~1.4.0").as_deref(), Some("V1.4.7"));
+        assert_eq!(pick(&all, "^3"), None);
+        assert_eq!(newest(&all).map(|t| t.name.as_str()), Some("V1.4.7"));
+
+        let released = tags(&["v1.4.0", "V1.4.7", "1.5.0-rc.1", "v1.5.0"]);
+        assert_eq!(pick(&released, "^1.4").as_deref(), Some("v1.5.0"));
+        assert!(newest(&tags(&["v2.0.0-beta", "docs"])).is_none());
+    }
+
+    #[test]
+    fn remote_tags_peel_annotated_tags() {
+        let (_dir, fake, repo) = repo();
+        let commit = fake.upstream_commit(URL, "main", &[("a.md", "a\n")], "Add a");
+        repo.use_remote("demo", URL).unwrap();
+        fake.tag(URL, "v1.0.0", &commit);
+        // An annotated tag: the tag object, then the commit it points to.
+        fake.tag(URL, "v2.0.0", "0123456789abcdef0123456789abcdef01234567");
+        fake.tag(URL, "v2.0.0^{}", &commit);
+
+        let tags = repo.remote_tags("demo").unwrap();
+        assert_eq!(
+            tags,
+            [
+                UpstreamTag {
+                    name: "v1.0.0".to_string(),
+                    commit: commit.clone(),
+                },
+                UpstreamTag {
+                    name: "v2.0.0".to_string(),
+                    commit,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,467 @@
+//! Native directory synchronisation, replacing `rsync`.
+//!
+//! [`mirror`] makes a destination directory match a source directory.
+//! Regular files are compared by content and executable bit (never by
+//! mtime), symlinks by their target. `.git` entries are never copied or
+//! deleted. Every call reports which files it added, modified or removed.
+
+use crate::filter::PathFilter;
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Files changed by a synchronisation, as paths relative to its root (or to
+/// the repository once collected by a plan).
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct TreeChanges {
+    pub added: Vec<String>,
+    pub modified: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl TreeChanges {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.modified.is_empty() && self.removed.is_empty()
+    }
+
+    pub fn append(&mut self, mut other: TreeChanges) {
+        self.added.append(&mut other.added);
+        self.modified.append(&mut other.modified);
+        self.removed.append(&mut other.removed);
+    }
+
+    /// The same changes with `prefix/` in front of every path.
+    pub fn prefixed(self, prefix: &str) -> TreeChanges {
+        let join = |paths: Vec<String>| -> Vec<String> {
+            paths
+                .into_iter()
+                .map(|p| format!("{}/{}", prefix, p))
+                .collect()
+        };
+        TreeChanges {
+            added: join(self.added),
+            modified: join(self.modified),
+            removed: join(self.removed),
+        }
+    }
+
+    pub(crate) fn record(&mut self, path: &str, existed: bool) {
+        if existed {
+            self.modified.push(path.to_string());
+        } else {
+            self.added.push(path.to_string());
+        }
+    }
+}
+
+/// `2 added, 1 modified, 0 removed`.
+impl fmt::Display for TreeChanges {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} added, {} modified, {} removed",
+            self.added.len(),
+            self.modified.len(),
+            self.removed.len()
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MirrorOptions {
+    /// Remove destination files that are not in the source.
+    pub delete: bool,
+    /// Only files passing the filter are copied or removed.
+    pub filter: PathFilter,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    File,
+    Symlink,
+    Dir,
+}
+
+/// Entries below `root` keyed by their `/` separated relative path,
+/// skipping `.git`.
+fn entries(root: &Path) -> Result<BTreeMap<String, Kind>> {
+    let mut entries = BTreeMap::new();
+    if !root.is_dir() {
+        return Ok(entries);
+    }
+    let walker = walkdir::WalkDir::new(root)
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(|e| e.file_name() != ".git");
+    for entry in walker {
+        let entry = entry?;
+        let kind = if entry.file_type().is_symlink() {
+            Kind::Symlink
+        } else if entry.file_type().is_dir() {
+            Kind::Dir
+        } else {
+            Kind::File
+        };
+        let rel = entry
+            .path()
+            .strip_prefix(root)?
+            .to_string_lossy()
+            .replace('\\', "/");
+        entries.insert(rel, kind);
+    }
+    Ok(entries)
+}
+
+/// Files and symlinks below `root` (relative, `/` separated), skipping `.git`.
+pub(crate) fn files(root: &Path) -> Result<BTreeSet<String>> {
+    Ok(entries(root)?
+        .into_iter()
+        .filter(|(_, kind)| *kind != Kind::Dir)
+        .map(|(rel, _)| rel)
+        .collect())
+}
+
+/// Files below `dst` (relative, sorted) that [`mirror`] with `delete` removes
+/// when the source holds `src_files`.
+pub(crate) fn mirror_removals(
+    src_files: &BTreeSet<String>,
+    dst: &Path,
+    filter: &PathFilter,
+) -> Result<Vec<String>> {
+    let matcher = filter.matcher()?;
+    Ok(files(dst)?
+        .into_iter()
+        .filter(|rel| !src_files.contains(rel) && matcher.matches(rel))
+        .collect())
+}
+
+/// Make `dst` match `src`. Returned paths are relative to `dst`.
+pub fn mirror(src: &Path, dst: &Path, opts: &MirrorOptions) -> Result<TreeChanges> {
+    if !src.is_dir() {
+        return Err(anyhow!("{} is not a directory", src.display()));
+    }
+    let matcher = opts.filter.matcher()?;
+    let selected = |rel: &str| matcher.matches(rel);
+    // Without a filter empty directories are mirrored too.
+    let whole_tree = opts.filter.is_empty();
+
+    let src_entries = entries(src)?;
+    let mut dst_entries = entries(dst)?;
+    let mut changes = TreeChanges::default();
+    fs::create_dir_all(dst)?;
+
+    for (rel, kind) in &src_entries {
+        let target = dst.join(rel);
+        match kind {
+            Kind::Dir if whole_tree && dst_entries.get(rel) != Some(&Kind::Dir) => {
+                if dst_entries.contains_key(rel) {
+                    remove_entry(&target, rel, &mut dst_entries, &mut changes)?;
+                }
+                fs::create_dir_all(&target)?;
+            }
+            Kind::File | Kind::Symlink if selected(rel) => {
+                if dst_entries.get(rel) == Some(&Kind::Dir) {
+                    remove_entry(&target, rel, &mut dst_entries, &mut changes)?;
+                }
+                if let Some(existed) = sync_file(&src.join(rel), &target)? {
+                    changes.record(rel, existed);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if opts.delete {
+        for (rel, kind) in &dst_entries {
+            if *kind != Kind::Dir && !src_entries.contains_key(rel) && selected(rel) {
+                fs::remove_file(dst.join(rel))?;
+                changes.removed.push(rel.clone());
+            }
+        }
+        // Deepest first, so parents are empty by the time they are checked.
+        for (rel, kind) in dst_entries.iter().rev() {
+            let path = dst.join(rel);
+            if *kind == Kind::Dir
+                && src_entries.get(rel) != Some(&Kind::Dir)
+                && fs::read_dir(&path).is_ok_and(|mut d| d.next().is_none())
+            {
+                fs::remove_dir(&path)?;
+            }
+        }
+    }
+
+    changes.added.sort();
+    changes.modified.sort();
+    changes.removed.sort();
+    Ok(changes)
+}
+
+/// Remove a destination entry whose type differs from the source, recording
+/// the files it held as removed.
+fn remove_entry(
+    path: &Path,
+    rel: &str,
+    dst_entries: &mut BTreeMap<String, Kind>,
+    changes: &mut TreeChanges,
+) -> Result<()> {
+    let prefix = format!("{}/", rel);
+    let inner: Vec<String> = dst_entries
+        .keys()
+        .filter(|p| *p == rel || p.starts_with(&prefix))
+        .cloned()
+        .collect();
+    for p in inner {
+        if let Some(kind) = dst_entries.remove(&p)
+            && kind != Kind::Dir
+            && p != rel
+        {
+            changes.removed.push(p);
+        }
+    }
+    if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)?;
+    } else {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Copy one file or symlink unless `dst` already matches it. Returns
+/// `Some(existed)` when `dst` was written, `None` when it was up to date.
+pub fn sync_file(src: &Path, dst: &Path) -> Result<Option<bool>> {
+    if same_file(src, dst)? {
+        return Ok(None);
+    }
+    let dst_meta = fs::symlink_metadata(dst).ok();
+    let existed = dst_meta.is_some();
+
+    if fs::symlink_metadata(src)?.file_type().is_symlink() {
+        remove_existing(dst, dst_meta.as_ref())?;
+        if let Some(dir) = dst.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        symlink(&fs::read_link(src)?, dst)?;
+        return Ok(Some(existed));
+    }
+    write_file(dst, &fs::read(src)?, is_executable(src))?;
+    Ok(Some(existed))
+}
+
+/// Whether `dst` is the same kind of entry as the file or symlink `src`,
+/// with the same content (or link target) and executable bit.
+pub(crate) fn same_file(src: &Path, dst: &Path) -> Result<bool> {
+    let src_meta =
+        fs::symlink_metadata(src).map_err(|e| anyhow!("Cannot read {}: {}", src.display(), e))?;
+    let Ok(dst_meta) = fs::symlink_metadata(dst) else {
+        return Ok(false);
+    };
+    if src_meta.file_type().is_symlink() {
+        return Ok(dst_meta.file_type().is_symlink() && fs::read_link(dst)? == fs::read_link(src)?);
+    }
+    Ok(dst_meta.is_file()
+        && dst_meta.len() == src_meta.len()
+        && is_executable(dst) == is_executable(src)
+        && fs::read(dst)? == fs::read(src)?)
+}
+
+/// Content of a file, or the target of a symlink, as git stores it.
+pub(crate) fn read_entry(path: &Path) -> Result<Vec<u8>> {
+    if fs::symlink_metadata(path)?.file_type().is_symlink() {
+        return Ok(fs::read_link(path)?.to_string_lossy().as_bytes().to_vec());
+    }
+    Ok(fs::read(path)?)
+}
+
+/// Clear the way for a new file: symlinks are never written through.
+fn remove_existing(path: &Path, meta: Option<&fs::Metadata>) -> Result<()> {
+    match meta {
+        Some(m) if m.is_dir() => fs::remove_dir_all(path)?,
+        Some(_) => fs::remove_file(path)?,
+        None => {}
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> Result<()> {
+    std::os::unix::fs::symlink(target, link)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn symlink(_target: &Path, link: &Path) -> Result<()> {
+    Err(anyhow!(
+        "Cannot create symlink {}: not supported on this platform",
+        link.display()
+    ))
+}
+
+/// Write `content` to `path` with mode 755 or 644, creating parent
+/// directories. A symlink or directory at `path` is replaced, never written
+/// through.
+pub(crate) fn write_file(path: &Path, content: &[u8], executable: bool) -> Result<()> {
+    let meta = fs::symlink_metadata(path).ok();
+    if meta.as_ref().is_some_and(|m| !m.is_file()) {
+        remove_existing(path, meta.as_ref())?;
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, content)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = if executable { 0o755 } else { 0o644 };
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+    }
+    #[cfg(not(unix))]
+    let _ = executable;
+    Ok(())
+}
+
+/// Replace whatever is at `path` with a symlink to `target` (as stored in a
+/// git blob).
+pub(crate) fn write_symlink(path: &Path, target: &[u8]) -> Result<()> {
+    remove_existing(path, fs::symlink_metadata(path).ok().as_ref())?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    symlink(Path::new(&*String::from_utf8_lossy(target)), path)
+}
+
+pub(crate) fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::metadata(path)
+            .map(|m| m.permissions().mode() & 0o111 != 0)
+            .unwrap_or(false)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MirrorOptions, TreeChanges, is_executable, mirror, write_file};
+    use crate::filter::PathFilter;
+    use std::fs;
+    use std::path::Path;
+    use std::time::{Duration, SystemTime};
+
+    fn write(root: &Path, rel: &str, content: &str) {
+        write_file(&root.join(rel), content.as_bytes(), false).unwrap();
+    }
+
+    fn changes(added: &[&str], modified: &[&str], removed: &[&str]) -> TreeChanges {
+        let owned = |paths: &[&str]| paths.iter().map(|p| p.to_string()).collect();
+        TreeChanges {
+            added: owned(added),
+            modified: owned(modified),
+            removed: owned(removed),
+        }
+    }
+
+    #[test]
+    fn mirror_copies_updates_and_deletes() {
+        let (src, dst) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        write(src.path(), "a.md", "a\n");
+        write(src.path(), "sub/b.md", "new\n");
+        write(dst.path(), "sub/b.md", "old\n");
+        write(dst.path(), "gone/c.md", "c\n");
+        write(dst.path(), ".git/HEAD", "ref\n");
+
+        let keep = MirrorOptions::default();
+        let result = mirror(src.path(), dst.path(), &keep).unwrap();
+        assert_eq!(result, changes(&["a.md"], &["sub/b.md"], &[]));
+        assert!(dst.path().join("gone/c.md").exists());
+
+        let delete = MirrorOptions {
+            delete: true,
+            ..MirrorOptions::default()
+        };
+        let result = mirror(src.path(), dst.path(), &delete).unwrap();
+        assert_eq!(result, changes(&[], &[], &["gone/c.md"]));
+        assert!(!dst.path().join("gone").exists());
+        assert!(dst.path().join(".git/HEAD").exists());
+    }
+
+    #[test]
+    fn mirror_leaves_excluded_files_alone() {
+        let (src, dst) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        write(src.path(), "a.md", "a\n");
+        write(src.path(), "build.log", "log\n");
+        write(dst.path(), "local.log", "mine\n");
+        write(dst.path(), "stale.md", "x\n");
+
+        let opts = MirrorOptions {
+            delete: true,
+            filter: PathFilter {
+                include: Vec::new(),
+                exclude: vec!["*.log".to_string()],
+            },
+        };
+        let result = mirror(src.path(), dst.path(), &opts).unwrap();
+        assert_eq!(result, changes(&["a.md"], &[], &["stale.md"]));
+        assert!(!dst.path().join("build.log").exists());
+        assert!(dst.path().join("local.log").exists());
+    }
+
+    #[test]
+    fn mirror_compares_content_not_mtime() {
+        let (src, dst) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        write(src.path(), "a.md", "same\n");
+        write(dst.path(), "a.md", "same\n");
+        let old = SystemTime::now() - Duration::from_secs(86_400);
+        fs::File::options()
+            .write(true)
+            .open(dst.path().join("a.md"))
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+
+        let result = mirror(src.path(), dst.path(), &MirrorOptions::default()).unwrap();
+        assert!(result.is_empty(), "{:?}", result);
+
+        // Same size and mtime, different content.
+        write(src.path(), "a.md", "diff\n");
+        for root in [src.path(), dst.path()] {
+            let file = fs::File::options().write(true).open(root.join("a.md"));
+            file.unwrap().set_modified(old).unwrap();
+        }
+        let result = mirror(src.path(), dst.path(), &MirrorOptions::default()).unwrap();
+        assert_eq!(result, changes(&[], &["a.md"], &[]));
+        assert_eq!(
+            fs::read_to_string(dst.path().join("a.md")).unwrap(),
+            "diff\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn mirror_carries_exec_bits_and_symlinks() {
+        let (src, dst) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        write_file(&src.path().join("run.sh"), b"#!/bin/sh\n", true).unwrap();
+        write(dst.path(), "run.sh", "#!/bin/sh\n");
+        std::os::unix::fs::symlink("run.sh", src.path().join("link")).unwrap();
+        write(dst.path(), "link", "not a link\n");
+
+        let result = mirror(src.path(), dst.path(), &MirrorOptions::default()).unwrap();
+        assert_eq!(result, changes(&[], &["link", "run.sh"], &[]));
+        assert!(is_executable(&dst.path().join("run.sh")));
+        assert_eq!(
+            fs::read_link(dst.path().join("link")).unwrap(),
+            Path::new("run.sh")
+        );
+
+        // A symlink with the same target is up to date.
+        let result = mirror(src.path(), dst.path(), &MirrorOptions::default()).unwrap();
+        assert!(result.is_empty(), "{:?}", result);
+    }
+}
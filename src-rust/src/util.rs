@@ -0,0 +1,38 @@
+//! Small helpers shared by the library modules.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+/// Normalize a user supplied local path to the form stored in metadata.
+pub fn normalize_local_path(path: &str) -> String {
+    let mut normalized = path.replace('\\', "/");
+    normalized = normalized.trim().to_string();
+    while let Some(stripped) = normalized.strip_prefix("./") {
+        normalized = stripped.to_string();
+    }
+    normalized.trim_matches('/').to_string()
+}
+
+/// Replace `path` with `content` atomically: readers see the old or the new
+/// file, never a partial write.
+pub(crate) fn write_atomic(path: &Path, content: &[u8]) -> Result<()> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(dir)?;
+    let mut builder = tempfile::Builder::new();
+    // Keep the mode of the file replaced; new files get the usual 0644
+    // rather than the 0600 of temporary files.
+    if let Ok(meta) = fs::metadata(path) {
+        builder.permissions(meta.permissions());
+    } else {
+        #[cfg(unix)]
+        builder.permissions(std::os::unix::fs::PermissionsExt::from_mode(0o644));
+    }
+    let mut tmp = builder.prefix(".tmp").tempfile_in(dir)?;
+    tmp.write_all(content)?;
+    tmp.as_file().sync_all()?;
+    tmp.persist(path)
+        .with_context(|| format!("Cannot write {}", path.display()))?;
+    Ok(())
+}
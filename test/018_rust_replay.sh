@@ -0,0 +1,55 @@
+#!/usr/bin/env bash
+source "$(dirname "$0")/common.sh"
+
+# Initialize sandbox
+setup_sandbox
+cd "$SANDBOX"
+
+RUST_CROSS="$REPO_ROOT/src-rust/target/debug/git-cross-rust"
+if [ ! -f "$RUST_CROSS" ]; then
+    (cd "$REPO_ROOT/src-rust" && cargo build)
+fi
+
+# Setup upstream
+upstream_path=$(create_upstream "rust-replay")
+upstream_url="file://$upstream_path"
+
+mkdir -p "$upstream_path/src/metal"
+echo "metal config" > "$upstream_path/src/metal/config.yaml"
+git -C "$upstream_path" add src/metal/config.yaml
+git -C "$upstream_path" commit -m "Add metal config" -q
+
+log_header "Testing Rust 'replay' with mixed prefixes..."
+cat > Crossfile <<EOF
+# upstream
+cross use repo1 $upstream_url
+
+git cross patch repo1:src/metal deploy/metal   # trailing comment
+just cross exec "echo 'Replay hook working' > hook.txt"
+EOF
+
+"$RUST_CROSS" replay || fail "Rust 'replay' failed"
+
+git remote | grep -q "^repo1$" || fail "Remote repo1 not added"
+assert_file_exists "deploy/metal/config.yaml"
+assert_grep "hook.txt" "Replay hook working"
+
+# Replaying an unchanged Crossfile must not append duplicate lines
+lines_before=$(wc -l < Crossfile)
+"$RUST_CROSS" replay || fail "Second Rust 'replay' failed"
+lines_after=$(wc -l < Crossfile)
+[ "$lines_before" = "$lines_after" ] || fail "Replay modified Crossfile ($lines_before -> $lines_after lines)"
+
+log_header "Testing Rust 'replay' rejects plain shell lines..."
+cat > Crossfile <<EOF
+cross use repo1 $upstream_url
+echo "should not run" > shell.txt
+EOF
+
+if output=$("$RUST_CROSS" replay 2>&1); then
+    fail "Replay should reject plain shell lines"
+fi
+echo "$output" | grep -q "Crossfile:2:" || fail "Expected error to point at Crossfile:2, got: $output"
+[ ! -f shell.txt ] || fail "Plain shell line was executed"
+
+log_success "Rust replay tests passed!"
@@ -0,0 +1,53 @@
+#!/usr/bin/env bash
+source "$(dirname "$0")/common.sh"
+
+# Initialize sandbox
+setup_sandbox
+cd "$SANDBOX"
+
+RUST_CROSS="$REPO_ROOT/src-rust/target/debug/git-cross-rust"
+if [ ! -f "$RUST_CROSS" ]; then
+    (cd "$REPO_ROOT/src-rust" && cargo build)
+fi
+
+# Setup upstream
+upstream_path=$(create_upstream "rust-lock")
+upstream_url="file://$upstream_path"
+
+mkdir -p "$upstream_path/docs"
+echo "v1" > "$upstream_path/docs/version.txt"
+git -C "$upstream_path" add docs
+git -C "$upstream_path" commit -m "v1" -q
+locked_commit=$(git -C "$upstream_path" rev-parse HEAD)
+
+log_header "Testing Rust 'patch' writes Crossfile.lock..."
+"$RUST_CROSS" use demo "$upstream_url"
+"$RUST_CROSS" patch demo:docs vendor/docs
+assert_file_exists "Crossfile.lock"
+assert_grep "Crossfile.lock" "$locked_commit"
+git add Crossfile Crossfile.lock vendor && git commit -q -m "Vendor docs"
+
+# Upstream moves on
+echo "v2" > "$upstream_path/docs/version.txt"
+git -C "$upstream_path" commit -am "v2" -q
+
+log_header "Testing Rust 'replay' in a fresh clone honours the lock..."
+git clone -q "$SANDBOX" "$SANDBOX/clone"
+pushd "$SANDBOX/clone" >/dev/null
+    "$RUST_CROSS" replay || fail "Rust 'replay' failed in clone"
+    grep -q "v1" vendor/docs/version.txt || fail "Replay did not check out the locked commit"
+    [ -z "$(git status --porcelain)" ] || fail "Replay changed the clone: $(git status --porcelain)"
+popd >/dev/null
+
+log_header "Testing Rust 'sync' updates the lock..."
+"$RUST_CROSS" sync || fail "Rust 'sync' failed"
+grep -q "v2" vendor/docs/version.txt || fail "Sync did not pull v2"
+assert_grep "Crossfile.lock" "$(git -C "$upstream_path" rev-parse HEAD)"
+
+log_header "Testing Rust 'remove' drops the lock entry..."
+"$RUST_CROSS" remove vendor/docs
+if grep -q "vendor/docs" Crossfile.lock; then
+    fail "Lock entry for vendor/docs still present"
+fi
+
+log_success "Rust lock tests passed!"
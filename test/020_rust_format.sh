@@ -0,0 +1,66 @@
+#!/usr/bin/env bash
+source "$(dirname "$0")/common.sh"
+
+# Initialize sandbox
+setup_sandbox
+cd "$SANDBOX"
+
+RUST_CROSS="$REPO_ROOT/src-rust/target/debug/git-cross-rust"
+if [ ! -f "$RUST_CROSS" ]; then
+    (cd "$REPO_ROOT/src-rust" && cargo build)
+fi
+
+# Setup upstream
+upstream_path=$(create_upstream "rust-format")
+upstream_url="file://$upstream_path"
+
+mkdir -p "$upstream_path/docs"
+echo "readme" > "$upstream_path/docs/README.md"
+echo "guide" > "$upstream_path/docs/guide.md"
+git -C "$upstream_path" add docs
+git -C "$upstream_path" commit -m "docs" -q
+
+"$RUST_CROSS" use demo "$upstream_url"
+"$RUST_CROSS" patch demo:docs vendor/docs
+
+log_header "Testing Rust 'list --format json'..."
+out=$("$RUST_CROSS" list --format json)
+[ "$(echo "$out" | jq -r '.patches[0].local_path')" == "vendor/docs" ] || fail "list json: $out"
+[ "$(echo "$out" | jq -r '.remotes[0].name')" == "demo" ] || fail "list json remotes: $out"
+
+log_header "Testing Rust 'list --format tsv'..."
+out=$("$RUST_CROSS" list --format tsv)
+echo "$out" | head -1 | grep -q "^id	remote	branch	remote_path	local_path	worktree$" || fail "list tsv header: $out"
+echo "$out" | grep -q "	demo	main	docs	vendor/docs	" || fail "list tsv row: $out"
+
+log_header "Testing Rust 'status --format json' on a clean patch..."
+out=$("$RUST_CROSS" status --format json)
+[ "$(echo "$out" | jq -r '.[0].diff')" == "clean" ] || fail "status json diff: $out"
+[ "$(echo "$out" | jq -r '.[0].worktree_present')" == "true" ] || fail "status json worktree: $out"
+[ "$(echo "$out" | jq -r '.[0].behind')" == "0" ] || fail "status json behind: $out"
+
+log_header "Testing Rust status/diff formats on a modified patch..."
+echo "local" >> vendor/docs/README.md
+rm vendor/docs/guide.md
+echo "new" > vendor/docs/notes.md
+
+out=$("$RUST_CROSS" status --format json)
+[ "$(echo "$out" | jq -r '.[0].diff')" == "modified" ] || fail "status json modified: $out"
+"$RUST_CROSS" status --format tsv | grep -q "^vendor/docs	modified	0	0	true	false	false$" \
+    || fail "status tsv: $("$RUST_CROSS" status --format tsv)"
+
+out=$("$RUST_CROSS" diff --format json)
+[ "$(echo "$out" | jq -r '.[0].files | map(.path + ":" + .change) | sort | join(",")')" \
+    == "README.md:modified,guide.md:deleted,notes.md:added" ] || fail "diff json files: $out"
+echo "$out" | jq -r '.[0].patch' | grep -q "^+local$" || fail "diff json patch: $out"
+
+"$RUST_CROSS" diff vendor/docs --format tsv | grep -q "^vendor/docs	added	notes.md$" \
+    || fail "diff tsv: $("$RUST_CROSS" diff --format tsv)"
+
+log_header "Testing Rust status reports a missing worktree..."
+rm -rf .git/cross/worktrees
+out=$("$RUST_CROSS" status --format json)
+[ "$(echo "$out" | jq -r '.[0].diff')" == "missing_worktree" ] || fail "status json missing: $out"
+[ "$(echo "$out" | jq -r '.[0].worktree_present')" == "false" ] || fail "status json missing: $out"
+
+log_success "Rust output format tests passed!"
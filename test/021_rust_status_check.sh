@@ -0,0 +1,63 @@
+#!/usr/bin/env bash
+source "$(dirname "$0")/common.sh"
+
+# Initialize sandbox
+setup_sandbox
+cd "$SANDBOX"
+
+RUST_CROSS="$REPO_ROOT/src-rust/target/debug/git-cross-rust"
+if [ ! -f "$RUST_CROSS" ]; then
+    (cd "$REPO_ROOT/src-rust" && cargo build)
+fi
+
+# Setup upstream
+upstream_path=$(create_upstream "rust-check")
+upstream_url="file://$upstream_path"
+
+mkdir -p "$upstream_path/docs"
+echo "v1" > "$upstream_path/docs/version.txt"
+git -C "$upstream_path" add docs
+git -C "$upstream_path" commit -m "v1" -q
+
+"$RUST_CROSS" use demo "$upstream_url"
+"$RUST_CROSS" patch demo:docs vendor/docs
+
+expect_exit() {
+    local expected=$1; shift
+    set +e
+    "$RUST_CROSS" status --check "$@" >/dev/null 2>&1
+    local code=$?
+    set -e
+    [ "$code" -eq "$expected" ] || fail "status --check $* exited $code, expected $expected"
+}
+
+log_header "Testing Rust 'status --check' on pristine patches..."
+expect_exit 0
+
+log_header "Testing Rust 'status --check' on a modified patch..."
+echo "local" >> vendor/docs/version.txt
+expect_exit 13
+expect_exit 0 --fail-on behind,conflicts,missing
+"$RUST_CROSS" status --check 2>&1 >/dev/null | grep -q "vendor/docs: modified" \
+    || fail "status --check did not name the modified patch"
+
+log_header "Testing Rust 'status --check' when behind upstream..."
+echo "v2" > "$upstream_path/docs/version.txt"
+git -C "$upstream_path" commit -am "v2" -q
+git fetch -q demo main
+expect_exit 12
+expect_exit 13 --fail-on modified
+
+log_header "Testing Rust 'status --check' with a missing worktree..."
+rm -rf "$(jq -r '.patches[0].worktree' .git/cross/metadata.json)"
+expect_exit 11
+expect_exit 0 --fail-on conflicts
+
+log_header "Testing Rust 'status --check' rejects unknown categories..."
+set +e
+"$RUST_CROSS" status --check --fail-on dirty >/dev/null 2>&1
+code=$?
+set -e
+[ "$code" -eq 2 ] || fail "unknown --fail-on category exited $code, expected 2"
+
+log_success "Rust status --check tests passed!"
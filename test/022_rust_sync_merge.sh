@@ -0,0 +1,88 @@
+#!/usr/bin/env bash
+source "$(dirname "$0")/common.sh"
+
+# Initialize sandbox
+setup_sandbox
+cd "$SANDBOX"
+
+RUST_CROSS="$REPO_ROOT/src-rust/target/debug/git-cross-rust"
+if [ ! -f "$RUST_CROSS" ]; then
+    (cd "$REPO_ROOT/src-rust" && cargo build)
+fi
+
+# Setup upstream
+upstream_path=$(create_upstream "rust-merge")
+upstream_url="file://$upstream_path"
+
+mkdir -p "$upstream_path/docs"
+printf 'one\ntwo\nthree\nfour\nfive\n' > "$upstream_path/docs/merge.txt"
+echo "base" > "$upstream_path/docs/conflict.txt"
+echo "gone soon" > "$upstream_path/docs/removed.txt"
+git -C "$upstream_path" add docs
+git -C "$upstream_path" commit -m "v1" -q
+
+"$RUST_CROSS" use demo "$upstream_url"
+"$RUST_CROSS" patch demo:docs vendor/docs
+base_commit=$(git -C "$upstream_path" rev-parse HEAD)
+[ "$(jq -r '.patches[0].base' .git/cross/metadata.json)" == "$base_commit" ] \
+    || fail "patch did not record the sync base"
+echo "tracked" > unrelated.txt
+git add Crossfile Crossfile.lock vendor unrelated.txt && git commit -q -m "Vendor docs"
+
+log_header "Preparing local and upstream changes..."
+sed -i 's/^one$/ONE (local)/' vendor/docs/merge.txt
+echo "local" > vendor/docs/conflict.txt
+echo "local only" > vendor/docs/mine.txt
+echo "unrelated work" >> unrelated.txt
+
+sed -i 's/^five$/FIVE (upstream)/' "$upstream_path/docs/merge.txt"
+echo "upstream" > "$upstream_path/docs/conflict.txt"
+git -C "$upstream_path" rm -q docs/removed.txt
+echo "new" > "$upstream_path/docs/added.txt"
+git -C "$upstream_path" add docs
+git -C "$upstream_path" commit -m "v2" -q
+new_commit=$(git -C "$upstream_path" rev-parse HEAD)
+
+log_header "Testing Rust 'sync --dry' lists the merge..."
+plan=$("$RUST_CROSS" sync --dry)
+echo "$plan" | grep -q "merge vendor/docs/conflict.txt (conflicts)" || fail "dry plan: $plan"
+echo "$plan" | grep -q "merge vendor/docs/merge.txt$" || fail "dry plan: $plan"
+echo "$plan" | grep -q "delete vendor/docs/removed.txt" || fail "dry plan: $plan"
+echo "$plan" | grep -q "update vendor/docs/added.txt from upstream" || fail "dry plan: $plan"
+grep -q "ONE (local)" vendor/docs/merge.txt || fail "--dry changed files"
+
+log_header "Testing Rust 'sync' three-way merge..."
+code=0
+out=$("$RUST_CROSS" sync 2>&1) || code=$?
+[ "$code" -eq 20 ] || fail "sync with conflicts exited $code, expected 20: $out"
+echo "$out" | grep -q "vendor/docs/conflict.txt" || fail "sync did not list the conflict: $out"
+
+grep -q "ONE (local)" vendor/docs/merge.txt || fail "local change lost"
+grep -q "FIVE (upstream)" vendor/docs/merge.txt || fail "upstream change not merged"
+grep -q "^<<<<<<< local$" vendor/docs/conflict.txt || fail "no conflict markers"
+grep -q "^>>>>>>> upstream$" vendor/docs/conflict.txt || fail "no conflict markers"
+[ ! -f vendor/docs/removed.txt ] || fail "file removed upstream still present"
+grep -q "new" vendor/docs/added.txt || fail "file added upstream missing"
+grep -q "local only" vendor/docs/mine.txt || fail "local-only file lost"
+
+log_header "Testing Rust 'sync' leaves the rest of the repository alone..."
+grep -q "unrelated work" unrelated.txt || fail "unrelated change lost"
+[ -z "$(git stash list)" ] || fail "sync used git stash"
+
+[ "$(jq -r '.patches[0].base' .git/cross/metadata.json)" == "$new_commit" ] \
+    || fail "sync did not record the new base"
+[ "$(jq -r '.patches[0].conflicts[0]' .git/cross/metadata.json)" == "vendor/docs/conflict.txt" ] \
+    || fail "sync did not record the conflict"
+[ "$("$RUST_CROSS" status --format json | jq -r '.[0].local_conflicts')" == "true" ] \
+    || fail "status does not report the conflict"
+
+log_header "Testing Rust status once the conflict is resolved..."
+echo "resolved" > vendor/docs/conflict.txt
+[ "$("$RUST_CROSS" status --format json | jq -r '.[0].local_conflicts')" == "false" ] \
+    || fail "status still reports the resolved conflict"
+"$RUST_CROSS" sync >/dev/null || fail "second sync failed"
+[ "$(jq -r '.patches[0].conflicts // [] | length' .git/cross/metadata.json)" == "0" ] \
+    || fail "resolved conflict still recorded"
+grep -q "resolved" vendor/docs/conflict.txt || fail "second sync touched the resolved file"
+
+log_success "Rust three-way sync tests passed!"
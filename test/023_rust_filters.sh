@@ -0,0 +1,90 @@
+#!/usr/bin/env bash
+source "$(dirname "$0")/common.sh"
+
+# Initialize sandbox
+setup_sandbox
+cd "$SANDBOX"
+
+RUST_CROSS="$REPO_ROOT/src-rust/target/debug/git-cross-rust"
+if [ ! -f "$RUST_CROSS" ]; then
+    (cd "$REPO_ROOT/src-rust" && cargo build)
+fi
+
+# Setup upstream
+upstream_path=$(create_upstream "rust-filters")
+upstream_url="file://$upstream_path"
+
+mkdir -p "$upstream_path/src/tests" "$upstream_path/src/sub"
+echo "lib" > "$upstream_path/src/lib.rs"
+echo "mod" > "$upstream_path/src/sub/mod.rs"
+echo "readme" > "$upstream_path/src/README.md"
+echo "test" > "$upstream_path/src/tests/t.rs"
+git -C "$upstream_path" add src
+git -C "$upstream_path" commit -m "v1" -q
+git -C "$upstream_path" config receive.denyCurrentBranch ignore
+
+"$RUST_CROSS" use demo "$upstream_url"
+
+log_header "Testing Rust 'patch --exclude'..."
+"$RUST_CROSS" patch demo:src vendor/src --exclude tests --exclude '*.md'
+test -f vendor/src/lib.rs || fail "lib.rs not vendored"
+test -f vendor/src/sub/mod.rs || fail "sub/mod.rs not vendored"
+[ ! -e vendor/src/README.md ] || fail "excluded README.md vendored"
+[ ! -e vendor/src/tests ] || fail "excluded tests/ vendored"
+
+grep -q "^cross patch demo:main:src vendor/src --exclude tests --exclude '\*.md'$" Crossfile \
+    || fail "Crossfile does not record the filter: $(cat Crossfile)"
+[ "$(jq -r '.patches[0].exclude | join(",")' .git/cross/metadata.json)" == "tests,*.md" ] \
+    || fail "metadata does not record the filter"
+git add Crossfile Crossfile.lock vendor && git commit -q -m "Vendor src"
+
+log_header "Testing Rust status/diff ignore excluded files..."
+echo "local notes" > vendor/src/NOTES.md
+mkdir -p vendor/src/tests && echo "local test" > vendor/src/tests/local.rs
+[ "$("$RUST_CROSS" status --format json | jq -r '.[0].diff')" == "clean" ] \
+    || fail "excluded local files reported as drift"
+[ -z "$("$RUST_CROSS" diff vendor/src)" ] || fail "diff shows excluded files"
+
+echo "local" >> vendor/src/lib.rs
+[ "$("$RUST_CROSS" diff --format json | jq -r '.[0].files | map(.path) | join(",")')" == "lib.rs" ] \
+    || fail "diff json: $("$RUST_CROSS" diff --format json)"
+git add vendor && git commit -q -m "Local change"
+
+log_header "Testing Rust 'sync' skips excluded upstream changes..."
+echo "upstream" >> "$upstream_path/src/tests/t.rs"
+echo "readme v2" > "$upstream_path/src/README.md"
+echo "new" > "$upstream_path/src/new.rs"
+git -C "$upstream_path" add src
+git -C "$upstream_path" commit -m "v2" -q
+
+"$RUST_CROSS" sync >/dev/null || fail "sync failed"
+test -f vendor/src/new.rs || fail "included upstream file not synced"
+[ ! -e vendor/src/README.md ] || fail "excluded upstream file synced"
+grep -q "local test" vendor/src/tests/local.rs || fail "excluded local file touched"
+[ ! -e vendor/src/tests/t.rs ] || fail "excluded upstream file synced"
+grep -q "local" vendor/src/lib.rs || fail "local change lost"
+git add vendor && git commit -q -m "Sync"
+
+log_header "Testing Rust 'push' leaves excluded upstream files alone..."
+"$RUST_CROSS" push vendor/src --yes --message "Push filtered" >/dev/null || fail "push failed"
+[ "$(git -C "$upstream_path" log -1 --pretty=%s main)" == "Push filtered" ] || fail "push did not land"
+files=$(git -C "$upstream_path" ls-tree -r --name-only main -- src | sort | tr '\n' ' ')
+[ "$files" == "src/README.md src/lib.rs src/new.rs src/sub/mod.rs src/tests/t.rs " ] \
+    || fail "unexpected upstream tree after push: $files"
+git -C "$upstream_path" show main:src/lib.rs | grep -q "local" || fail "local change not pushed"
+
+log_header "Testing Rust 'replay' keeps the filter..."
+rm -rf vendor .git/cross && git worktree prune
+"$RUST_CROSS" replay >/dev/null || fail "replay failed"
+test -f vendor/src/lib.rs || fail "replay did not vendor lib.rs"
+[ ! -e vendor/src/README.md ] || fail "replay ignored the filter"
+[ "$(jq -r '.patches[0].exclude | join(",")' .git/cross/metadata.json)" == "tests,*.md" ] \
+    || fail "replay lost the filter"
+
+log_header "Testing Rust 'patch --include'..."
+"$RUST_CROSS" patch demo:src vendor/rs-only --include '*.rs' --exclude sub >/dev/null
+test -f vendor/rs-only/lib.rs || fail "included file missing"
+[ ! -e vendor/rs-only/README.md ] || fail "file outside include vendored"
+[ ! -e vendor/rs-only/sub ] || fail "excluded dir vendored"
+
+log_success "Rust filter tests passed!"
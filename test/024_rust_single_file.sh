@@ -0,0 +1,89 @@
+#!/usr/bin/env bash
+source "$(dirname "$0")/common.sh"
+
+# Initialize sandbox
+setup_sandbox
+cd "$SANDBOX"
+
+RUST_CROSS="$REPO_ROOT/src-rust/target/debug/git-cross-rust"
+if [ ! -f "$RUST_CROSS" ]; then
+    (cd "$REPO_ROOT/src-rust" && cargo build)
+fi
+
+# Setup upstream
+upstream_path=$(create_upstream "rust-single-file")
+upstream_url="file://$upstream_path"
+
+mkdir -p "$upstream_path/docs" "$upstream_path/bin"
+printf 'MIT License\n\nline 3\nline 4\nline 5\n' > "$upstream_path/LICENSE"
+echo "nested readme" > "$upstream_path/docs/README.md"
+printf '#!/bin/sh\necho run\n' > "$upstream_path/bin/run.sh"
+chmod +x "$upstream_path/bin/run.sh"
+git -C "$upstream_path" add .
+git -C "$upstream_path" commit -m "v1" -q
+git -C "$upstream_path" config receive.denyCurrentBranch ignore
+
+"$RUST_CROSS" use demo "$upstream_url"
+
+log_header "Testing Rust 'patch' of a single file..."
+"$RUST_CROSS" patch demo:LICENSE third_party/foo/LICENSE
+test -f third_party/foo/LICENSE || fail "LICENSE not vendored as a file"
+grep -q "MIT License" third_party/foo/LICENSE || fail "LICENSE content wrong"
+grep -q "^cross patch demo:main:LICENSE third_party/foo/LICENSE$" Crossfile \
+    || fail "Crossfile line missing: $(cat Crossfile)"
+[ "$(jq -r '.patches[0].file' .git/cross/metadata.json)" == "true" ] \
+    || fail "metadata does not mark the file patch"
+
+"$RUST_CROSS" patch demo:README.md vendor/readme.md
+grep -q "Content for rust-single-file" vendor/readme.md || fail "README.md not vendored"
+wt=$(jq -r '.patches[] | select(.local_path == "vendor/readme.md") | .worktree' .git/cross/metadata.json)
+[ ! -e "$wt/docs/README.md" ] || fail "sparse checkout pulled in docs/README.md"
+
+"$RUST_CROSS" patch demo:bin/run.sh vendor/run.sh
+[ -x vendor/run.sh ] || fail "executable bit lost"
+
+if "$RUST_CROSS" patch demo:LICENSE third_party 2>/dev/null; then
+    fail "patching a file onto a directory should fail"
+fi
+if "$RUST_CROSS" patch demo:LICENSE vendor/license --exclude '*.md' 2>/dev/null; then
+    fail "filters on a file patch should fail"
+fi
+git add Crossfile Crossfile.lock third_party vendor && git commit -q -m "Vendor files"
+
+log_header "Testing Rust status/diff on a single file..."
+[ "$("$RUST_CROSS" status --format json | jq -r '.[0].diff')" == "clean" ] || fail "file patch not clean"
+sed -i 's/^line 3$/line 3 (local)/' third_party/foo/LICENSE
+[ "$("$RUST_CROSS" status --format json | jq -r '.[0].diff')" == "modified" ] || fail "file change not detected"
+out=$("$RUST_CROSS" diff third_party/foo/LICENSE --format json)
+[ "$(echo "$out" | jq -r '.[0].files | map(.path + ":" + .change) | join(",")')" == "LICENSE:modified" ] \
+    || fail "diff json: $out"
+echo "$out" | jq -r '.[0].patch' | grep -q "^+line 3 (local)$" || fail "diff text: $out"
+git add third_party && git commit -q -m "Local license change"
+
+log_header "Testing Rust 'sync' merges a single file..."
+sed -i 's/^line 5$/line 5 (upstream)/' "$upstream_path/LICENSE"
+git -C "$upstream_path" commit -q -am "v2"
+"$RUST_CROSS" sync third_party/foo/LICENSE >/dev/null || fail "sync failed"
+grep -q "line 3 (local)" third_party/foo/LICENSE || fail "local change lost"
+grep -q "line 5 (upstream)" third_party/foo/LICENSE || fail "upstream change not merged"
+git add third_party && git commit -q -m "Sync license"
+
+log_header "Testing Rust 'push' of a single file..."
+"$RUST_CROSS" push third_party/foo/LICENSE --yes --message "Push license" >/dev/null || fail "push failed"
+[ "$(git -C "$upstream_path" log -1 --pretty=%s main)" == "Push license" ] || fail "push did not land"
+git -C "$upstream_path" show main:LICENSE | grep -q "line 3 (local)" || fail "local change not pushed"
+git -C "$upstream_path" cat-file -e main:docs/README.md || fail "push removed unrelated upstream files"
+
+log_header "Testing Rust status on a deleted single file..."
+rm vendor/readme.md
+out=$("$RUST_CROSS" diff vendor/readme.md --format json)
+[ "$(echo "$out" | jq -r '.[0].files[0].change')" == "deleted" ] || fail "deleted file: $out"
+
+log_header "Testing Rust 'remove' of a single file..."
+"$RUST_CROSS" remove third_party/foo/LICENSE >/dev/null || fail "remove failed"
+[ ! -e third_party/foo/LICENSE ] || fail "file not removed"
+grep -q "LICENSE" Crossfile && fail "Crossfile still lists the file patch"
+[ "$(jq -r '.patches | map(.local_path) | join(",")' .git/cross/metadata.json)" == "vendor/readme.md,vendor/run.sh" ] \
+    || fail "metadata still lists the file patch"
+
+log_success "Rust single-file patch tests passed!"
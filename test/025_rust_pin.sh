@@ -0,0 +1,73 @@
+#!/usr/bin/env bash
+source "$(dirname "$0")/common.sh"
+
+# Initialize sandbox
+setup_sandbox
+cd "$SANDBOX"
+
+RUST_CROSS="$REPO_ROOT/src-rust/target/debug/git-cross-rust"
+if [ ! -f "$RUST_CROSS" ]; then
+    (cd "$REPO_ROOT/src-rust" && cargo build)
+fi
+
+# Setup upstream
+upstream_path=$(create_upstream "rust-pin")
+upstream_url="file://$upstream_path"
+
+mkdir -p "$upstream_path/docs"
+echo "v1.4.2" > "$upstream_path/docs/VERSION"
+git -C "$upstream_path" add docs
+git -C "$upstream_path" commit -m "Release 1.4.2" -q
+git -C "$upstream_path" tag v1.4.2
+echo "unreleased" > "$upstream_path/docs/VERSION"
+git -C "$upstream_path" commit -qam "Work in progress"
+wip_commit=$(git -C "$upstream_path" rev-parse HEAD)
+
+"$RUST_CROSS" use demo "$upstream_url"
+
+log_header "Testing Rust 'patch' pinned to a tag..."
+"$RUST_CROSS" patch demo:v1.4.2:docs vendor/tagged
+grep -q "v1.4.2" vendor/tagged/VERSION || fail "tag content not vendored"
+[ "$(jq -r '.patches[0].ref_kind' .git/cross/metadata.json)" == "tag" ] || fail "ref kind not recorded"
+[ "$(jq -r '.patches[0].branch' .git/cross/metadata.json)" == "v1.4.2" ] || fail "tag not recorded"
+grep -q "^cross patch demo:v1.4.2:docs vendor/tagged$" Crossfile || fail "Crossfile: $(cat Crossfile)"
+
+log_header "Testing Rust 'patch' pinned to a commit..."
+"$RUST_CROSS" patch "demo:${wip_commit:0:8}:docs" vendor/commit
+grep -q "unreleased" vendor/commit/VERSION || fail "commit content not vendored"
+[ "$(jq -r '.patches[1].ref_kind' .git/cross/metadata.json)" == "commit" ] || fail "ref kind not recorded"
+[ "$(jq -r '.patches[1].branch' .git/cross/metadata.json)" == "$wip_commit" ] || fail "full SHA not recorded"
+grep -q "^cross patch demo:$wip_commit:docs vendor/commit$" Crossfile || fail "Crossfile: $(cat Crossfile)"
+
+"$RUST_CROSS" patch demo:docs vendor/main
+[ "$(jq -r '.patches[2].ref_kind // "branch"' .git/cross/metadata.json)" == "branch" ] || fail "branch patch kind"
+
+if "$RUST_CROSS" patch demo:no-such-ref:docs vendor/bad 2>/dev/null; then
+    fail "unknown ref should fail"
+fi
+
+log_header "Testing Rust 'sync' keeps pinned patches in place..."
+echo "v1.5.0" > "$upstream_path/docs/VERSION"
+git -C "$upstream_path" commit -qam "Release 1.5.0"
+git -C "$upstream_path" tag v1.5.0
+
+"$RUST_CROSS" sync >/dev/null || fail "sync failed"
+grep -q "v1.4.2" vendor/tagged/VERSION || fail "tag pin moved"
+grep -q "unreleased" vendor/commit/VERSION || fail "commit pin moved"
+grep -q "v1.5.0" vendor/main/VERSION || fail "branch patch did not follow"
+
+log_header "Testing Rust 'status' on pinned patches..."
+out=$("$RUST_CROSS" status)
+echo "$out" | grep -q "pinned at v1.4.2, latest tag v1.5.0" || fail "status text: $out"
+echo "$out" | grep -q "pinned at ${wip_commit:0:12}, latest tag v1.5.0" || fail "status text: $out"
+json=$("$RUST_CROSS" status --format json)
+[ "$(echo "$json" | jq -r '.[0].latest_tag')" == "v1.5.0" ] || fail "status json: $json"
+[ "$(echo "$json" | jq -r '.[0].behind')" == "0" ] || fail "pinned patch reported behind: $json"
+"$RUST_CROSS" status --check --fail-on behind >/dev/null || fail "pinned patches fail the behind check"
+
+log_header "Testing Rust 'push' of a pinned patch needs a branch..."
+if "$RUST_CROSS" push vendor/tagged --yes 2>/dev/null; then
+    fail "push of a tag pin without --branch should fail"
+fi
+
+log_success "Rust pinned patch tests passed!"
@@ -0,0 +1,66 @@
+#!/usr/bin/env bash
+source "$(dirname "$0")/common.sh"
+
+# Initialize sandbox
+setup_sandbox
+cd "$SANDBOX"
+
+RUST_CROSS="$REPO_ROOT/src-rust/target/debug/git-cross-rust"
+if [ ! -f "$RUST_CROSS" ]; then
+    (cd "$REPO_ROOT/src-rust" && cargo build)
+fi
+
+# Setup upstream
+upstream_path=$(create_upstream "rust-outdated")
+upstream_url="file://$upstream_path"
+
+mkdir -p "$upstream_path/docs" "$upstream_path/src"
+echo "v1" > "$upstream_path/docs/guide.md"
+echo "code" > "$upstream_path/src/main.c"
+git -C "$upstream_path" add .
+git -C "$upstream_path" commit -m "Release 1.0.0" -q
+git -C "$upstream_path" tag -a v1.0.0 -m "1.0.0"
+
+"$RUST_CROSS" use demo "$upstream_url"
+"$RUST_CROSS" patch demo:docs vendor/docs
+"$RUST_CROSS" patch demo:v1.0.0:docs vendor/docs-pinned
+
+log_header "Testing Rust 'outdated' with nothing new..."
+out=$("$RUST_CROSS" outdated)
+echo "$out" | grep -q "^vendor/docs (demo/main): up to date, latest tag v1.0.0$" || fail "outdated: $out"
+
+log_header "Preparing upstream changes..."
+echo "more code" >> "$upstream_path/src/main.c"
+git -C "$upstream_path" commit -qam "Unrelated code change"
+echo "v1.1" > "$upstream_path/docs/guide.md"
+git -C "$upstream_path" commit -qam "Update guide"
+git -C "$upstream_path" tag v1.1.0
+echo "v2 preview" > "$upstream_path/docs/guide.md"
+git -C "$upstream_path" commit -qam "Preview guide"
+git -C "$upstream_path" tag v2.0.0-rc.1
+docs_commit=$(git -C "$upstream_path" rev-parse HEAD)
+
+log_header "Testing Rust 'outdated' lists commits touching the patch path..."
+out=$("$RUST_CROSS" outdated)
+echo "$out" | grep -q "^vendor/docs (demo/main): 2 new commit(s), latest tag v1.1.0$" || fail "outdated: $out"
+echo "$out" | grep -q "Update guide" || fail "missing docs commit: $out"
+echo "$out" | grep -q "Unrelated code change" && fail "commit outside remote_path listed: $out"
+echo "$out" | grep -q "^vendor/docs-pinned (pinned at v1.0.0): 1 new commit(s), latest tag v1.1.0$" \
+    || fail "pinned outdated: $out"
+
+json=$("$RUST_CROSS" outdated --format json)
+[ "$(echo "$json" | jq -r '.[0].branch_tip')" == "$docs_commit" ] || fail "branch tip: $json"
+[ "$(echo "$json" | jq -r '.[0].commits | map(.subject) | join(",")')" == "Preview guide,Update guide" ] \
+    || fail "commits: $json"
+[ "$(echo "$json" | jq -r '.[1].latest_tag.name')" == "v1.1.0" ] || fail "latest tag: $json"
+[ "$(echo "$json" | jq -r '.[1].branch_tip')" == "null" ] || fail "pinned branch tip: $json"
+tsv=$("$RUST_CROSS" outdated --format tsv)
+echo "$tsv" | grep -q "^vendor/docs	[0-9a-f]*	$docs_commit	v1.1.0	2$" || fail "tsv: $tsv"
+
+[ -z "$(git tag)" ] || fail "outdated created local tags: $(git tag)"
+
+log_header "Testing Rust 'outdated' after sync..."
+"$RUST_CROSS" sync vendor/docs >/dev/null
+"$RUST_CROSS" outdated | grep -q "^vendor/docs (demo/main): up to date" || fail "still outdated after sync"
+
+log_success "Rust outdated tests passed!"
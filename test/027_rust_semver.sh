@@ -0,0 +1,71 @@
+#!/usr/bin/env bash
+source "$(dirname "$0")/common.sh"
+
+# Initialize sandbox
+setup_sandbox
+cd "$SANDBOX"
+
+RUST_CROSS="$REPO_ROOT/src-rust/target/debug/git-cross-rust"
+if [ ! -f "$RUST_CROSS" ]; then
+    (cd "$REPO_ROOT/src-rust" && cargo build)
+fi
+
+# Setup upstream
+upstream_path=$(create_upstream "rust-semver")
+upstream_url="file://$upstream_path"
+
+release() {
+    mkdir -p "$upstream_path/docs"
+    echo "$1" > "$upstream_path/docs/VERSION"
+    git -C "$upstream_path" add docs
+    git -C "$upstream_path" commit -q -m "Release $1"
+    git -C "$upstream_path" tag "$1"
+}
+for v in v1.3.0 v1.4.0 v1.4.5 v1.5.0 v2.0.0 v2.0.3 v2.0.7 v2.1.0; do release $v; done
+
+"$RUST_CROSS" use demo "$upstream_url"
+
+log_header "Testing Rust 'patch' with version ranges..."
+"$RUST_CROSS" patch 'demo:^1.4:docs' vendor/caret
+grep -q "v1.5.0" vendor/caret/VERSION || fail "^1.4 did not resolve to v1.5.0: $(cat vendor/caret/VERSION)"
+"$RUST_CROSS" patch 'demo:~2.0.3:docs' vendor/tilde
+grep -q "v2.0.7" vendor/tilde/VERSION || fail "~2.0.3 did not resolve to v2.0.7: $(cat vendor/tilde/VERSION)"
+
+[ "$(jq -r '.patches[0].ref_kind' .git/cross/metadata.json)" == "range" ] || fail "ref kind not recorded"
+[ "$(jq -r '.patches[0].branch' .git/cross/metadata.json)" == "^1.4" ] || fail "range not recorded"
+[ "$(jq -r '.patches[0].tag' .git/cross/metadata.json)" == "v1.5.0" ] || fail "resolved tag not recorded"
+[ "$(jq -r '.patches[0].tag' Crossfile.lock)" == "v1.5.0" ] || fail "lock does not record the tag"
+grep -q "demo:^1.4:docs" Crossfile || fail "Crossfile does not record the range: $(cat Crossfile)"
+
+if "$RUST_CROSS" patch 'demo:^9:docs' vendor/none 2>/dev/null; then
+    fail "range without matching tag should fail"
+fi
+
+log_header "Testing Rust 'status' shows tag and range..."
+out=$("$RUST_CROSS" status)
+echo "$out" | grep -q "v1.5.0 (^1.4), latest tag v2.1.0" || fail "status: $out"
+echo "$out" | grep -q "v2.0.7 (~2.0.3), latest tag v2.1.0" || fail "status: $out"
+
+log_header "Testing Rust 'sync' moves to the highest matching tag..."
+release v1.6.0
+release v2.0.9
+out=$("$RUST_CROSS" outdated)
+echo "$out" | grep -q "^vendor/caret (v1.5.0 (^1.4)): .*, v1.6.0 matches ^1.4, latest tag v2.1.0$" \
+    || fail "outdated: $out"
+
+"$RUST_CROSS" sync >/dev/null || fail "sync failed"
+grep -q "v1.6.0" vendor/caret/VERSION || fail "^1.4 did not move to v1.6.0"
+grep -q "v2.0.9" vendor/tilde/VERSION || fail "~2.0.3 did not move to v2.0.9"
+[ "$(jq -r '.patches[0].tag' .git/cross/metadata.json)" == "v1.6.0" ] || fail "metadata tag not updated"
+[ "$(jq -r '.patches[0].tag' Crossfile.lock)" == "v1.6.0" ] || fail "lock tag not updated"
+out=$("$RUST_CROSS" status)
+echo "$out" | grep -q "v1.6.0 (^1.4), latest tag v2.1.0" || fail "status after sync: $out"
+
+log_header "Testing Rust 'replay' reproduces the locked tag..."
+release v1.7.0
+rm -rf vendor .git/cross && git worktree prune
+"$RUST_CROSS" replay >/dev/null || fail "replay failed"
+grep -q "v1.6.0" vendor/caret/VERSION || fail "replay ignored the lock"
+[ "$(jq -r '.patches[0].tag' .git/cross/metadata.json)" == "v1.6.0" ] || fail "replay lost the tag"
+
+log_success "Rust semver range tests passed!"
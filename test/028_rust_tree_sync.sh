@@ -0,0 +1,72 @@
+#!/usr/bin/env bash
+source "$(dirname "$0")/common.sh"
+
+# Initialize sandbox
+setup_sandbox
+cd "$SANDBOX"
+
+RUST_CROSS="$REPO_ROOT/src-rust/target/debug/git-cross-rust"
+if [ ! -f "$RUST_CROSS" ]; then
+    (cd "$REPO_ROOT/src-rust" && cargo build)
+fi
+
+# The Rust CLI must not need rsync
+mkdir -p "$SANDBOX/no-rsync"
+printf '#!/bin/sh\necho "rsync must not be called" >&2\nexit 1\n' > "$SANDBOX/no-rsync/rsync"
+chmod +x "$SANDBOX/no-rsync/rsync"
+export PATH="$SANDBOX/no-rsync:$PATH"
+
+# Setup upstream
+upstream_path=$(create_upstream "rust-tree-sync")
+upstream_url="file://$upstream_path"
+
+mkdir -p "$upstream_path/pkg/bin" "$upstream_path/pkg/sub"
+echo "abc" > "$upstream_path/pkg/a.txt"
+echo "deep" > "$upstream_path/pkg/sub/deep.txt"
+printf '#!/bin/sh\necho tool\n' > "$upstream_path/pkg/bin/tool.sh"
+chmod +x "$upstream_path/pkg/bin/tool.sh"
+ln -s a.txt "$upstream_path/pkg/link"
+git -C "$upstream_path" add pkg
+git -C "$upstream_path" commit -m "v1" -q
+git -C "$upstream_path" config receive.denyCurrentBranch ignore
+
+"$RUST_CROSS" use demo "$upstream_url"
+
+log_header "Testing Rust 'patch' without rsync..."
+out=$("$RUST_CROSS" patch demo:pkg vendor/pkg) || fail "patch failed: $out"
+echo "$out" | grep -q "Files: 4 added, 0 modified, 0 removed" || fail "patch summary: $out"
+echo "$out" | grep -q "^  A vendor/pkg/sub/deep.txt$" || fail "patch file list: $out"
+[ -x vendor/pkg/bin/tool.sh ] || fail "executable bit lost"
+[ ! -x vendor/pkg/a.txt ] || fail "a.txt should not be executable"
+[ -L vendor/pkg/link ] || fail "symlink not preserved"
+[ "$(readlink vendor/pkg/link)" == "a.txt" ] || fail "symlink target wrong"
+git add Crossfile Crossfile.lock vendor && git commit -q -m "Vendor pkg"
+
+log_header "Testing Rust 'push' compares content, not mtime..."
+stamp=$(stat -c %Y vendor/pkg/a.txt)
+echo "xyz" > vendor/pkg/a.txt
+touch -d "@$stamp" vendor/pkg/a.txt
+rm vendor/pkg/sub/deep.txt
+chmod -x vendor/pkg/bin/tool.sh
+ln -s bin/tool.sh vendor/pkg/tool
+git add -A vendor && git commit -q -m "Local changes"
+
+out=$("$RUST_CROSS" push vendor/pkg --yes --message "Push tree") || fail "push failed: $out"
+echo "$out" | grep -q "Files: 1 added, 2 modified, 1 removed" || fail "push summary: $out"
+git -C "$upstream_path" show main:pkg/a.txt | grep -q "xyz" || fail "same-size, same-mtime change not pushed"
+git -C "$upstream_path" cat-file -e main:pkg/sub/deep.txt 2>/dev/null && fail "deleted file still upstream"
+[ "$(git -C "$upstream_path" ls-tree main pkg/bin/tool.sh | cut -d' ' -f1)" == "100644" ] \
+    || fail "executable bit change not pushed"
+[ "$(git -C "$upstream_path" ls-tree main pkg/tool | cut -d' ' -f1)" == "120000" ] \
+    || fail "symlink not pushed as a symlink"
+
+log_header "Testing Rust 'sync' writes upstream symlinks as symlinks..."
+git -C "$upstream_path" reset -q --hard main
+ln -s sub "$upstream_path/pkg/sublink"
+git -C "$upstream_path" add pkg
+git -C "$upstream_path" commit -q -m "Add sublink"
+out=$("$RUST_CROSS" sync vendor/pkg) || fail "sync failed: $out"
+echo "$out" | grep -q "^  A vendor/pkg/sublink$" || fail "sync summary: $out"
+[ -L vendor/pkg/sublink ] || fail "upstream symlink not synced as a symlink"
+
+log_success "Rust tree sync tests passed!"
@@ -0,0 +1,90 @@
+#!/usr/bin/env bash
+source "$(dirname "$0")/common.sh"
+
+# Initialize sandbox
+setup_sandbox
+cd "$SANDBOX"
+
+RUST_CROSS="$REPO_ROOT/src-rust/target/debug/git-cross-rust"
+if [ ! -f "$RUST_CROSS" ]; then
+    (cd "$REPO_ROOT/src-rust" && cargo build)
+fi
+
+# The Rust CLI must not run the git binary; the test itself still uses it.
+mkdir -p "$SANDBOX/no-git"
+printf '#!/bin/sh\necho "git must not be called" >&2\nexit 1\n' > "$SANDBOX/no-git/git"
+chmod +x "$SANDBOX/no-git/git"
+cross() {
+    PATH="$SANDBOX/no-git:$PATH" GIT_CROSS_BACKEND=libgit2 "$RUST_CROSS" "$@"
+}
+
+# Setup upstream
+upstream_path=$(create_upstream "rust-libgit2")
+upstream_url="file://$upstream_path"
+
+mkdir -p "$upstream_path/docs" "$upstream_path/src"
+printf 'one\ntwo\nthree\n' > "$upstream_path/docs/guide.md"
+echo "src" > "$upstream_path/src/lib.rs"
+echo "MIT" > "$upstream_path/LICENSE"
+git -C "$upstream_path" add docs src LICENSE
+git -C "$upstream_path" commit -m "Add docs" -q
+git -C "$upstream_path" config receive.denyCurrentBranch ignore
+
+log_header "Testing Rust 'use' and 'patch' without the git binary..."
+cross use demo "$upstream_url" || fail "use failed"
+git remote get-url demo | grep -q "rust-libgit2" || fail "remote not added"
+cross patch demo:docs vendor/docs || fail "patch failed"
+cross patch demo:LICENSE vendor/LICENSE || fail "file patch failed"
+[ "$(cat vendor/docs/guide.md)" == "$(printf 'one\ntwo\nthree')" ] || fail "docs not vendored"
+grep -q "MIT" vendor/LICENSE || fail "LICENSE not vendored"
+git add Crossfile Crossfile.lock vendor && git commit -q -m "Vendor docs"
+
+wt=$(jq -r '.patches[] | select(.local_path == "vendor/docs") | .worktree' .git/cross/metadata.json)
+git worktree list | grep -q "$wt" || fail "worktree not registered"
+[ ! -e "$wt/src/lib.rs" ] || fail "worktree checked out outside the remote path"
+git -C "$wt" ls-files -v | grep -q "^S src/lib.rs$" || fail "excluded paths not skip-worktree"
+[ -z "$(git -C "$wt" status --porcelain)" ] || fail "worktree not clean: $(git -C "$wt" status --porcelain)"
+[ -z "$(git branch --list 'git-cross/*')" ] || fail "temporary branch left behind"
+
+log_header "Testing Rust 'status' and 'diff' without the git binary..."
+sed -i 's/^one$/ONE (local)/' vendor/docs/guide.md
+echo "local" > vendor/docs/notes.md
+[ "$(cross status --format json | jq -r '.[0].diff')" == "modified" ] || fail "status not modified"
+out=$(cross diff vendor/docs) || fail "diff failed"
+echo "$out" | grep -q "^+ONE (local)$" || fail "diff misses change: $out"
+echo "$out" | grep -q "^new file mode 100644$" || fail "diff misses added file: $out"
+git add -A vendor && git commit -q -m "Local edits"
+
+log_header "Testing Rust 'sync' merges without the git binary..."
+sed -i 's/^three$/THREE (upstream)/' "$upstream_path/docs/guide.md"
+git -C "$upstream_path" commit -am "Upstream edit" -q
+cross sync vendor/docs || fail "sync failed"
+grep -q "ONE (local)" vendor/docs/guide.md || fail "local change lost"
+grep -q "THREE (upstream)" vendor/docs/guide.md || fail "upstream change missing"
+[ "$(git -C "$wt" rev-parse HEAD)" == "$(git -C "$upstream_path" rev-parse HEAD)" ] \
+    || fail "worktree not moved to upstream"
+git add -A vendor && git commit -q -m "Sync docs"
+
+log_header "Testing Rust 'push' without the git binary..."
+out=$(cross push vendor/docs --yes --message "Push docs") || fail "push failed: $out"
+git -C "$upstream_path" show main:docs/guide.md | grep -q "ONE (local)" || fail "change not pushed"
+git -C "$upstream_path" cat-file -e main:docs/notes.md || fail "new file not pushed"
+[ "$(git -C "$upstream_path" log -1 --pretty=%s main)" == "Push docs" ] || fail "wrong commit message"
+
+log_header "Testing Rust 'push' refuses a non-fast-forward update..."
+echo "other" > "$upstream_path/docs/other.md"
+git -C "$upstream_path" add docs/other.md
+git -C "$upstream_path" commit -m "Upstream moved on" -q
+echo "more" >> vendor/docs/notes.md
+git add -A vendor && git commit -q -m "More local edits"
+cross push vendor/docs --yes && fail "non-fast-forward push succeeded"
+[ "$(git -C "$upstream_path" log -1 --pretty=%s main)" == "Upstream moved on" ] || fail "upstream overwritten"
+
+log_header "Testing Rust 'remove' and 'prune' without the git binary..."
+cross remove vendor/LICENSE || fail "remove failed"
+[ ! -e vendor/LICENSE ] || fail "LICENSE not removed"
+cross prune demo || fail "prune failed"
+git remote | grep -q "^demo$" && fail "remote not removed"
+[ -z "$(git worktree list | grep cross/worktrees)" ] || fail "worktrees left: $(git worktree list)"
+
+log_success "Rust libgit2 tests passed!"
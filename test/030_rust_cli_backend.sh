@@ -0,0 +1,25 @@
+#!/usr/bin/env bash
+source "$(dirname "$0")/common.sh"
+
+# Initialize sandbox
+setup_sandbox
+cd "$SANDBOX"
+
+RUST_CROSS="$REPO_ROOT/src-rust/target/debug/git-cross-rust"
+if [ ! -f "$RUST_CROSS" ]; then
+    (cd "$REPO_ROOT/src-rust" && cargo build)
+fi
+
+log_header "Testing GIT_CROSS_BACKEND rejects unknown backends..."
+out=$(GIT_CROSS_BACKEND=bogus "$RUST_CROSS" list 2>&1) && fail "unknown backend accepted"
+echo "$out" | grep -q "Unknown GIT_CROSS_BACKEND 'bogus'" || fail "unexpected error: $out"
+
+# The same scenarios as with libgit2, run through the git binary.
+for t in "$REPO_ROOT"/test/0*_rust_*.sh; do
+    name=$(basename "$t")
+    [ "$t" -ef "$0" ] && continue
+    log_header "Running $name with the cli backend..."
+    (cd "$REPO_ROOT" && GIT_CROSS_BACKEND=cli bash "$t") || fail "$name failed with the cli backend"
+done
+
+log_success "Rust cli backend tests passed!"
@@ -0,0 +1,57 @@
+#!/usr/bin/env bash
+source "$(dirname "$0")/common.sh"
+
+# Initialize sandbox
+setup_sandbox
+cd "$SANDBOX"
+
+RUST_CROSS="$REPO_ROOT/src-rust/target/debug/git-cross-rust"
+if [ ! -f "$RUST_CROSS" ]; then
+    (cd "$REPO_ROOT/src-rust" && cargo build)
+fi
+
+# expect_code <code> <args...>: run the Rust CLI and check its exit code.
+expect_code() {
+    local expected=$1 code=0 out
+    shift
+    out=$("$RUST_CROSS" "$@" 2>&1) || code=$?
+    [ "$code" -eq "$expected" ] || fail "'$*' exited $code, expected $expected: $out"
+    echo "$out"
+}
+
+# Setup upstream
+upstream_path=$(create_upstream "rust-exit-codes")
+upstream_url="file://$upstream_path"
+mkdir -p "$upstream_path/docs"
+echo "v1" > "$upstream_path/docs/guide.md"
+git -C "$upstream_path" add docs
+git -C "$upstream_path" commit -m "docs" -q
+
+"$RUST_CROSS" use demo "$upstream_url"
+"$RUST_CROSS" patch demo:docs vendor/docs
+
+log_header "Testing Rust exit codes of typed errors..."
+expect_code 2 no-such-command >/dev/null
+out=$(expect_code 3 patch "demo:")
+echo "$out" | grep -q "Invalid spec 'demo:'" || fail "invalid spec message: $out"
+expect_code 4 patch nope:docs vendor/nope >/dev/null
+out=$(expect_code 5 remove vendor/none)
+echo "$out" | grep -q "Patch not found for path: vendor/none" || fail "unknown patch message: $out"
+
+log_header "Testing Rust exit code of a rejected push..."
+# The upstream has main checked out, so pushing to it is refused.
+echo "local" >> vendor/docs/guide.md
+git add vendor/docs && git commit -qm "local change"
+expect_code 21 push vendor/docs --yes --message "Push" >/dev/null
+
+log_header "Testing Rust exit code of a missing worktree..."
+rm -rf "$(jq -r '.patches[0].worktree' .git/cross/metadata.json)"
+out=$(expect_code 6 sync vendor/docs)
+echo "$out" | grep -q "Worktree not found for vendor/docs" || fail "missing worktree message: $out"
+
+log_header "Testing Rust exit code of corrupt metadata..."
+echo "{" > .git/cross/metadata.json
+out=$(expect_code 7 list)
+echo "$out" | grep -q "metadata.json is corrupt" || fail "corrupt metadata message: $out"
+
+log_success "Rust exit code tests passed!"
@@ -0,0 +1,50 @@
+#!/usr/bin/env bash
+source "$(dirname "$0")/common.sh"
+
+# Initialize sandbox
+setup_sandbox
+cd "$SANDBOX"
+
+RUST_CROSS="$REPO_ROOT/src-rust/target/debug/git-cross-rust"
+if [ ! -f "$RUST_CROSS" ]; then
+    (cd "$REPO_ROOT/src-rust" && cargo build)
+fi
+
+# Setup upstream
+upstream_path=$(create_upstream "rust-lock")
+upstream_url="file://$upstream_path"
+mkdir -p "$upstream_path/docs"
+echo "v1" > "$upstream_path/docs/guide.md"
+git -C "$upstream_path" add docs
+git -C "$upstream_path" commit -m "docs" -q
+
+"$RUST_CROSS" use demo "$upstream_url"
+lock=.git/cross/operation.lock
+[ -f "$lock" ] && [ ! -s "$lock" ] || fail "lock file not released after 'use'"
+
+log_header "Testing Rust operations fail while another one runs..."
+# flock(1) takes the same advisory lock as a running cross process.
+echo 4242 > "$lock"
+code=0
+out=$(flock "$lock" "$RUST_CROSS" patch demo:docs vendor/docs 2>&1) || code=$?
+[ "$code" -eq 9 ] || fail "locked patch exited $code, expected 9: $out"
+echo "$out" | grep -q "another cross operation is running (pid 4242)" || fail "lock message: $out"
+[ ! -e vendor/docs ] || fail "locked patch changed the repository"
+
+# Read-only commands do not take the lock.
+flock "$lock" "$RUST_CROSS" list >/dev/null || fail "list failed while locked"
+
+log_header "Testing Rust takes over stale locks..."
+# The pid is still in the file, but the lock was released when flock exited.
+out=$("$RUST_CROSS" patch demo:docs vendor/docs) || fail "patch with stale lock failed: $out"
+echo "$out" | grep -q "stale lock of pid 4242" || fail "stale lock not reported: $out"
+[ ! -s "$lock" ] || fail "lock file not emptied: $(cat "$lock")"
+assert_file_exists vendor/docs/guide.md
+
+log_header "Testing Rust metadata writes leave no temporary files..."
+leftovers=$(find .git/cross -maxdepth 1 -name '.tmp*')
+[ -z "$leftovers" ] || fail "temporary files left: $leftovers"
+[ "$(jq -r '.patches[0].local_path' .git/cross/metadata.json)" == "vendor/docs" ] \
+    || fail "metadata not written"
+
+log_success "Rust operation lock tests passed!"
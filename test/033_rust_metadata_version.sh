@@ -0,0 +1,75 @@
+#!/usr/bin/env bash
+source "$(dirname "$0")/common.sh"
+
+# Initialize sandbox
+setup_sandbox
+cd "$SANDBOX"
+
+RUST_CROSS="$REPO_ROOT/src-rust/target/debug/git-cross-rust"
+if [ ! -f "$RUST_CROSS" ]; then
+    (cd "$REPO_ROOT/src-rust" && cargo build)
+fi
+
+# Setup upstream
+upstream_path=$(create_upstream "rust-metadata-version")
+upstream_url="file://$upstream_path"
+mkdir -p "$upstream_path/docs"
+echo "v1" > "$upstream_path/docs/guide.md"
+git -C "$upstream_path" add docs
+git -C "$upstream_path" commit -m "docs" -q
+
+"$RUST_CROSS" use demo "$upstream_url"
+"$RUST_CROSS" patch demo:docs vendor/docs
+meta=.git/cross/metadata.json
+[ "$(jq -r '.version' "$meta")" == "2" ] || fail "metadata has no version: $(cat "$meta")"
+
+log_header "Testing Rust upgrades unversioned metadata..."
+id=$(jq -r '.patches[0].id' "$meta")
+jq 'del(.version) | del(.patches[0].id)' "$meta" > "$meta.tmp" && mv "$meta.tmp" "$meta"
+old=$(cat "$meta")
+out=$("$RUST_CROSS" list) || fail "list failed on old metadata: $out"
+echo "$out" | grep -q "vendor/docs" || fail "list lost the patch: $out"
+[ "$(cat "$meta")" == "$old" ] || fail "list rewrote the metadata: $(cat "$meta")"
+out=$("$RUST_CROSS" --dry sync) || fail "dry sync failed on old metadata: $out"
+echo "$out" | grep -q "upgrade from version 0 to 2" || fail "dry sync did not list the upgrade: $out"
+[ "$(cat "$meta")" == "$old" ] && [ ! -f "$meta.v0.bak" ] || fail "--dry upgraded the metadata"
+out=$("$RUST_CROSS" sync) || fail "sync failed on old metadata: $out"
+[ "$(jq -r '.version' "$meta")" == "2" ] || fail "metadata not upgraded: $(cat "$meta")"
+[ "$(jq -r '.patches[0].id' "$meta")" == "$id" ] || fail "patch id not migrated: $(cat "$meta")"
+[ "$(cat "$meta.v0.bak")" == "$old" ] || fail "backup differs from the original"
+
+log_header "Testing Rust moves worktrees named after unstable ids..."
+# Version 1 named worktrees after a DefaultHasher id.
+worktree=$(jq -r '.patches[0].worktree' "$meta")
+[ "$(basename "$worktree")" == "demo_$(printf 'demo:main:docs' | sha256sum | cut -c1-16)" ] \
+    || fail "worktree not named after the SHA-256 id: $worktree"
+git worktree move "$worktree" .git/cross/worktrees/demo_1a2b3c4d
+jq '.version = 1 | .patches[0].id = "1a2b3c4d" | .patches[0].worktree = ".git/cross/worktrees/demo_1a2b3c4d"' \
+    "$meta" > "$meta.tmp" && mv "$meta.tmp" "$meta"
+v1=$(cat "$meta")
+out=$("$RUST_CROSS" status) || fail "status failed on version 1 metadata: $out"
+echo "$out" | grep -q "vendor/docs" || fail "status lost the patch: $out"
+"$RUST_CROSS" diff vendor/docs >/dev/null || fail "diff failed on version 1 metadata"
+[ "$(cat "$meta")" == "$v1" ] && [ -d .git/cross/worktrees/demo_1a2b3c4d ] \
+    || fail "read-only commands migrated the metadata"
+out=$("$RUST_CROSS" --dry sync) || fail "dry sync failed on version 1 metadata: $out"
+echo "$out" | grep -q "git worktree remove .*demo_1a2b3c4d" || fail "dry sync did not list the move: $out"
+[ "$(cat "$meta")" == "$v1" ] || fail "--dry migrated the metadata"
+out=$("$RUST_CROSS" sync) || fail "sync failed on version 1 metadata: $out"
+[ "$(jq -r '.patches[0].worktree' "$meta")" == "$worktree" ] || fail "worktree not moved: $(cat "$meta")"
+[ "$(jq -r '.patches[0].id' "$meta")" == "$id" ] || fail "patch id not migrated: $(cat "$meta")"
+[ -d .git/cross/worktrees/demo_1a2b3c4d ] && fail "old worktree left behind"
+git worktree list | grep -q "$(basename "$worktree")" || fail "moved worktree not registered: $(git worktree list)"
+assert_file_exists "$worktree/docs/guide.md"
+[ -f "$meta.v1.bak" ] || fail "no backup of the version 1 metadata"
+
+log_header "Testing Rust refuses newer metadata..."
+jq '.version = 99' "$meta" > "$meta.tmp" && mv "$meta.tmp" "$meta"
+newer=$(cat "$meta")
+code=0
+out=$("$RUST_CROSS" status 2>&1) || code=$?
+[ "$code" -eq 7 ] || fail "status on newer metadata exited $code, expected 7: $out"
+echo "$out" | grep -q "schema version 99, this build supports up to 2" || fail "version message: $out"
+[ "$(cat "$meta")" == "$newer" ] || fail "newer metadata was modified"
+
+log_success "Rust metadata version tests passed!"
@@ -0,0 +1,72 @@
+#!/usr/bin/env bash
+source "$(dirname "$0")/common.sh"
+
+# Initialize sandbox
+setup_sandbox
+cd "$SANDBOX"
+
+RUST_CROSS="$REPO_ROOT/src-rust/target/debug/git-cross-rust"
+if [ ! -f "$RUST_CROSS" ]; then
+    (cd "$REPO_ROOT/src-rust" && cargo build)
+fi
+
+# Setup upstream
+upstream_path=$(create_upstream "rust-doctor")
+upstream_url="file://$upstream_path"
+mkdir -p "$upstream_path/docs" "$upstream_path/src"
+echo "guide" > "$upstream_path/docs/guide.md"
+echo "code" > "$upstream_path/src/main.c"
+git -C "$upstream_path" add .
+git -C "$upstream_path" commit -m "docs and src" -q
+
+"$RUST_CROSS" use demo "$upstream_url"
+"$RUST_CROSS" patch demo:docs vendor/docs
+"$RUST_CROSS" patch demo:src vendor/src
+
+log_header "Testing Rust 'doctor' on a consistent repository..."
+out=$("$RUST_CROSS" doctor) || fail "doctor failed: $out"
+echo "$out" | grep -q "No problems found." || fail "doctor: $out"
+
+log_header "Breaking the state..."
+docs_wt=$(jq -r '.patches[] | select(.local_path == "vendor/docs") | .worktree' .git/cross/metadata.json)
+rm -rf "$docs_wt"
+git remote remove demo
+grep -v "vendor/src" Crossfile > Crossfile.tmp && mv Crossfile.tmp Crossfile
+echo "cross patch demo:main:lib vendor/lib" >> Crossfile
+mkdir -p .git/cross/worktrees/junk
+
+log_header "Testing Rust 'doctor' reports every finding..."
+code=0
+out=$("$RUST_CROSS" doctor 2>&1) || code=$?
+[ "$code" -eq 1 ] || fail "doctor with errors exited $code: $out"
+echo "$out" | grep -q "^error    demo: remote is not configured in git (fixable)" || fail "remote: $out"
+echo "$out" | grep -q "^error    vendor/docs: worktree $docs_wt is missing (fixable)" || fail "worktree: $out"
+echo "$out" | grep -q "^warning  vendor/src: patch is not recorded in the Crossfile (fixable)" || fail "Crossfile: $out"
+echo "$out" | grep -q "^warning  vendor/lib: Crossfile patch has no metadata$" || fail "metadata: $out"
+echo "$out" | grep -q "hint: run 'cross replay' to vendor it" || fail "hint: $out"
+echo "$out" | grep -q "^warning  .git/cross/worktrees/junk: directory is neither a worktree nor used by any patch (fixable)" \
+    || fail "orphan: $out"
+echo "$out" | grep -q "cross doctor --fix' to repair 4 finding(s)" || fail "fix summary: $out"
+json=$("$RUST_CROSS" doctor --format json 2>/dev/null) || true
+[ "$(echo "$json" | jq '[.[] | select(.severity == "error")] | length')" == "2" ] || fail "json: $json"
+
+log_header "Testing Rust 'doctor --fix --dry' only prints the repairs..."
+out=$("$RUST_CROSS" --dry doctor --fix 2>&1) || true
+echo "$out" | grep -q "git remote add demo $upstream_url" || fail "dry plan: $out"
+echo "$out" | grep -q "git worktree add --detach $docs_wt" || fail "dry plan: $out"
+[ ! -d "$docs_wt" ] || fail "--dry recreated the worktree"
+
+log_header "Testing Rust 'doctor --fix' repairs the state..."
+out=$("$RUST_CROSS" doctor --fix 2>&1) || fail "doctor --fix failed: $out"
+echo "$out" | grep -q "Remaining findings:" || fail "doctor --fix: $out"
+[ "$(git remote get-url demo)" == "$upstream_url" ] || fail "remote not restored"
+assert_file_exists "$docs_wt/docs/guide.md"
+git worktree list | grep -q "$docs_wt" || fail "worktree not registered"
+grep -q "vendor/src" Crossfile || fail "Crossfile line not restored"
+[ ! -d .git/cross/worktrees/junk ] || fail "orphan directory left"
+
+out=$("$RUST_CROSS" doctor) || fail "doctor after fix failed: $out"
+[ "$(echo "$out" | grep -c '^\(error\|warning\)')" == "1" ] || fail "unexpected findings after fix: $out"
+"$RUST_CROSS" sync >/dev/null || fail "sync after fix failed"
+
+log_success "Rust doctor tests passed!"
@@ -0,0 +1,85 @@
+#!/usr/bin/env bash
+source "$(dirname "$0")/common.sh"
+
+# Initialize sandbox
+setup_sandbox
+cd "$SANDBOX"
+
+RUST_CROSS="$REPO_ROOT/src-rust/target/debug/git-cross-rust"
+if [ ! -f "$RUST_CROSS" ]; then
+    (cd "$REPO_ROOT/src-rust" && cargo build)
+fi
+
+# Setup upstream
+upstream_path=$(create_upstream "rust-repair")
+upstream_url="file://$upstream_path"
+mkdir -p "$upstream_path/docs" "$upstream_path/src"
+echo "guide" > "$upstream_path/docs/guide.md"
+echo "code" > "$upstream_path/src/main.c"
+git -C "$upstream_path" add .
+git -C "$upstream_path" commit -m "docs and src" -q
+
+"$RUST_CROSS" use demo "$upstream_url"
+"$RUST_CROSS" patch demo:docs vendor/docs
+"$RUST_CROSS" patch demo:src vendor/src
+echo "local fix" >> vendor/src/main.c
+git add -A
+git commit -m "Vendor docs and src" -q
+
+# Upstream moves on; repair has to use the locked commit.
+echo "newer guide" > "$upstream_path/docs/guide.md"
+git -C "$upstream_path" commit -am "newer docs" -q
+
+log_header "Cloning the project (no .git/cross, no remote)..."
+clone="$SANDBOX/clone"
+git clone -q "$SANDBOX" "$clone"
+cd "$clone"
+[ ! -e .git/cross ] || fail "clone already has .git/cross"
+
+log_header "Testing Rust 'repair --from-crossfile --dry' only prints the plan..."
+out=$("$RUST_CROSS" --dry repair --from-crossfile 2>&1) || fail "dry repair failed: $out"
+echo "$out" | grep -q "git remote add demo $upstream_url" || fail "dry plan: $out"
+[ ! -e .git/cross/metadata.json ] || fail "--dry wrote metadata"
+
+log_header "Testing Rust 'repair --from-crossfile' rebuilds metadata and worktrees..."
+before=$(git status --porcelain)
+out=$("$RUST_CROSS" repair --from-crossfile 2>&1) || fail "repair failed: $out"
+echo "$out" | grep -q "^pristine  vendor/docs (demo:main:docs)" || fail "docs state: $out"
+echo "$out" | grep -q "^modified  vendor/src (demo:main:src)" || fail "src state: $out"
+echo "$out" | grep -q "modified main.c" || fail "src files: $out"
+echo "$out" | grep -q "1 patch(es) differ from upstream" || fail "summary: $out"
+
+[ "$(git status --porcelain)" == "$before" ] || fail "repair touched the working tree: $(git status --porcelain)"
+grep -q "local fix" vendor/src/main.c || fail "local edit lost"
+grep -q "^guide$" vendor/docs/guide.md || fail "docs updated to upstream"
+[ "$(jq '.patches | length' .git/cross/metadata.json)" == "2" ] || fail "metadata: $(cat .git/cross/metadata.json)"
+docs_wt=$(jq -r '.patches[] | select(.local_path == "vendor/docs") | .worktree' .git/cross/metadata.json)
+grep -q "^guide$" "$docs_wt/docs/guide.md" || fail "worktree not at the locked commit"
+
+log_header "Testing the repaired state works with other commands..."
+out=$("$RUST_CROSS" doctor 2>&1) || fail "doctor after repair: $out"
+echo "$out" | grep -q "No problems found." || fail "doctor: $out"
+out=$("$RUST_CROSS" diff vendor/src 2>&1) || fail "diff failed: $out"
+echo "$out" | grep -q "+local fix" || fail "diff: $out"
+
+log_header "Testing a second repair has nothing to do..."
+json=$("$RUST_CROSS" repair --from-crossfile --format json 2>/dev/null) || fail "second repair failed"
+[ "$(echo "$json" | jq -r '[.[].state] | join(",")')" == "pristine,modified" ] || fail "json: $json"
+
+log_header "Testing patches without a Crossfile.lock entry are reported as unpinned..."
+unlocked="$SANDBOX/unlocked"
+git clone -q "$SANDBOX" "$unlocked"
+cd "$unlocked"
+git rm -q Crossfile.lock
+out=$("$RUST_CROSS" repair --from-crossfile 2>&1) || fail "repair without lock failed: $out"
+echo "$out" | grep -q "^unpinned  vendor/docs (demo:main:docs)" || fail "docs state: $out"
+echo "$out" | grep -q "2 patch(es) have no Crossfile.lock entry" || fail "summary: $out"
+[ "$(jq -r '[.patches[].base] | map(. == null) | all' .git/cross/metadata.json)" == "true" ] \
+    || fail "base recorded without a lock: $(cat .git/cross/metadata.json)"
+[ ! -e Crossfile.lock ] || fail "repair wrote a lock from the upstream tip"
+cd "$clone"
+
+log_header "Testing 'repair' requires --from-crossfile..."
+"$RUST_CROSS" repair >/dev/null 2>&1 && fail "repair without a source succeeded"
+
+echo "Rust repair test passed!"
@@ -0,0 +1,86 @@
+#!/usr/bin/env bash
+source "$(dirname "$0")/common.sh"
+
+# Initialize sandbox
+setup_sandbox
+cd "$SANDBOX"
+
+RUST_CROSS="$REPO_ROOT/src-rust/target/debug/git-cross-rust"
+if [ ! -f "$RUST_CROSS" ]; then
+    (cd "$REPO_ROOT/src-rust" && cargo build)
+fi
+
+# Setup upstream with a second branch
+upstream_path=$(create_upstream "rust-apply")
+upstream_url="file://$upstream_path"
+mkdir -p "$upstream_path/docs" "$upstream_path/src" "$upstream_path/lib"
+echo "guide" > "$upstream_path/docs/guide.md"
+echo "code" > "$upstream_path/src/main.c"
+echo "lib" > "$upstream_path/lib/lib.c"
+git -C "$upstream_path" add .
+git -C "$upstream_path" commit -m "docs, src and lib" -q
+git -C "$upstream_path" checkout -q -b dev
+echo "dev guide" > "$upstream_path/docs/guide.md"
+git -C "$upstream_path" commit -am "dev docs" -q
+git -C "$upstream_path" checkout -q main
+
+"$RUST_CROSS" use demo "$upstream_url"
+"$RUST_CROSS" patch demo:docs vendor/docs
+"$RUST_CROSS" patch demo:src vendor/src
+
+log_header "Testing Rust 'apply' on a matching repository..."
+out=$("$RUST_CROSS" apply --yes 2>&1) || fail "apply failed: $out"
+echo "$out" | grep -q "No changes: the repository matches the Crossfile." || fail "no changes: $out"
+
+log_header "Editing the Crossfile by hand..."
+cat > Crossfile <<CROSS
+# vendored dependencies
+cross use demo $upstream_url
+cross patch demo:dev:docs vendor/docs
+cross patch demo:lib vendor/lib
+CROSS
+cp Crossfile Crossfile.before
+echo "local" >> vendor/src/main.c
+
+log_header "Testing Rust 'apply' shows the plan and refuses to drop local edits..."
+code=0
+out=$("$RUST_CROSS" apply --yes 2>&1) || code=$?
+[ "$code" -eq 8 ] || fail "apply with local edits exited $code: $out"
+echo "$out" | grep -q "^- vendor/src (demo:main:src)" || fail "removal: $out"
+echo "$out" | grep -q "local edit discarded: modified main.c" || fail "local edit: $out"
+echo "$out" | grep -q "^~ vendor/docs: demo:main:docs -> demo:dev:docs" || fail "update: $out"
+echo "$out" | grep -q "^+ vendor/lib (demo:lib)" || fail "addition: $out"
+echo "$out" | grep -q "Plan: 1 to add, 1 to update, 1 to remove." || fail "summary: $out"
+grep -q "local" vendor/src/main.c || fail "refused apply changed files"
+[ ! -d vendor/lib ] || fail "refused apply added vendor/lib"
+
+log_header "Testing Rust 'apply --dry' prints the operations only, even with local edits..."
+out=$("$RUST_CROSS" --dry apply 2>&1) || fail "dry apply with local edits failed: $out"
+echo "$out" | grep -q "delete directory vendor/src" || fail "dry removal: $out"
+echo "$out" | grep -q "mirror .* -> vendor/lib/" || fail "dry addition: $out"
+[ -d vendor/src ] && [ ! -d vendor/lib ] || fail "--dry changed files"
+
+log_header "Testing Rust 'apply --force' converges to the Crossfile..."
+out=$("$RUST_CROSS" apply --yes --force 2>&1) || fail "apply failed: $out"
+[ ! -e vendor/src ] || fail "vendor/src not removed"
+grep -q "dev guide" vendor/docs/guide.md || fail "vendor/docs not on dev"
+[ -f vendor/lib/lib.c ] || fail "vendor/lib not added"
+cmp -s Crossfile Crossfile.before || fail "apply edited the Crossfile: $(cat Crossfile)"
+paths=$(jq -r '[.patches[].local_path] | sort | join(",")' .git/cross/metadata.json)
+[ "$paths" == "vendor/docs,vendor/lib" ] || fail "metadata: $paths"
+[ "$(jq -r '.patches[] | select(.local_path == "vendor/docs") | .branch' .git/cross/metadata.json)" == "dev" ] \
+    || fail "docs branch not updated"
+grep -q "vendor/src" Crossfile.lock && fail "Crossfile.lock still pins vendor/src"
+[ "$(git worktree list | grep -c .git/cross/worktrees)" -eq 2 ] || fail "worktrees: $(git worktree list)"
+
+json=$("$RUST_CROSS" apply --format json 2>/dev/null) || fail "apply json failed"
+[ "$json" == "[]" ] || fail "apply not idempotent: $json"
+
+log_header "Testing Rust 'apply' removes a remote nothing uses..."
+echo "# empty" > Crossfile
+out=$("$RUST_CROSS" apply --yes 2>&1) || fail "apply failed: $out"
+echo "$out" | grep -q "^- remote demo ($upstream_url)" || fail "remote removal: $out"
+git remote | grep -q "^demo$" && fail "remote demo still configured"
+[ ! -e vendor/docs ] && [ ! -e vendor/lib ] || fail "patches not removed"
+
+echo "Rust apply test passed!"
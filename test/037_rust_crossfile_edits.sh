@@ -0,0 +1,62 @@
+#!/usr/bin/env bash
+source "$(dirname "$0")/common.sh"
+
+# Initialize sandbox
+setup_sandbox
+cd "$SANDBOX"
+
+RUST_CROSS="$REPO_ROOT/src-rust/target/debug/git-cross-rust"
+if [ ! -f "$RUST_CROSS" ]; then
+    (cd "$REPO_ROOT/src-rust" && cargo build)
+fi
+
+# Setup upstream
+upstream_path=$(create_upstream "rust-crossfile-edits")
+upstream_url="file://$upstream_path"
+mkdir -p "$upstream_path/lib" "$upstream_path/lib-extra" "$upstream_path/docs"
+echo "lib" > "$upstream_path/lib/lib.c"
+echo "extra" > "$upstream_path/lib-extra/extra.c"
+echo "docs" > "$upstream_path/docs/guide.md"
+git -C "$upstream_path" add .
+git -C "$upstream_path" commit -m "lib, lib-extra and docs" -q
+
+cat > Crossfile <<CROSS
+# Vendored code, see README
+just cross use demo $upstream_url
+
+# the core library (patch vendor/lib by hand if needed)
+just cross patch demo:lib vendor/lib   # pinned by Crossfile.lock
+just cross patch demo:lib-extra vendor/lib-extra
+CROSS
+"$RUST_CROSS" replay >/dev/null 2>&1 || fail "replay failed"
+
+log_header "Testing Rust 'patch' keeps comments and prefix and does not duplicate lines..."
+"$RUST_CROSS" patch demo:docs vendor/docs >/dev/null 2>&1 || fail "patch failed"
+"$RUST_CROSS" patch demo:lib vendor/lib >/dev/null 2>&1 || fail "re-patch failed"
+"$RUST_CROSS" use demo "$upstream_url" >/dev/null 2>&1 || fail "use failed"
+expected="# Vendored code, see README
+just cross use demo $upstream_url
+
+# the core library (patch vendor/lib by hand if needed)
+just cross patch demo:lib vendor/lib   # pinned by Crossfile.lock
+just cross patch demo:lib-extra vendor/lib-extra
+just cross patch demo:main:docs vendor/docs"
+[ "$(cat Crossfile)" == "$expected" ] || fail "Crossfile after patch: $(cat Crossfile)"
+
+log_header "Testing Rust 'remove vendor/lib' keeps vendor/lib-extra and comments..."
+"$RUST_CROSS" remove vendor/lib >/dev/null 2>&1 || fail "remove failed"
+expected="# Vendored code, see README
+just cross use demo $upstream_url
+
+# the core library (patch vendor/lib by hand if needed)
+just cross patch demo:lib-extra vendor/lib-extra
+just cross patch demo:main:docs vendor/docs"
+[ "$(cat Crossfile)" == "$expected" ] || fail "Crossfile after remove: $(cat Crossfile)"
+[ -f vendor/lib-extra/extra.c ] || fail "vendor/lib-extra removed"
+
+log_header "Testing Rust 'use' with a new URL rewrites the use line..."
+"$RUST_CROSS" use demo "$upstream_url/" >/dev/null 2>&1 || fail "use failed"
+[ "$(grep -c "use demo" Crossfile)" -eq 1 ] || fail "duplicate use line: $(cat Crossfile)"
+grep -q "^just cross use demo $upstream_url/$" Crossfile || fail "use line not updated: $(cat Crossfile)"
+
+echo "Rust Crossfile edit test passed!"
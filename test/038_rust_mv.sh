@@ -0,0 +1,73 @@
+#!/usr/bin/env bash
+source "$(dirname "$0")/common.sh"
+
+# Initialize sandbox
+setup_sandbox
+cd "$SANDBOX"
+
+RUST_CROSS="$REPO_ROOT/src-rust/target/debug/git-cross-rust"
+if [ ! -f "$RUST_CROSS" ]; then
+    (cd "$REPO_ROOT/src-rust" && cargo build)
+fi
+
+# Setup upstream
+upstream_path=$(create_upstream "rust-mv")
+upstream_url="file://$upstream_path"
+mkdir -p "$upstream_path/docs" "$upstream_path/lib"
+echo "guide" > "$upstream_path/docs/guide.md"
+echo "lib" > "$upstream_path/lib/lib.c"
+git -C "$upstream_path" add .
+git -C "$upstream_path" commit -m "docs and lib" -q
+
+"$RUST_CROSS" use demo "$upstream_url"
+"$RUST_CROSS" patch demo:docs vendor/docs
+"$RUST_CROSS" patch demo:lib vendor/lib
+# Keep the Crossfile line without a ref, with a comment.
+sed -i 's|^cross patch demo:main:docs vendor/docs$|cross patch demo:docs vendor/docs  # user guide|' Crossfile
+git add -A
+git commit -m "Vendor docs and lib" -q
+docs_wt=$(jq -r '.patches[] | select(.local_path == "vendor/docs") | .worktree' .git/cross/metadata.json)
+
+# A staged edit and an untracked file have to move along.
+echo "staged" >> vendor/docs/guide.md
+git add vendor/docs/guide.md
+echo "notes" > vendor/docs/notes.txt
+
+log_header "Testing Rust 'mv' refuses other patches' paths..."
+out=$("$RUST_CROSS" mv vendor/docs vendor/lib 2>&1) && fail "moved onto another patch"
+echo "$out" | grep -q "vendor/lib is the local path of another patch" || fail "error: $out"
+out=$("$RUST_CROSS" mv vendor/docs vendor/lib/docs 2>&1) && fail "moved into another patch"
+echo "$out" | grep -q "would nest with patch vendor/lib" || fail "error: $out"
+out=$("$RUST_CROSS" mv vendor/missing docs 2>&1) && fail "moved an unknown patch"
+[ -d vendor/docs ] || fail "refused move changed files"
+
+log_header "Testing Rust 'mv --dry' only prints the plan..."
+out=$("$RUST_CROSS" --dry mv vendor/docs third_party/docs 2>&1) || fail "dry mv failed: $out"
+echo "$out" | grep -q "git mv vendor/docs third_party/docs" || fail "dry plan: $out"
+[ -d vendor/docs ] && [ ! -e third_party ] || fail "--dry moved files"
+
+log_header "Testing Rust 'mv' moves files, index, metadata, lock and Crossfile..."
+out=$("$RUST_CROSS" mv vendor/docs third_party/docs 2>&1) || fail "mv failed: $out"
+[ ! -e vendor/docs ] || fail "vendor/docs still exists"
+[ -f third_party/docs/notes.txt ] || fail "untracked file not moved"
+staged=$(git diff --cached --name-status)
+echo "$staged" | grep -q "^D.vendor/docs/guide.md$" || fail "old index entry kept: $staged"
+echo "$staged" | grep -q "^A.third_party/docs/guide.md$" || fail "index not renamed: $staged"
+echo "$staged" | grep -q "third_party/docs/notes.txt" && fail "untracked file was staged: $staged"
+[ "$(git show :third_party/docs/guide.md | tail -1)" == "staged" ] || fail "staged content lost"
+
+grep -q "^cross patch demo:docs third_party/docs  # user guide$" Crossfile || fail "Crossfile: $(cat Crossfile)"
+grep -q "vendor/docs" Crossfile && fail "old path left in Crossfile"
+grep -q "vendor/lib" Crossfile || fail "vendor/lib lost from Crossfile"
+[ "$(jq -r '.patches[] | select(.local_path == "third_party/docs") | .worktree' .git/cross/metadata.json)" == "$docs_wt" ] \
+    || fail "metadata: $(cat .git/cross/metadata.json)"
+grep -q "third_party/docs" Crossfile.lock || fail "Crossfile.lock not updated"
+grep -q "vendor/docs" Crossfile.lock && fail "Crossfile.lock still has vendor/docs"
+
+log_header "Testing the moved patch keeps working..."
+out=$("$RUST_CROSS" diff third_party/docs 2>&1) || fail "diff failed: $out"
+echo "$out" | grep -q "+staged" || fail "diff: $out"
+out=$("$RUST_CROSS" doctor 2>&1) || fail "doctor after mv: $out"
+echo "$out" | grep -q "No problems found." || fail "doctor: $out"
+
+echo "Rust mv test passed!"
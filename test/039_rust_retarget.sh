@@ -0,0 +1,82 @@
+#!/usr/bin/env bash
+source "$(dirname "$0")/common.sh"
+
+# Initialize sandbox
+setup_sandbox
+cd "$SANDBOX"
+
+RUST_CROSS="$REPO_ROOT/src-rust/target/debug/git-cross-rust"
+if [ ! -f "$RUST_CROSS" ]; then
+    (cd "$REPO_ROOT/src-rust" && cargo build)
+fi
+
+# Setup upstream
+upstream_path=$(create_upstream "rust-retarget")
+upstream_url="file://$upstream_path"
+mkdir -p "$upstream_path/docs"
+printf "one\ntwo\nthree\nfour\nfive\n" > "$upstream_path/docs/guide.md"
+echo "faq" > "$upstream_path/docs/faq.md"
+git -C "$upstream_path" add .
+git -C "$upstream_path" commit -m "docs" -q
+
+"$RUST_CROSS" use demo "$upstream_url"
+"$RUST_CROSS" patch demo:docs vendor/docs
+sed -i 's|^cross patch demo:main:docs vendor/docs$|cross patch demo:main:docs vendor/docs  # user guide|' Crossfile
+git add -A
+git commit -m "Vendor docs" -q
+old_wt=$(jq -r '.patches[0].worktree' .git/cross/metadata.json)
+
+# Local edits: one line of guide.md and a new file.
+sed -i 's/^one$/ONE (local)/' vendor/docs/guide.md
+echo "ours" > vendor/docs/local.md
+
+# Upstream moves docs/ to manual/ and changes another line of guide.md.
+git -C "$upstream_path" mv docs manual
+sed -i 's/^five$/five (upstream)/' "$upstream_path/manual/guide.md"
+echo "new" > "$upstream_path/manual/new.md"
+git -C "$upstream_path" add .
+git -C "$upstream_path" commit -m "Move docs to manual" -q
+
+log_header "Testing Rust 'retarget' refuses unknown patches and the current source..."
+out=$("$RUST_CROSS" retarget vendor/missing demo:main:manual 2>&1) && fail "retargeted an unknown patch"
+out=$("$RUST_CROSS" retarget vendor/docs demo:main:docs 2>&1) && fail "retargeted to the same source"
+echo "$out" | grep -q "already tracks demo:main:docs" || fail "error: $out"
+
+log_header "Testing Rust 'retarget --dry' only prints the plan..."
+out=$("$RUST_CROSS" --dry retarget vendor/docs demo:main:manual 2>&1) || fail "dry retarget failed: $out"
+echo "$out" | grep -q "retarget vendor/docs -> demo:main:manual" || fail "dry plan: $out"
+grep -q "demo:main:docs" Crossfile || fail "--dry changed the Crossfile"
+[ ! -e vendor/docs/new.md ] || fail "--dry wrote files"
+
+log_header "Testing Rust 'retarget' follows a moved directory and keeps local edits..."
+out=$("$RUST_CROSS" retarget vendor/docs demo:main:manual 2>&1) || fail "retarget failed: $out"
+grep -q "^ONE (local)$" vendor/docs/guide.md || fail "local edit lost: $(cat vendor/docs/guide.md)"
+grep -q "^five (upstream)$" vendor/docs/guide.md || fail "upstream edit missing: $(cat vendor/docs/guide.md)"
+grep -q "<<<<<<<" vendor/docs/guide.md && fail "unexpected conflict: $(cat vendor/docs/guide.md)"
+[ "$(cat vendor/docs/local.md)" == "ours" ] || fail "local file lost"
+[ "$(cat vendor/docs/new.md)" == "new" ] || fail "new upstream file missing"
+[ "$(cat vendor/docs/faq.md)" == "faq" ] || fail "faq.md changed"
+
+grep -q "^cross patch demo:main:manual vendor/docs  # user guide$" Crossfile || fail "Crossfile: $(cat Crossfile)"
+[ "$(jq -r '.patches[0].remote_path' .git/cross/metadata.json)" == "manual" ] \
+    || fail "metadata: $(cat .git/cross/metadata.json)"
+[ ! -e "$old_wt" ] || fail "old worktree kept"
+grep -q "manual" Crossfile.lock || fail "Crossfile.lock not updated"
+
+log_header "Testing Rust 'retarget' to a fork leaves conflicts for overlapping edits..."
+fork_path="$SANDBOX/upstream/rust-retarget-fork"
+git clone -q "$upstream_path" "$fork_path"
+git -C "$fork_path" config user.email "fork@example.com"
+git -C "$fork_path" config user.name "Fork User"
+sed -i 's/^one$/one (fork)/' "$fork_path/manual/guide.md"
+git -C "$fork_path" commit -am "Fork edit" -q
+"$RUST_CROSS" use fork "file://$fork_path"
+out=$("$RUST_CROSS" retarget vendor/docs fork:main:manual 2>&1)
+[ $? -eq 20 ] || fail "expected conflict exit code 20: $out"
+echo "$out" | grep -q "vendor/docs/guide.md" || fail "conflict not reported: $out"
+grep -q "<<<<<<<" vendor/docs/guide.md || fail "no conflict markers: $(cat vendor/docs/guide.md)"
+[ "$(jq -r '.patches[0].remote' .git/cross/metadata.json)" == "fork" ] || fail "patch not retargeted"
+out=$("$RUST_CROSS" status 2>&1) || true
+echo "$out" | grep -q "vendor/docs" || fail "status: $out"
+
+echo "Rust retarget test passed!"